@@ -0,0 +1,185 @@
+use crate::rtttl::Tune;
+use chrono::{DateTime, Duration, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// Maximum number of alarms kept in the settings.
+pub const MAX_ALARMS: usize = 4;
+
+/// Minutes a snoozed alarm waits before ringing again.
+pub const SNOOZE_MINUTES: i64 = 9;
+
+/// Minutes an alarm or timer rings before stopping on its own.
+pub const RING_MINUTES: i64 = 10;
+
+/// Longest countdown timer, in minutes, so it fits the `MM:SS` display.
+pub const MAX_TIMER_MINUTES: u32 = 99;
+
+/// Type alias for the shared [Scheduler].
+/// This is an ´Arc<Mutex<>>´ to ensure thread safety and shared access to the alarms and the timer.
+pub type Alarms = Arc<Mutex<Scheduler>>;
+
+/// A daily alarm, set in local time.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct Alarm {
+    pub hour: u8,
+    pub minute: u8,
+    pub enabled: bool,
+    /// The melody played by the buzzer while the alarm rings.
+    pub tune: Tune,
+}
+
+impl Default for Alarm {
+    fn default() -> Self {
+        Alarm {
+            hour: 7,
+            minute: 0,
+            enabled: false,
+            tune: Tune::default(),
+        }
+    }
+}
+
+impl Alarm {
+    /// Whether the hour and minute form a valid time of day.
+    pub fn is_valid(&self) -> bool {
+        self.hour < 24 && self.minute < 60
+    }
+}
+
+/// What is currently ringing.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Ringing {
+    /// The alarm at this index of the settings.
+    Alarm(usize),
+    /// The countdown timer reached zero.
+    Timer,
+}
+
+/// Decides when the alarms and the countdown timer ring.
+///
+/// The scheduler holds no hardware, the main loop feeds it the current time with
+/// [Scheduler::tick] and renders [Scheduler::ringing] however the clock can.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    ringing: Option<Ringing>,
+    ring_until: Option<DateTime<Utc>>,
+    snoozed: Option<(usize, DateTime<Utc>)>,
+    timer_end: Option<DateTime<Utc>>,
+    last_minute: Option<(u32, u32)>,
+}
+
+impl Scheduler {
+    /// Returns what is ringing, if anything.
+    pub fn ringing(&self) -> Option<Ringing> {
+        self.ringing
+    }
+
+    /// Starts, or restarts, the countdown timer.
+    ///
+    /// # Parameters
+    /// - `now`: The current time.
+    /// - `minutes`: The duration of the countdown, capped to [MAX_TIMER_MINUTES].
+    pub fn start_timer<Tz: TimeZone>(&mut self, now: &DateTime<Tz>, minutes: u32) {
+        let minutes = minutes.min(MAX_TIMER_MINUTES);
+        self.timer_end = Some(now.to_utc() + Duration::minutes(minutes.into()));
+    }
+
+    /// Returns the time left on the countdown timer, if one is running.
+    pub fn timer_remaining<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<Duration> {
+        self.timer_end
+            .map(|end| (end - now.to_utc()).max(Duration::zero()))
+    }
+
+    /// Silences what is ringing: an alarm rings again after [SNOOZE_MINUTES], a timer stops.
+    ///
+    /// # Returns
+    /// What was ringing, or `None` if nothing was.
+    pub fn snooze<Tz: TimeZone>(&mut self, now: &DateTime<Tz>) -> Option<Ringing> {
+        let ringing = self.ringing.take();
+        self.ring_until = None;
+
+        if let Some(Ringing::Alarm(index)) = ringing {
+            self.snoozed = Some((index, now.to_utc() + Duration::minutes(SNOOZE_MINUTES)));
+        }
+
+        ringing
+    }
+
+    /// Stops what is ringing and cancels any snoozed alarm and running timer.
+    ///
+    /// # Returns
+    /// `true` if anything was stopped or cancelled.
+    pub fn dismiss(&mut self) -> bool {
+        let active = self.ringing.is_some() || self.snoozed.is_some() || self.timer_end.is_some();
+
+        self.ringing = None;
+        self.ring_until = None;
+        self.snoozed = None;
+        self.timer_end = None;
+
+        active
+    }
+
+    /// Advances the scheduler to `now`, starting and stopping the rings that are due.
+    ///
+    /// Alarms are matched on the local hour and minute of `now`, once per minute, so
+    /// calling this more than once a minute is fine.
+    ///
+    /// # Parameters
+    /// - `now`: The current time, in the timezone the alarms are set in.
+    /// - `alarms`: The alarms from the settings.
+    ///
+    /// # Returns
+    /// What started ringing during this tick, if anything.
+    pub fn tick<Tz: TimeZone>(&mut self, now: &DateTime<Tz>, alarms: &[Alarm]) -> Option<Ringing> {
+        let utc = now.to_utc();
+
+        if self.ring_until.is_some_and(|until| utc >= until) {
+            log::info!("{:?} stopped ringing on its own", self.ringing);
+            self.ringing = None;
+            self.ring_until = None;
+        }
+
+        let mut due = None;
+
+        if self.timer_end.is_some_and(|end| utc >= end) {
+            self.timer_end = None;
+            due = Some(Ringing::Timer);
+        }
+
+        if let Some((index, until)) = self.snoozed {
+            if utc >= until {
+                self.snoozed = None;
+                due = Some(Ringing::Alarm(index));
+            }
+        }
+
+        let minute = (now.hour(), now.minute());
+        if self.last_minute != Some(minute) {
+            self.last_minute = Some(minute);
+            let matching = alarms.iter().position(|alarm| {
+                alarm.enabled
+                    && u32::from(alarm.hour) == minute.0
+                    && u32::from(alarm.minute) == minute.1
+            });
+            if let Some(index) = matching {
+                due = Some(Ringing::Alarm(index));
+            }
+        }
+
+        if due.is_some() {
+            self.ringing = due;
+            self.ring_until = Some(utc + Duration::minutes(RING_MINUTES));
+        }
+
+        due
+    }
+}
+
+/// Creates the shared [Alarms] scheduler, with nothing ringing and no timer running.
+pub fn get_alarms() -> Alarms {
+    Arc::new(Mutex::new(Scheduler::default()))
+}
@@ -0,0 +1,246 @@
+use crate::{
+    error::{self, AppError},
+    health,
+    rtttl::{Melody, Note},
+};
+use esp_idf_svc::{
+    hal::{
+        gpio::OutputPin,
+        ledc::{
+            config::TimerConfig, LedcChannel, LedcDriver, LedcTimer, LedcTimerDriver, LowSpeed,
+        },
+        peripheral::Peripheral,
+        prelude::*,
+    },
+    sys::{esp, ledc_mode_t_LEDC_LOW_SPEED_MODE, ledc_set_freq, ledc_timer_t},
+};
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+    thread,
+    time::Duration,
+};
+
+/// Frequency, in hertz, the PWM timer starts at.
+const BASE_FREQUENCY_HZ: u32 = 1000;
+
+/// Silence, in milliseconds, between two repetitions of a melody.
+const REPEAT_PAUSE_MS: u64 = 1000;
+
+/// Part, in percent, of each note during which the tone sounds, so repeated notes stay distinct.
+const NOTE_ON_PERCENT: u32 = 90;
+
+/// Number of requests waiting to be played before new ones are dropped.
+const QUEUE_LEN: usize = 4;
+
+/// Stack size of the thread playing the melodies.
+const BUZZER_STACK_SIZE: usize = 4096;
+
+/// A request to the player thread.
+enum Request {
+    Play {
+        melody: Melody,
+        volume: u8,
+        repeat: bool,
+    },
+    Stop,
+}
+
+/// Handle to the piezo buzzer, playing the melodies on a thread of its own.
+///
+/// Cloning is cheap, every clone sends its requests to the same player.
+#[derive(Clone)]
+pub struct Buzzer {
+    requests: SyncSender<Request>,
+}
+
+impl Buzzer {
+    /// Plays a melody, interrupting the one playing.
+    ///
+    /// # Parameters
+    /// - `melody`: The [Melody] to play.
+    /// - `volume`: The volume, from 0 to 100 percent.
+    /// - `repeat`: Whether the melody loops until [Buzzer::stop] is called.
+    pub fn play(&self, melody: Melody, volume: u8, repeat: bool) {
+        self.send(Request::Play {
+            melody,
+            volume,
+            repeat,
+        });
+    }
+
+    /// Silences the buzzer.
+    pub fn stop(&self) {
+        self.send(Request::Stop);
+    }
+
+    fn send(&self, request: Request) {
+        if let Err(TrySendError::Full(_)) = self.requests.try_send(request) {
+            log::warn!("Buzzer queue full, dropping request");
+        }
+    }
+}
+
+/// The LEDC channel driving the buzzer and the timer setting its pitch.
+struct Player {
+    driver: LedcDriver<'static>,
+    timer: ledc_timer_t,
+}
+
+impl Player {
+    /// Sounds a tone at `frequency` hertz, louder as `volume` gets closer to 100.
+    ///
+    /// A piezo is loudest with a square wave, so the volume scales the duty cycle up to 50%.
+    fn tone(&mut self, frequency: u32, volume: u8) -> Result<(), AppError> {
+        esp!(unsafe { ledc_set_freq(ledc_mode_t_LEDC_LOW_SPEED_MODE, self.timer, frequency) })?;
+
+        let duty = self.driver.get_max_duty() / 2 * u32::from(volume.min(100)) / 100;
+        self.driver.set_duty(duty)?;
+
+        Ok(())
+    }
+
+    fn silence(&mut self) -> Result<(), AppError> {
+        self.driver.set_duty(0)?;
+
+        Ok(())
+    }
+
+    /// Plays a note, returning early with the request received meanwhile, if any.
+    fn note(
+        &mut self,
+        note: &Note,
+        volume: u8,
+        requests: &Receiver<Request>,
+    ) -> Result<Option<Request>, AppError> {
+        let on_ms = note.duration_ms * NOTE_ON_PERCENT / 100;
+
+        match note.frequency {
+            Some(frequency) => self.tone(frequency, volume)?,
+            None => self.silence()?,
+        }
+        if let Some(request) = wait(requests, on_ms.into()) {
+            return Ok(Some(request));
+        }
+
+        self.silence()?;
+        Ok(wait(requests, (note.duration_ms - on_ms).into()))
+    }
+
+    /// Plays the requests forever, a new request interrupting the melody playing.
+    fn run(mut self, requests: Receiver<Request>) {
+        let mut pending = None;
+        let _heartbeat = health::track();
+
+        loop {
+            let request = match pending.take() {
+                Some(request) => request,
+                None => match requests.recv() {
+                    Ok(request) => request,
+                    Err(_) => return,
+                },
+            };
+            health::beat();
+
+            if let Request::Play {
+                melody,
+                volume,
+                repeat,
+            } = request
+            {
+                log::info!("Playing `{}`", melody.name);
+                'melody: loop {
+                    for note in &melody.notes {
+                        match self.note(note, volume, &requests) {
+                            Ok(Some(request)) => {
+                                pending = Some(request);
+                                break 'melody;
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                log::error!("Failed to play note: {:#?}", e);
+                                error::record(&e);
+                                break 'melody;
+                            }
+                        }
+                    }
+
+                    if !repeat {
+                        break;
+                    }
+                    if let Some(request) = wait(&requests, REPEAT_PAUSE_MS) {
+                        pending = Some(request);
+                        break;
+                    }
+                }
+            }
+
+            if let Err(e) = self.silence() {
+                log::error!("Failed to silence buzzer: {:#?}", e);
+                error::record(&e);
+            }
+        }
+    }
+}
+
+/// Waits up to `ms` milliseconds for a new request.
+fn wait(requests: &Receiver<Request>, ms: u64) -> Option<Request> {
+    match requests.recv_timeout(Duration::from_millis(ms)) {
+        Ok(request) => Some(request),
+        Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => None,
+    }
+}
+
+/// Creates the [Buzzer] and starts the thread playing its melodies.
+///
+/// The pitch is set by changing the frequency of the LEDC timer, so the timer must
+/// not be shared with other channels.
+///
+/// # Parameters
+/// - `timer`: The LEDC timer dedicated to the buzzer.
+/// - `channel`: The LEDC channel driving the buzzer.
+/// - `pin`: The pin the piezo is wired to (implements [OutputPin]).
+///
+/// # Returns
+/// The [Buzzer] handle, or an [AppError] if the LEDC setup or the thread fails.
+///
+/// # Example
+/// ```rust
+/// let buzzer = get_buzzer(ledc.timer0, ledc.channel0, pins.gpio27).expect("Failed to get buzzer");
+/// ```
+pub fn get_buzzer<T, C>(
+    timer: impl Peripheral<P = T> + 'static,
+    channel: impl Peripheral<P = C> + 'static,
+    pin: impl Peripheral<P = impl OutputPin> + 'static,
+) -> Result<Buzzer, AppError>
+where
+    T: LedcTimer<SpeedMode = LowSpeed> + 'static,
+    C: LedcChannel<SpeedMode = LowSpeed>,
+{
+    let timer_driver = LedcTimerDriver::new(
+        timer,
+        &TimerConfig::default().frequency(BASE_FREQUENCY_HZ.Hz()),
+    )?;
+    let timer = timer_driver.timer();
+
+    let mut driver = LedcDriver::new(channel, timer_driver, pin)?;
+    driver.set_duty(0)?;
+
+    let (requests, receiver) = mpsc::sync_channel(QUEUE_LEN);
+    let player = Player { driver, timer };
+
+    thread::Builder::new()
+        .name("buzzer".to_string())
+        .stack_size(BUZZER_STACK_SIZE)
+        .spawn(move || player.run(receiver))
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    Ok(Buzzer { requests })
+}
+
+/// Creates a [Buzzer] without a player, for a clock whose buzzer cannot be set up: its
+/// requests are dropped.
+pub fn silent_buzzer() -> Buzzer {
+    let (requests, _) = mpsc::sync_channel(QUEUE_LEN);
+
+    Buzzer { requests }
+}
@@ -0,0 +1,455 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Seconds each page of the [crate::display::DisplayMode::Climate] mode stays on the display.
+pub const PAGE_SECONDS: u32 = 3;
+
+/// Unit the temperature is shown in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    /// Returns the letter shown after the degree sign.
+    pub fn symbol(self) -> char {
+        match self {
+            TemperatureUnit::Celsius => 'C',
+            TemperatureUnit::Fahrenheit => 'F',
+        }
+    }
+}
+
+/// A sample of the climate sensor.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Reading {
+    /// Temperature, in [Reading::unit].
+    pub temperature: f32,
+    pub unit: TemperatureUnit,
+    /// Relative humidity, in percent, if the sensor measures it.
+    pub humidity: Option<f32>,
+    /// Air pressure, in hectopascals, if the sensor measures it.
+    pub pressure: Option<f32>,
+}
+
+impl Reading {
+    /// Creates a reading from a temperature in degrees Celsius.
+    pub fn celsius(temperature: f32, humidity: Option<f32>, pressure: Option<f32>) -> Self {
+        Reading {
+            temperature,
+            unit: TemperatureUnit::Celsius,
+            humidity,
+            pressure,
+        }
+    }
+
+    /// Converts the temperature to `unit` and rounds every value to one decimal, as published.
+    pub fn in_unit(self, unit: TemperatureUnit) -> Reading {
+        let round = |value: f32| (value * 10.0).round() / 10.0;
+        let temperature = match (self.unit, unit) {
+            (TemperatureUnit::Celsius, TemperatureUnit::Fahrenheit) => {
+                self.temperature * 9.0 / 5.0 + 32.0
+            }
+            (TemperatureUnit::Fahrenheit, TemperatureUnit::Celsius) => {
+                (self.temperature - 32.0) * 5.0 / 9.0
+            }
+            _ => self.temperature,
+        };
+
+        Reading {
+            temperature: round(temperature),
+            unit,
+            humidity: self.humidity.map(round),
+            pressure: self.pressure.map(round),
+        }
+    }
+}
+
+/// Errors returned when a sensor frame cannot be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SensorError {
+    /// The checksum sent by the sensor does not match the data.
+    Checksum,
+    /// The values decoded are outside of what the sensor can measure.
+    OutOfRange,
+}
+
+impl fmt::Display for SensorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SensorError::Checksum => write!(f, "checksum mismatch"),
+            SensorError::OutOfRange => write!(f, "value out of range"),
+        }
+    }
+}
+
+/// Returns the text showing the temperature on the 4-digit display, e.g. `23°C`.
+///
+/// The degree sign is dropped for three-digit values (`-12C`, `104F`), out-of-range
+/// values show as dashes.
+pub fn temperature_text(reading: &Reading) -> String {
+    let value = reading.temperature.round() as i32;
+    let symbol = reading.unit.symbol();
+
+    match value {
+        -9..=99 => format!("{value:>2}°{symbol}"),
+        -99..=999 => format!("{value}{symbol}"),
+        _ => "----".to_string(),
+    }
+}
+
+/// Returns the text showing the relative humidity on the 4-digit display, e.g. ` 45H`.
+pub fn humidity_text(humidity: f32) -> String {
+    format!("{:>3}H", humidity.round().clamp(0.0, 100.0) as u8)
+}
+
+/// Returns the text of the climate page shown at `second`, alternating between the
+/// temperature and, if measured, the humidity every [PAGE_SECONDS].
+pub fn page_text(reading: &Reading, second: u32) -> String {
+    match reading.humidity {
+        Some(humidity) if (second / PAGE_SECONDS) % 2 == 1 => humidity_text(humidity),
+        _ => temperature_text(reading),
+    }
+}
+
+/// Calibration of a BME280, read once from its non-volatile memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bme280Calibration {
+    t1: f64,
+    t2: f64,
+    t3: f64,
+    p: [f64; 9],
+    h1: f64,
+    h2: f64,
+    h3: f64,
+    h4: f64,
+    h5: f64,
+    h6: f64,
+}
+
+impl Bme280Calibration {
+    /// Decodes the calibration registers.
+    ///
+    /// # Parameters
+    /// - `low`: The 26 registers from `0x88` to `0xA1`.
+    /// - `high`: The 7 registers from `0xE1` to `0xE7`.
+    pub fn parse(low: &[u8; 26], high: &[u8; 7]) -> Self {
+        let unsigned = |i: usize| f64::from(u16::from_le_bytes([low[i], low[i + 1]]));
+        let signed = |i: usize| f64::from(i16::from_le_bytes([low[i], low[i + 1]]));
+
+        let mut p = [0.0; 9];
+        p[0] = unsigned(6);
+        for (n, value) in p.iter_mut().enumerate().skip(1) {
+            *value = signed(6 + 2 * n);
+        }
+
+        // H4 and H5 are 12-bit signed values sharing the nibbles of 0xE5
+        let h4 = i16::from(high[3] as i8) << 4 | i16::from(high[4] & 0x0f);
+        let h5 = i16::from(high[5] as i8) << 4 | i16::from(high[4] >> 4);
+
+        Bme280Calibration {
+            t1: unsigned(0),
+            t2: signed(2),
+            t3: signed(4),
+            p,
+            h1: f64::from(low[25]),
+            h2: f64::from(i16::from_le_bytes([high[0], high[1]])),
+            h3: f64::from(high[2]),
+            h4: f64::from(h4),
+            h5: f64::from(h5),
+            h6: f64::from(high[6] as i8),
+        }
+    }
+
+    /// Converts the raw measurement registers into a [Reading], with the floating-point
+    /// formulas of the datasheet.
+    ///
+    /// # Parameters
+    /// - `raw`: The 8 registers from `0xF7` to `0xFE`.
+    ///
+    /// # Returns
+    /// The [Reading] in degrees Celsius, or [SensorError::OutOfRange] if the sensor
+    /// has not measured yet.
+    pub fn compensate(&self, raw: &[u8; 8]) -> Result<Reading, SensorError> {
+        let twenty_bits = |i: usize| {
+            f64::from(
+                u32::from(raw[i]) << 12 | u32::from(raw[i + 1]) << 4 | u32::from(raw[i + 2]) >> 4,
+            )
+        };
+        let adc_p = twenty_bits(0);
+        let adc_t = twenty_bits(3);
+        let adc_h = f64::from(u16::from_be_bytes([raw[6], raw[7]]));
+
+        // The registers hold 0x80000 until the first measurement completes
+        if adc_t == f64::from(0x80000) {
+            return Err(SensorError::OutOfRange);
+        }
+
+        let (t1, t2, t3, p) = (self.t1, self.t2, self.t3, &self.p);
+        let var1 = (adc_t / 16384.0 - t1 / 1024.0) * t2;
+        let var2 = (adc_t / 131072.0 - t1 / 8192.0).powi(2) * t3;
+        let t_fine = var1 + var2;
+        let temperature = t_fine / 5120.0;
+
+        let mut var1 = t_fine / 2.0 - 64000.0;
+        let mut var2 = var1 * var1 * p[5] / 32768.0;
+        var2 += var1 * p[4] * 2.0;
+        var2 = var2 / 4.0 + p[3] * 65536.0;
+        var1 = (p[2] * var1 * var1 / 524288.0 + p[1] * var1) / 524288.0;
+        var1 = (1.0 + var1 / 32768.0) * p[0];
+        let pressure = if var1 == 0.0 {
+            None
+        } else {
+            let pascals = (1048576.0 - adc_p - var2 / 4096.0) * 6250.0 / var1;
+            let var1 = p[8] * pascals * pascals / 2147483648.0;
+            let var2 = pascals * p[7] / 32768.0;
+            Some((pascals + (var1 + var2 + p[6]) / 16.0) / 100.0)
+        };
+
+        let h = t_fine - 76800.0;
+        let h = (adc_h - (self.h4 * 64.0 + self.h5 / 16384.0 * h))
+            * (self.h2 / 65536.0
+                * (1.0 + self.h6 / 67108864.0 * h * (1.0 + self.h3 / 67108864.0 * h)));
+        let humidity = (h * (1.0 - self.h1 * h / 524288.0)).clamp(0.0, 100.0);
+
+        Ok(Reading::celsius(
+            temperature as f32,
+            Some(humidity as f32),
+            pressure.map(|pressure| pressure as f32),
+        ))
+    }
+}
+
+/// Computes the CRC-8 the SHT3x appends to each 16-bit word (polynomial `0x31`, init `0xFF`).
+pub fn sht3x_crc(data: &[u8]) -> u8 {
+    data.iter().fold(0xff, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                crc << 1 ^ 0x31
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Decodes the 6 bytes of an SHT3x measurement: temperature, CRC, humidity, CRC.
+///
+/// # Returns
+/// The [Reading] in degrees Celsius, or [SensorError::Checksum] if a word is corrupted.
+pub fn sht3x_decode(frame: &[u8; 6]) -> Result<Reading, SensorError> {
+    if sht3x_crc(&frame[0..2]) != frame[2] || sht3x_crc(&frame[3..5]) != frame[5] {
+        return Err(SensorError::Checksum);
+    }
+
+    let raw_t = f32::from(u16::from_be_bytes([frame[0], frame[1]]));
+    let raw_h = f32::from(u16::from_be_bytes([frame[3], frame[4]]));
+
+    Ok(Reading::celsius(
+        -45.0 + 175.0 * raw_t / 65535.0,
+        Some(100.0 * raw_h / 65535.0),
+        None,
+    ))
+}
+
+/// Decodes the 5 bytes sent by a DHT22: humidity, temperature and a checksum.
+///
+/// The temperature is in sign-magnitude, both values in tenths.
+///
+/// # Returns
+/// The [Reading] in degrees Celsius, [SensorError::Checksum] if the sum does not match,
+/// or [SensorError::OutOfRange] if the values cannot come from a DHT22.
+pub fn dht22_decode(frame: &[u8; 5]) -> Result<Reading, SensorError> {
+    let sum = frame[..4]
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    if sum != frame[4] {
+        return Err(SensorError::Checksum);
+    }
+
+    let humidity = f32::from(u16::from_be_bytes([frame[0], frame[1]])) / 10.0;
+    let magnitude = f32::from(u16::from_be_bytes([frame[2] & 0x7f, frame[3]])) / 10.0;
+    let temperature = if frame[2] & 0x80 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    };
+
+    if humidity > 100.0 || !(-40.0..=80.0).contains(&temperature) {
+        return Err(SensorError::OutOfRange);
+    }
+
+    Ok(Reading::celsius(temperature, Some(humidity), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Calibration registers holding the compensation example of the Bosch datasheet,
+    /// with the humidity trimming of a sample sensor.
+    fn calibration() -> Bme280Calibration {
+        let words: [i32; 12] = [
+            27504, 26435, -1000, 36477, -10685, 3024, 2855, 140, -7, 15500, -14600, 6000,
+        ];
+        let mut low = [0; 26];
+        for (n, word) in words.iter().enumerate() {
+            low[2 * n..2 * n + 2].copy_from_slice(&(*word as u16).to_le_bytes());
+        }
+        low[25] = 75;
+
+        // H2 = 362, H3 = 0, H4 = 313, H5 = 50, H6 = 30
+        let high = [0x6a, 0x01, 0x00, 0x13, 0x29, 0x03, 30];
+
+        Bme280Calibration::parse(&low, &high)
+    }
+
+    /// Raw registers with adc_P = 415148, adc_T = 519888 and adc_H = 29000.
+    const RAW: [u8; 8] = [0x65, 0x5a, 0xc0, 0x7e, 0xed, 0x00, 0x71, 0x48];
+
+    fn assert_close(value: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{value} is not {expected} ± {tolerance}"
+        );
+    }
+
+    #[test]
+    fn bme280_parses_the_calibration() {
+        let calibration = calibration();
+        assert_eq!(calibration.t1, 27504.0);
+        assert_eq!(calibration.t3, -1000.0);
+        assert_eq!(calibration.p[0], 36477.0);
+        assert_eq!(calibration.p[8], 6000.0);
+        assert_eq!(calibration.h4, 313.0);
+        assert_eq!(calibration.h5, 50.0);
+
+        // H4 and H5 are signed 12-bit values
+        let high = [0, 0, 0, 0xff, 0xff, 0xff, 0xff];
+        let calibration = Bme280Calibration::parse(&[0; 26], &high);
+        assert_eq!(calibration.h4, -1.0);
+        assert_eq!(calibration.h5, -1.0);
+        assert_eq!(calibration.h6, -1.0);
+    }
+
+    #[test]
+    fn bme280_matches_the_datasheet() {
+        let reading = calibration().compensate(&RAW).unwrap();
+        assert_eq!(reading.unit, TemperatureUnit::Celsius);
+        // 25.08 °C and 100653.27 Pa in the datasheet
+        assert_close(reading.temperature, 25.08, 0.005);
+        assert_close(reading.pressure.unwrap(), 1006.5327, 0.001);
+        assert_close(reading.humidity.unwrap(), 49.43, 0.01);
+    }
+
+    #[test]
+    fn bme280_rejects_a_missing_measurement() {
+        let mut raw = RAW;
+        raw[3..6].copy_from_slice(&[0x80, 0x00, 0x00]);
+        assert_eq!(calibration().compensate(&raw), Err(SensorError::OutOfRange));
+    }
+
+    #[test]
+    fn bme280_clamps_the_humidity() {
+        let mut raw = RAW;
+        raw[6..8].copy_from_slice(&[0xff, 0xff]);
+        let reading = calibration().compensate(&raw).unwrap();
+        assert_eq!(reading.humidity, Some(100.0));
+
+        raw[6..8].copy_from_slice(&[0x00, 0x00]);
+        let reading = calibration().compensate(&raw).unwrap();
+        assert_eq!(reading.humidity, Some(0.0));
+    }
+
+    #[test]
+    fn sht3x_crc_matches_the_datasheet() {
+        assert_eq!(sht3x_crc(&[0xbe, 0xef]), 0x92);
+        assert_eq!(sht3x_crc(&[0x66, 0x66]), 0x93);
+        assert_eq!(sht3x_crc(&[0x80, 0x00]), 0xa2);
+    }
+
+    #[test]
+    fn sht3x_decodes_a_measurement() {
+        let cases = [
+            ([0x66, 0x66, 0x93, 0x80, 0x00, 0xa2], 25.0, 50.0),
+            ([0x00, 0x00, 0x81, 0x00, 0x00, 0x81], -45.0, 0.0),
+            ([0xff, 0xff, 0xac, 0xff, 0xff, 0xac], 130.0, 100.0),
+        ];
+        for (frame, temperature, humidity) in cases {
+            let reading = sht3x_decode(&frame).unwrap();
+            assert_close(reading.temperature, temperature, 0.01);
+            assert_close(reading.humidity.unwrap(), humidity, 0.01);
+            assert_eq!(reading.pressure, None);
+        }
+    }
+
+    #[test]
+    fn sht3x_rejects_a_bad_crc() {
+        let frame = [0x66, 0x66, 0x93, 0x80, 0x00, 0xa2];
+        for byte in 0..frame.len() {
+            let mut corrupted = frame;
+            corrupted[byte] ^= 0x01;
+            assert_eq!(sht3x_decode(&corrupted), Err(SensorError::Checksum));
+        }
+    }
+
+    #[test]
+    fn dht22_decodes_the_datasheet_frames() {
+        // 65.2 %RH and 35.1 °C, then -10.1 °C in sign-magnitude
+        let reading = dht22_decode(&[0x02, 0x8c, 0x01, 0x5f, 0xee]).unwrap();
+        assert_eq!(reading, Reading::celsius(35.1, Some(65.2), None));
+
+        let reading = dht22_decode(&[0x02, 0x8c, 0x80, 0x65, 0x73]).unwrap();
+        assert_eq!(reading, Reading::celsius(-10.1, Some(65.2), None));
+    }
+
+    #[test]
+    fn dht22_rejects_a_bad_checksum() {
+        assert_eq!(
+            dht22_decode(&[0x02, 0x8c, 0x01, 0x5f, 0xef]),
+            Err(SensorError::Checksum)
+        );
+        assert_eq!(
+            dht22_decode(&[0x02, 0x8d, 0x01, 0x5f, 0xee]),
+            Err(SensorError::Checksum)
+        );
+    }
+
+    #[test]
+    fn dht22_rejects_impossible_values() {
+        // 100.1 %RH, then 80.1 °C and -40.1 °C
+        assert_eq!(
+            dht22_decode(&[0x03, 0xe9, 0x00, 0xfa, 0xe6]),
+            Err(SensorError::OutOfRange)
+        );
+        assert_eq!(
+            dht22_decode(&[0x01, 0xf4, 0x03, 0x21, 0x19]),
+            Err(SensorError::OutOfRange)
+        );
+        assert_eq!(
+            dht22_decode(&[0x01, 0xf4, 0x81, 0x91, 0x07]),
+            Err(SensorError::OutOfRange)
+        );
+        // The checksum wraps around, 6553.5 %RH then fails the range check
+        assert_eq!(
+            dht22_decode(&[0xff, 0xff, 0x00, 0x00, 0xfe]),
+            Err(SensorError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn converts_and_rounds_the_unit() {
+        let reading = Reading::celsius(23.46, Some(45.04), Some(1013.25));
+        let fahrenheit = reading.in_unit(TemperatureUnit::Fahrenheit);
+        assert_eq!(fahrenheit.unit, TemperatureUnit::Fahrenheit);
+        assert_eq!(fahrenheit.temperature, 74.2);
+        assert_eq!(fahrenheit.humidity, Some(45.0));
+        assert_eq!(
+            fahrenheit.in_unit(TemperatureUnit::Celsius).temperature,
+            23.4
+        );
+    }
+}
@@ -0,0 +1,762 @@
+use crate::alarm::{Ringing, MAX_ALARMS, MAX_TIMER_MINUTES, SNOOZE_MINUTES};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+use log::LevelFilter;
+use std::{fmt, str::FromStr};
+
+/// Maximum length, in bytes, of a Wi-Fi SSID.
+const MAX_SSID_LEN: usize = 32;
+
+/// Maximum length, in bytes, of a Wi-Fi password.
+const MAX_PASSWORD_LEN: usize = 64;
+
+/// Text listing the commands understood by [parse].
+pub const HELP: &str = "Commands:
+  status                         Show time, display and network state
+  set brightness <1-7>           Set the display brightness
+  set tz <Area/City>             Set the timezone, e.g. Europe/Berlin
+  set wifi <ssid> [password]     Store Wi-Fi credentials, applied after reboot
+  set 12h <on|off>               Show the time in 12-hour format
+  set alarm <1-4> <HH:MM|off>    Set or disable a daily alarm
+  set time <HH:MM[:SS]>          Set the local time by hand, without network
+  set date <YYYY-MM-DD>          Set the local date by hand, without network
+  sync                           Synchronize the time with SNTP
+  mode                           Show the next display mode
+  timer <1-99>                   Start a countdown timer, in minutes
+  snooze                         Snooze the ringing alarm, or stop the timer
+  dismiss                        Stop the alarm, the timer and the sunrise
+  sunrise cancel                 Turn off the sunrise before the next alarm
+  reboot                         Restart the clock
+  factory reset                  Erase all settings and restart
+  log level <level> [target]     Set the log level (off, error, warn, info, debug, trace)
+  help                           Show this help
+Quote arguments containing spaces, e.g. set wifi \"My Network\" secret";
+
+/// An action on the clock, shared by the serial shell and the HTTP handlers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Help,
+    Status,
+    SetBrightness(u8),
+    SetTimezone(Tz),
+    SetWifi {
+        ssid: String,
+        password: String,
+    },
+    SetTwelveHour(bool),
+    /// Sets the alarm at `index` (0-based) to `time`, or disables it with `None`.
+    SetAlarm {
+        index: usize,
+        time: Option<(u8, u8)>,
+    },
+    /// Sets the local date, the time of day, or both by hand; the missing part is
+    /// kept from the current time.
+    SetTime {
+        date: Option<NaiveDate>,
+        time: Option<NaiveTime>,
+    },
+    Sync,
+    CycleMode,
+    StartTimer(u32),
+    Snooze,
+    Dismiss,
+    CancelSunrise,
+    Reboot,
+    FactoryReset,
+    SetLogLevel {
+        target: String,
+        level: LevelFilter,
+    },
+}
+
+/// Errors returned by [parse] for malformed command lines.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Empty,
+    UnterminatedQuote,
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    InvalidArgument { name: &'static str, value: String },
+    UnexpectedArgument(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty command"),
+            ParseError::UnterminatedQuote => write!(f, "unterminated quote"),
+            ParseError::UnknownCommand(command) => {
+                write!(f, "unknown command `{command}`, type `help` for a list")
+            }
+            ParseError::MissingArgument(name) => write!(f, "missing argument <{name}>"),
+            ParseError::InvalidArgument { name, value } => {
+                write!(f, "invalid <{name}> `{value}`")
+            }
+            ParseError::UnexpectedArgument(argument) => {
+                write!(f, "unexpected argument `{argument}`")
+            }
+        }
+    }
+}
+
+/// Splits a command line into words, keeping double-quoted text together.
+fn tokenize(line: &str) -> Result<Vec<String>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => tokens.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quoted {
+        return Err(ParseError::UnterminatedQuote);
+    }
+    tokens.extend(current);
+
+    Ok(tokens)
+}
+
+/// Parses a line typed on the serial shell into a [Command].
+///
+/// Keywords are case-insensitive, arguments are kept as typed.
+///
+/// # Returns
+/// The parsed [Command], or a [ParseError] describing what is wrong with the line.
+///
+/// # Example
+/// ```rust
+/// assert_eq!(parse("set brightness 5"), Ok(Command::SetBrightness(5)));
+/// ```
+pub fn parse(line: &str) -> Result<Command, ParseError> {
+    let tokens = tokenize(line)?;
+    let mut args = tokens.iter().map(String::as_str);
+
+    let keyword = args.next().ok_or(ParseError::Empty)?.to_ascii_lowercase();
+    let command = match keyword.as_str() {
+        "help" | "?" => Command::Help,
+        "status" => Command::Status,
+        "sync" => Command::Sync,
+        "mode" => Command::CycleMode,
+        "snooze" => Command::Snooze,
+        "dismiss" => Command::Dismiss,
+        "sunrise" => {
+            expect_keyword(args.next(), "cancel", "sunrise")?;
+            Command::CancelSunrise
+        }
+        "timer" => {
+            let value = args.next().ok_or(ParseError::MissingArgument("1-99"))?;
+            match value.parse::<u32>() {
+                Ok(minutes) if (1..=MAX_TIMER_MINUTES).contains(&minutes) => {
+                    Command::StartTimer(minutes)
+                }
+                _ => return Err(invalid("1-99", value)),
+            }
+        }
+        "reboot" => Command::Reboot,
+        "factory" => {
+            expect_keyword(args.next(), "reset", "factory")?;
+            Command::FactoryReset
+        }
+        "set" => {
+            let setting = args.next().ok_or(ParseError::MissingArgument("setting"))?;
+            match setting.to_ascii_lowercase().as_str() {
+                "brightness" => {
+                    let value = args.next().ok_or(ParseError::MissingArgument("1-7"))?;
+                    match value.parse::<u8>() {
+                        Ok(brightness) if (1..=7).contains(&brightness) => {
+                            Command::SetBrightness(brightness)
+                        }
+                        _ => return Err(invalid("1-7", value)),
+                    }
+                }
+                "tz" | "timezone" => {
+                    let value = args
+                        .next()
+                        .ok_or(ParseError::MissingArgument("Area/City"))?;
+                    let timezone = Tz::from_str(value).map_err(|_| invalid("Area/City", value))?;
+                    Command::SetTimezone(timezone)
+                }
+                "wifi" => {
+                    let ssid = args.next().ok_or(ParseError::MissingArgument("ssid"))?;
+                    let password = args.next().unwrap_or_default();
+                    if ssid.is_empty() || ssid.len() > MAX_SSID_LEN {
+                        return Err(invalid("ssid", ssid));
+                    }
+                    if password.len() > MAX_PASSWORD_LEN {
+                        return Err(invalid("password", "*".repeat(password.len())));
+                    }
+                    Command::SetWifi {
+                        ssid: ssid.to_string(),
+                        password: password.to_string(),
+                    }
+                }
+                "12h" => {
+                    let value = args.next().ok_or(ParseError::MissingArgument("on|off"))?;
+                    Command::SetTwelveHour(
+                        parse_switch(value).ok_or_else(|| invalid("on|off", value))?,
+                    )
+                }
+                "alarm" => {
+                    let value = args.next().ok_or(ParseError::MissingArgument("1-4"))?;
+                    let index = match value.parse::<usize>() {
+                        Ok(number) if (1..=MAX_ALARMS).contains(&number) => number - 1,
+                        _ => return Err(invalid("1-4", value)),
+                    };
+                    let value = args
+                        .next()
+                        .ok_or(ParseError::MissingArgument("HH:MM|off"))?;
+                    let time = if value.eq_ignore_ascii_case("off") {
+                        None
+                    } else {
+                        Some(parse_time(value).ok_or_else(|| invalid("HH:MM|off", value))?)
+                    };
+                    Command::SetAlarm { index, time }
+                }
+                "time" => {
+                    let value = args
+                        .next()
+                        .ok_or(ParseError::MissingArgument("HH:MM[:SS]"))?;
+                    let time = NaiveTime::parse_from_str(value, "%H:%M:%S")
+                        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M"))
+                        .map_err(|_| invalid("HH:MM[:SS]", value))?;
+                    Command::SetTime {
+                        date: None,
+                        time: Some(time),
+                    }
+                }
+                "date" => {
+                    let value = args
+                        .next()
+                        .ok_or(ParseError::MissingArgument("YYYY-MM-DD"))?;
+                    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                        .map_err(|_| invalid("YYYY-MM-DD", value))?;
+                    Command::SetTime {
+                        date: Some(date),
+                        time: None,
+                    }
+                }
+                _ => return Err(ParseError::UnknownCommand(format!("set {setting}"))),
+            }
+        }
+        "log" => {
+            expect_keyword(args.next(), "level", "log")?;
+            let value = args.next().ok_or(ParseError::MissingArgument("level"))?;
+            let level = LevelFilter::from_str(value).map_err(|_| invalid("level", value))?;
+            let target = args.next().unwrap_or("*").to_string();
+            Command::SetLogLevel { target, level }
+        }
+        _ => return Err(ParseError::UnknownCommand(keyword)),
+    };
+
+    match args.next() {
+        Some(extra) => Err(ParseError::UnexpectedArgument(extra.to_string())),
+        None => Ok(command),
+    }
+}
+
+/// Checks that the second word of a two-word command is the expected one.
+fn expect_keyword(
+    word: Option<&str>,
+    expected: &'static str,
+    command: &str,
+) -> Result<(), ParseError> {
+    match word {
+        Some(word) if word.eq_ignore_ascii_case(expected) => Ok(()),
+        Some(word) => Err(ParseError::UnknownCommand(format!("{command} {word}"))),
+        None => Err(ParseError::MissingArgument(expected)),
+    }
+}
+
+/// Parses `on` or `off`, in any case.
+fn parse_switch(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a time of day written as `HH:MM`.
+fn parse_time(value: &str) -> Option<(u8, u8)> {
+    let (hour, minute) = value.split_once(':')?;
+    let (hour, minute) = (hour.parse::<u8>().ok()?, minute.parse::<u8>().ok()?);
+
+    (hour < 24 && minute < 60).then_some((hour, minute))
+}
+
+fn invalid(name: &'static str, value: impl Into<String>) -> ParseError {
+    ParseError::InvalidArgument {
+        name,
+        value: value.into(),
+    }
+}
+
+/// The operations a [Command] can trigger on the clock.
+///
+/// Implemented by the firmware on top of the hardware drivers, and by test doubles
+/// so the dispatch in [execute] can run on the host.
+pub trait Device {
+    type Error;
+
+    /// Returns a human-readable summary of the clock state.
+    fn status(&self) -> Result<String, Self::Error>;
+    fn set_brightness(&self, brightness: u8) -> Result<(), Self::Error>;
+    fn set_timezone(&self, timezone: Tz) -> Result<(), Self::Error>;
+    fn set_wifi(&self, ssid: &str, password: &str) -> Result<(), Self::Error>;
+    fn set_twelve_hour(&self, twelve_hour: bool) -> Result<(), Self::Error>;
+    fn set_alarm(&self, index: usize, time: Option<(u8, u8)>) -> Result<(), Self::Error>;
+    /// Sets the local date and time by hand, keeping the current value of the missing
+    /// part, and returns the new local time.
+    fn set_time(
+        &self,
+        date: Option<NaiveDate>,
+        time: Option<NaiveTime>,
+    ) -> Result<NaiveDateTime, Self::Error>;
+    fn sync_time(&self) -> Result<(), Self::Error>;
+    /// Switches the display to the next mode and returns its name.
+    fn cycle_mode(&self) -> Result<String, Self::Error>;
+    fn start_timer(&self, minutes: u32) -> Result<(), Self::Error>;
+    /// Silences what is ringing and returns what it was.
+    fn snooze(&self) -> Result<Option<Ringing>, Self::Error>;
+    /// Stops the alarms, the timer and the sunrise and returns whether anything was active.
+    fn dismiss(&self) -> Result<bool, Self::Error>;
+    /// Turns off the sunrise and returns whether one was showing.
+    fn cancel_sunrise(&self) -> Result<bool, Self::Error>;
+    fn reboot(&self) -> Result<(), Self::Error>;
+    fn factory_reset(&self) -> Result<(), Self::Error>;
+    fn set_log_level(&self, target: &str, level: LevelFilter) -> Result<(), Self::Error>;
+}
+
+/// Runs a [Command] on a [Device].
+///
+/// # Returns
+/// The reply shown to the user, or the error raised by the device.
+///
+/// # Example
+/// ```rust
+/// let reply = execute(&clock, &Command::SetBrightness(5))?;
+/// ```
+pub fn execute<D: Device>(device: &D, command: &Command) -> Result<String, D::Error> {
+    let reply = match command {
+        Command::Help => HELP.to_string(),
+        Command::Status => device.status()?,
+        Command::SetBrightness(brightness) => {
+            device.set_brightness(*brightness)?;
+            format!("Brightness set to {brightness}")
+        }
+        Command::SetTimezone(timezone) => {
+            device.set_timezone(*timezone)?;
+            format!("Timezone set to {timezone}")
+        }
+        Command::SetWifi { ssid, password } => {
+            device.set_wifi(ssid, password)?;
+            format!("Wi-Fi credentials for `{ssid}` saved, reboot to connect")
+        }
+        Command::SetTwelveHour(twelve_hour) => {
+            device.set_twelve_hour(*twelve_hour)?;
+            format!("{}-hour format set", if *twelve_hour { 12 } else { 24 })
+        }
+        Command::SetAlarm { index, time } => {
+            device.set_alarm(*index, *time)?;
+            match time {
+                Some((hour, minute)) => format!("Alarm {} set to {hour:02}:{minute:02}", index + 1),
+                None => format!("Alarm {} disabled", index + 1),
+            }
+        }
+        Command::SetTime { date, time } => {
+            let now = device.set_time(*date, *time)?;
+            format!(
+                "Time set to {}, unsynced until SNTP or GPS takes over",
+                now.format("%Y-%m-%d %H:%M:%S")
+            )
+        }
+        Command::Sync => {
+            device.sync_time()?;
+            "Time synced successfully!".to_string()
+        }
+        Command::CycleMode => format!("Display mode set to {}", device.cycle_mode()?),
+        Command::StartTimer(minutes) => {
+            device.start_timer(*minutes)?;
+            format!("Timer started for {minutes} minute(s)")
+        }
+        Command::Snooze => match device.snooze()? {
+            Some(Ringing::Alarm(index)) => {
+                format!("Alarm {} snoozed for {SNOOZE_MINUTES} minutes", index + 1)
+            }
+            Some(Ringing::Timer) => "Timer stopped".to_string(),
+            None => "Nothing is ringing".to_string(),
+        },
+        Command::Dismiss => {
+            if device.dismiss()? {
+                "Alarm, timer and sunrise stopped".to_string()
+            } else {
+                "Nothing to stop".to_string()
+            }
+        }
+        Command::CancelSunrise => {
+            if device.cancel_sunrise()? {
+                "Sunrise cancelled".to_string()
+            } else {
+                "No sunrise showing".to_string()
+            }
+        }
+        Command::Reboot => {
+            device.reboot()?;
+            "Rebooting...".to_string()
+        }
+        Command::FactoryReset => {
+            device.factory_reset()?;
+            "Settings erased, rebooting...".to_string()
+        }
+        Command::SetLogLevel { target, level } => {
+            device.set_log_level(target, *level)?;
+            format!("Log level of `{target}` set to {level}")
+        }
+    };
+
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Records the calls made by [execute] instead of driving hardware.
+    #[derive(Default)]
+    struct FakeDevice {
+        calls: RefCell<Vec<String>>,
+        ringing: Option<Ringing>,
+        fail: bool,
+    }
+
+    impl FakeDevice {
+        fn call(&self, call: String) -> Result<(), String> {
+            if self.fail {
+                return Err(format!("{call} failed"));
+            }
+            self.calls.borrow_mut().push(call);
+            Ok(())
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls.borrow().clone()
+        }
+    }
+
+    impl Device for FakeDevice {
+        type Error = String;
+
+        fn status(&self) -> Result<String, String> {
+            self.call("status".to_string())?;
+            Ok("all good".to_string())
+        }
+        fn set_brightness(&self, brightness: u8) -> Result<(), String> {
+            self.call(format!("brightness {brightness}"))
+        }
+        fn set_timezone(&self, timezone: Tz) -> Result<(), String> {
+            self.call(format!("tz {timezone}"))
+        }
+        fn set_wifi(&self, ssid: &str, password: &str) -> Result<(), String> {
+            self.call(format!("wifi {ssid} {password}"))
+        }
+        fn set_twelve_hour(&self, twelve_hour: bool) -> Result<(), String> {
+            self.call(format!("12h {twelve_hour}"))
+        }
+        fn set_alarm(&self, index: usize, time: Option<(u8, u8)>) -> Result<(), String> {
+            self.call(format!("alarm {index} {time:?}"))
+        }
+        fn set_time(
+            &self,
+            date: Option<NaiveDate>,
+            time: Option<NaiveTime>,
+        ) -> Result<NaiveDateTime, String> {
+            self.call(format!("time {date:?} {time:?}"))?;
+            let date = date.unwrap_or(NaiveDate::from_ymd_opt(2026, 1, 1).unwrap());
+            Ok(date.and_time(time.unwrap_or_default()))
+        }
+        fn sync_time(&self) -> Result<(), String> {
+            self.call("sync".to_string())
+        }
+        fn cycle_mode(&self) -> Result<String, String> {
+            self.call("mode".to_string())?;
+            Ok("Date".to_string())
+        }
+        fn start_timer(&self, minutes: u32) -> Result<(), String> {
+            self.call(format!("timer {minutes}"))
+        }
+        fn snooze(&self) -> Result<Option<Ringing>, String> {
+            self.call("snooze".to_string())?;
+            Ok(self.ringing)
+        }
+        fn dismiss(&self) -> Result<bool, String> {
+            self.call("dismiss".to_string())?;
+            Ok(self.ringing.is_some())
+        }
+        fn cancel_sunrise(&self) -> Result<bool, String> {
+            self.call("sunrise".to_string())?;
+            Ok(false)
+        }
+        fn reboot(&self) -> Result<(), String> {
+            self.call("reboot".to_string())
+        }
+        fn factory_reset(&self) -> Result<(), String> {
+            self.call("factory reset".to_string())
+        }
+        fn set_log_level(&self, target: &str, level: LevelFilter) -> Result<(), String> {
+            self.call(format!("log {target} {level}"))
+        }
+    }
+
+    #[test]
+    fn parses_simple_commands() {
+        assert_eq!(parse("help"), Ok(Command::Help));
+        assert_eq!(parse("?"), Ok(Command::Help));
+        assert_eq!(parse("  STATUS  "), Ok(Command::Status));
+        assert_eq!(parse("sunrise cancel"), Ok(Command::CancelSunrise));
+        assert_eq!(parse("Factory Reset"), Ok(Command::FactoryReset));
+        assert_eq!(parse("timer 99"), Ok(Command::StartTimer(99)));
+    }
+
+    #[test]
+    fn parses_settings() {
+        assert_eq!(parse("set brightness 7"), Ok(Command::SetBrightness(7)));
+        assert_eq!(
+            parse("set tz Europe/Berlin"),
+            Ok(Command::SetTimezone(chrono_tz::Europe::Berlin))
+        );
+        assert_eq!(parse("set 12h ON"), Ok(Command::SetTwelveHour(true)));
+        assert_eq!(
+            parse("set alarm 4 06:05"),
+            Ok(Command::SetAlarm {
+                index: 3,
+                time: Some((6, 5))
+            })
+        );
+        assert_eq!(
+            parse("set alarm 1 off"),
+            Ok(Command::SetAlarm {
+                index: 0,
+                time: None
+            })
+        );
+        assert_eq!(
+            parse("set time 14:30"),
+            Ok(Command::SetTime {
+                date: None,
+                time: NaiveTime::from_hms_opt(14, 30, 0)
+            })
+        );
+        assert_eq!(
+            parse("set date 2026-10-18"),
+            Ok(Command::SetTime {
+                date: NaiveDate::from_ymd_opt(2026, 10, 18),
+                time: None
+            })
+        );
+        assert_eq!(
+            parse("log level debug esp_interstellar_clock::wifi"),
+            Ok(Command::SetLogLevel {
+                target: "esp_interstellar_clock::wifi".to_string(),
+                level: LevelFilter::Debug
+            })
+        );
+        assert_eq!(
+            parse("log level warn"),
+            Ok(Command::SetLogLevel {
+                target: "*".to_string(),
+                level: LevelFilter::Warn
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_quoted_arguments_together() {
+        assert_eq!(
+            parse(r#"set wifi "My Network" "pass word""#),
+            Ok(Command::SetWifi {
+                ssid: "My Network".to_string(),
+                password: "pass word".to_string()
+            })
+        );
+        assert_eq!(
+            parse("set wifi open"),
+            Ok(Command::SetWifi {
+                ssid: "open".to_string(),
+                password: String::new()
+            })
+        );
+        assert_eq!(
+            parse(r#"set wifi "unterminated"#),
+            Err(ParseError::UnterminatedQuote)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(parse(""), Err(ParseError::Empty));
+        assert_eq!(parse("   "), Err(ParseError::Empty));
+        assert_eq!(
+            parse("explode"),
+            Err(ParseError::UnknownCommand("explode".to_string()))
+        );
+        assert_eq!(
+            parse("set volume 3"),
+            Err(ParseError::UnknownCommand("set volume".to_string()))
+        );
+        assert_eq!(
+            parse("factory settings"),
+            Err(ParseError::UnknownCommand("factory settings".to_string()))
+        );
+        assert_eq!(parse("sunrise"), Err(ParseError::MissingArgument("cancel")));
+        assert_eq!(parse("set"), Err(ParseError::MissingArgument("setting")));
+        assert_eq!(
+            parse("set alarm 1"),
+            Err(ParseError::MissingArgument("HH:MM|off"))
+        );
+        assert_eq!(
+            parse("status now"),
+            Err(ParseError::UnexpectedArgument("now".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        assert_eq!(parse("set brightness 0"), Err(invalid("1-7", "0")));
+        assert_eq!(parse("set brightness 8"), Err(invalid("1-7", "8")));
+        assert_eq!(parse("set brightness -1"), Err(invalid("1-7", "-1")));
+        assert_eq!(parse("timer 0"), Err(invalid("1-99", "0")));
+        assert_eq!(parse("timer 100"), Err(invalid("1-99", "100")));
+        assert_eq!(parse("set alarm 5 07:00"), Err(invalid("1-4", "5")));
+        assert_eq!(
+            parse("set alarm 1 24:00"),
+            Err(invalid("HH:MM|off", "24:00"))
+        );
+        assert_eq!(
+            parse("set alarm 1 07:60"),
+            Err(invalid("HH:MM|off", "07:60"))
+        );
+        assert_eq!(parse("set 12h maybe"), Err(invalid("on|off", "maybe")));
+        assert_eq!(
+            parse("set tz Mars/Olympus"),
+            Err(invalid("Area/City", "Mars/Olympus"))
+        );
+        assert_eq!(parse("set time 25:00"), Err(invalid("HH:MM[:SS]", "25:00")));
+        assert_eq!(
+            parse("set date 2026-02-30"),
+            Err(invalid("YYYY-MM-DD", "2026-02-30"))
+        );
+        assert_eq!(parse("log level loud"), Err(invalid("level", "loud")));
+        assert_eq!(
+            parse(&format!("set wifi {}", "x".repeat(MAX_SSID_LEN + 1))),
+            Err(invalid("ssid", "x".repeat(MAX_SSID_LEN + 1)))
+        );
+        // The password is never echoed back
+        assert_eq!(
+            parse(&format!(
+                "set wifi ssid {}",
+                "p".repeat(MAX_PASSWORD_LEN + 1)
+            )),
+            Err(invalid("password", "*".repeat(MAX_PASSWORD_LEN + 1)))
+        );
+    }
+
+    #[test]
+    fn executes_on_the_device() {
+        let device = FakeDevice::default();
+
+        assert_eq!(
+            execute(&device, &parse("set brightness 5").unwrap()),
+            Ok("Brightness set to 5".to_string())
+        );
+        assert_eq!(
+            execute(&device, &parse("set alarm 2 07:05").unwrap()),
+            Ok("Alarm 2 set to 07:05".to_string())
+        );
+        assert_eq!(
+            execute(&device, &parse("set alarm 2 off").unwrap()),
+            Ok("Alarm 2 disabled".to_string())
+        );
+        assert_eq!(
+            execute(&device, &parse("set time 14:30").unwrap()),
+            Ok(
+                "Time set to 2026-01-01 14:30:00, unsynced until SNTP or GPS takes over"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            execute(&device, &Command::CycleMode),
+            Ok("Display mode set to Date".to_string())
+        );
+        assert_eq!(execute(&device, &Command::Help), Ok(HELP.to_string()));
+
+        assert_eq!(
+            device.calls(),
+            [
+                "brightness 5",
+                "alarm 1 Some((7, 5))",
+                "alarm 1 None",
+                "time None Some(14:30:00)",
+                "mode",
+            ]
+        );
+    }
+
+    #[test]
+    fn replies_with_what_was_ringing() {
+        let idle = FakeDevice::default();
+        assert_eq!(
+            execute(&idle, &Command::Snooze),
+            Ok("Nothing is ringing".to_string())
+        );
+        assert_eq!(
+            execute(&idle, &Command::Dismiss),
+            Ok("Nothing to stop".to_string())
+        );
+
+        let alarm = FakeDevice {
+            ringing: Some(Ringing::Alarm(1)),
+            ..Default::default()
+        };
+        assert_eq!(
+            execute(&alarm, &Command::Snooze),
+            Ok(format!("Alarm 2 snoozed for {SNOOZE_MINUTES} minutes"))
+        );
+
+        let timer = FakeDevice {
+            ringing: Some(Ringing::Timer),
+            ..Default::default()
+        };
+        assert_eq!(
+            execute(&timer, &Command::Snooze),
+            Ok("Timer stopped".to_string())
+        );
+    }
+
+    #[test]
+    fn passes_device_errors_through() {
+        let device = FakeDevice {
+            fail: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            execute(&device, &Command::Sync),
+            Err("sync failed".to_string())
+        );
+        assert_eq!(
+            execute(&device, &Command::StartTimer(5)),
+            Err("timer 5 failed".to_string())
+        );
+        // Help needs no device
+        assert_eq!(execute(&device, &Command::Help), Ok(HELP.to_string()));
+    }
+}
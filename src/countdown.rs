@@ -0,0 +1,115 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of events kept in the settings.
+pub const MAX_EVENTS: usize = 8;
+
+/// Longest event name, in characters.
+pub const MAX_NAME_LENGTH: usize = 24;
+
+/// Seconds the countdown page shows the event name, then the time remaining, in turns.
+pub const PAGE_SECONDS: u32 = 3;
+
+/// A named day counted down to on [crate::display::DisplayMode::Countdown], e.g. a launch
+/// or a holiday.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Event {
+    /// Name of the event, its first 4 characters are shown on the display.
+    pub name: String,
+    /// The day of the event, in the configured timezone, e.g. `2026-12-25`.
+    pub date: NaiveDate,
+}
+
+impl Event {
+    /// Whether the name holds 1 to [MAX_NAME_LENGTH] characters and is not blank.
+    pub fn is_valid(&self) -> bool {
+        !self.name.trim().is_empty() && self.name.chars().count() <= MAX_NAME_LENGTH
+    }
+}
+
+/// The time left until an event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Remaining {
+    /// Whole days left, from 2 on.
+    Days(i64),
+    /// Hours and minutes left on the final day, rounded up to the minute.
+    Time(u32, u32),
+    /// The event is today.
+    Today,
+}
+
+/// Returns the number of days from `today` to `date`, negative once it passed.
+pub fn days_until(date: NaiveDate, today: NaiveDate) -> i64 {
+    (date - today).num_days()
+}
+
+/// Returns the time left until the start of a day.
+///
+/// Days are counted on the calendar of `now`, so the count changes at local midnight.
+///
+/// # Parameters
+/// - `date`: The day of the event.
+/// - `now`: The current time, in the configured timezone.
+///
+/// # Returns
+/// The [Remaining] time, or `None` once the day of the event is over.
+pub fn remaining<Tz: TimeZone>(date: NaiveDate, now: &DateTime<Tz>) -> Option<Remaining> {
+    match days_until(date, now.date_naive()) {
+        ..=-1 => None,
+        0 => Some(Remaining::Today),
+        1 => {
+            let midnight = date.and_time(NaiveTime::MIN);
+            // Midnight may be skipped by a DST change, the local difference is then close enough
+            let left = match now.timezone().from_local_datetime(&midnight).earliest() {
+                Some(start) => start.naive_utc() - now.naive_utc(),
+                None => midnight - now.naive_local(),
+            };
+            let minutes = (left.num_seconds().max(0) as u32).div_ceil(60);
+
+            Some(Remaining::Time(minutes / 60, minutes % 60))
+        }
+        days => Some(Remaining::Days(days)),
+    }
+}
+
+/// Returns the soonest event that is not over yet.
+///
+/// # Parameters
+/// - `events`: The events from the settings.
+/// - `now`: The current time, in the configured timezone.
+///
+/// # Returns
+/// The event with the time left until it, or `None` if every event is over.
+///
+/// # Example
+/// ```rust
+/// if let Some((event, remaining)) = next_event(&settings.events, &time::now()) {
+///     log::info!("{} in {:?}", event.name, remaining);
+/// }
+/// ```
+pub fn next_event<'a, Tz: TimeZone>(
+    events: &'a [Event],
+    now: &DateTime<Tz>,
+) -> Option<(&'a Event, Remaining)> {
+    events
+        .iter()
+        .filter_map(|event| Some((event, remaining(event.date, now)?)))
+        .min_by_key(|(event, _)| event.date)
+}
+
+/// Returns the text of the countdown page shown at `second`.
+///
+/// Before the day of the event, the name and the time left alternate every
+/// [PAGE_SECONDS]: days as ` 42d` (or `1234` from 1000 days on), then `HH:MM` on
+/// the final day. On the day itself only the name is shown, flashed by the caller.
+pub fn page_text(event: &Event, remaining: Remaining, second: u32) -> String {
+    let counting = (second / PAGE_SECONDS) % 2 == 1;
+
+    match remaining {
+        Remaining::Days(days @ ..=999) if counting => format!("{days:>3}d"),
+        Remaining::Days(days @ ..=9999) if counting => format!("{days}"),
+        Remaining::Days(_) if counting => "----".to_string(),
+        Remaining::Time(hours, minutes) if counting => format!("{hours:02}:{minutes:02}"),
+        _ => event.name.chars().take(4).collect(),
+    }
+}
@@ -0,0 +1,320 @@
+use crate::error::{self, AppError};
+use esp_idf_svc::{
+    http::client::{Client, Configuration as HttpConfiguration, EspHttpConnection},
+    io::Write,
+    nvs::{EspDefaultNvsPartition, EspNvs},
+    sys::{self, esp},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::CStr,
+    ptr::{self, addr_of, addr_of_mut},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+/// Number of boots kept in the crash log, the oldest are dropped first.
+pub const MAX_CRASH_ENTRIES: usize = 10;
+
+/// NVS namespace holding the crash log, apart from the settings.
+const NVS_NAMESPACE: &str = "crashlog";
+
+/// NVS key of the crash log, stored as JSON.
+const NVS_KEY: &str = "entries";
+
+/// Marks a [PanicRecord] written by the panic hook, anything else is leftover memory.
+const PANIC_MAGIC: u32 = 0x5041_4E43;
+
+/// Longest panic message kept, in bytes.
+const MAX_PANIC_LENGTH: usize = 200;
+
+/// Size, in bytes, of the chunks of the core dump read from flash and uploaded.
+const UPLOAD_CHUNK_SIZE: usize = 1024;
+
+/// Time allowed to the server receiving a core dump to answer.
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Stack size of the thread uploading the core dump, TLS needs some room.
+const UPLOAD_STACK_SIZE: usize = 8192;
+
+/// Where the crashed task stood, from the core dump summary.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Backtrace {
+    /// Name of the task that crashed.
+    pub task: String,
+    /// Program counter of the exception, as `0x400d1234`.
+    pub pc: String,
+    /// Program counters of the frames, innermost first; decode them with
+    /// `xtensa-esp32-elf-addr2line -e <firmware>`.
+    pub frames: Vec<String>,
+    /// Whether the stack was corrupted, so the frames may be incomplete.
+    pub corrupted: bool,
+}
+
+/// Why the clock started, recorded once per boot.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CrashEntry {
+    /// Number of the boot, counted since the log was first written.
+    pub boot: u32,
+    /// The reset reason reported by ESP-IDF, e.g. `power_on`, `panic` or `brownout`.
+    pub reason: String,
+    /// The message of the Rust panic that caused the reset, if any.
+    pub panic: Option<String>,
+    /// The backtrace summary of the core dump, after a crash.
+    pub backtrace: Option<Backtrace>,
+}
+
+/// The panic message kept across the reset in RTC memory.
+#[repr(C)]
+struct PanicRecord {
+    magic: u32,
+    len: u32,
+    message: [u8; MAX_PANIC_LENGTH],
+}
+
+/// Written by the panic hook and read on the next boot; RTC memory survives software
+/// and watchdog resets, but not a power loss.
+#[link_section = ".rtc_noinit"]
+static mut PANIC: PanicRecord = PanicRecord {
+    magic: 0,
+    len: 0,
+    message: [0; MAX_PANIC_LENGTH],
+};
+
+/// The entries recorded by [record_boot], the most recent first.
+static ENTRIES: Mutex<Vec<CrashEntry>> = Mutex::new(Vec::new());
+
+/// Keeps the message of any panic in RTC memory before the chip resets.
+///
+/// Called first thing in `main`, so panics of every task are caught.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info.to_string();
+        let bytes = &message.as_bytes()[..message.len().min(MAX_PANIC_LENGTH)];
+
+        // Only this hook writes the record, and the chip resets right after it
+        unsafe {
+            let record = addr_of_mut!(PANIC);
+            ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                addr_of_mut!((*record).message).cast::<u8>(),
+                bytes.len(),
+            );
+            ptr::write_volatile(addr_of_mut!((*record).len), bytes.len() as u32);
+            ptr::write_volatile(addr_of_mut!((*record).magic), PANIC_MAGIC);
+        }
+
+        default_hook(info);
+    }));
+}
+
+/// Returns the panic message kept by the hook before the last reset, and clears it.
+fn take_panic() -> Option<String> {
+    unsafe {
+        let record = addr_of_mut!(PANIC);
+        if ptr::read_volatile(addr_of!((*record).magic)) != PANIC_MAGIC {
+            return None;
+        }
+        ptr::write_volatile(addr_of_mut!((*record).magic), 0);
+
+        let len = (ptr::read_volatile(addr_of!((*record).len)) as usize).min(MAX_PANIC_LENGTH);
+        let message = &(*addr_of!((*record).message))[..len];
+
+        Some(String::from_utf8_lossy(message).into_owned())
+    }
+}
+
+/// Returns the reason of the last reset, as named in [CrashEntry::reason].
+fn reset_reason() -> &'static str {
+    match unsafe { sys::esp_reset_reason() } {
+        sys::esp_reset_reason_t_ESP_RST_POWERON => "power_on",
+        sys::esp_reset_reason_t_ESP_RST_EXT => "external",
+        sys::esp_reset_reason_t_ESP_RST_SW => "software",
+        sys::esp_reset_reason_t_ESP_RST_PANIC => "panic",
+        sys::esp_reset_reason_t_ESP_RST_INT_WDT => "interrupt_watchdog",
+        sys::esp_reset_reason_t_ESP_RST_TASK_WDT => "task_watchdog",
+        sys::esp_reset_reason_t_ESP_RST_WDT => "watchdog",
+        sys::esp_reset_reason_t_ESP_RST_DEEPSLEEP => "deep_sleep",
+        sys::esp_reset_reason_t_ESP_RST_BROWNOUT => "brownout",
+        sys::esp_reset_reason_t_ESP_RST_SDIO => "sdio",
+        _ => "unknown",
+    }
+}
+
+/// Whether a reset reason means the firmware crashed or hung.
+fn is_crash_reason(reason: &str) -> bool {
+    matches!(
+        reason,
+        "panic" | "interrupt_watchdog" | "task_watchdog" | "watchdog"
+    )
+}
+
+/// Reads the backtrace summary of the core dump saved in flash, if there is one.
+fn core_dump_backtrace() -> Option<Backtrace> {
+    esp!(unsafe { sys::esp_core_dump_image_check() }).ok()?;
+
+    let mut summary: sys::esp_core_dump_summary_t = unsafe { std::mem::zeroed() };
+    esp!(unsafe { sys::esp_core_dump_get_summary(&mut summary) }).ok()?;
+
+    let task = unsafe { CStr::from_ptr(summary.exc_task.as_ptr()) };
+    let info = &summary.exc_bt_info;
+    let depth = (info.depth as usize).min(info.bt.len());
+
+    Some(Backtrace {
+        task: task.to_string_lossy().into_owned(),
+        pc: format!("{:#010x}", summary.exc_pc),
+        frames: info.bt[..depth]
+            .iter()
+            .map(|pc| format!("{pc:#010x}"))
+            .collect(),
+        corrupted: info.corrupted,
+    })
+}
+
+/// Records why the clock started in the crash log kept in NVS.
+///
+/// After a crash, the entry holds the panic message kept by [install_panic_hook] and
+/// the backtrace summary of the core dump. Only the last [MAX_CRASH_ENTRIES] boots
+/// are kept.
+///
+/// # Parameters
+/// - `nvs`: The default NVS partition.
+///
+/// # Returns
+/// The new entry, or an [AppError::Storage] if the log cannot be read or saved.
+///
+/// # Example
+/// ```rust
+/// let boot = crashlog::record_boot(nvs.clone())?;
+/// log::info!("Boot {} after {}", boot.boot, boot.reason);
+/// ```
+pub fn record_boot(nvs: EspDefaultNvsPartition) -> Result<CrashEntry, AppError> {
+    let storage = |e: sys::EspError| AppError::Storage(format!("cannot keep the crash log: {e}"));
+    let mut nvs = EspNvs::new(nvs, NVS_NAMESPACE, true).map_err(storage)?;
+
+    let mut entries: Vec<CrashEntry> = match nvs.blob_len(NVS_KEY).map_err(storage)? {
+        Some(len) => {
+            let mut buf = vec![0; len];
+            let stored = nvs
+                .get_blob(NVS_KEY, &mut buf)
+                .map_err(storage)?
+                .unwrap_or_default();
+            serde_json::from_slice(stored).unwrap_or_else(|e| {
+                log::warn!("Stored crash log is invalid, starting a new one: {e}");
+                Vec::new()
+            })
+        }
+        None => Vec::new(),
+    };
+
+    let reason = reset_reason();
+    let crashed = is_crash_reason(reason);
+    let entry = CrashEntry {
+        boot: entries.first().map_or(1, |last| last.boot.wrapping_add(1)),
+        reason: reason.to_string(),
+        panic: take_panic(),
+        backtrace: crashed.then(core_dump_backtrace).flatten(),
+    };
+    if crashed {
+        log::warn!("Restarted after a crash: {:?}", entry);
+    } else {
+        log::info!("Boot {} after {}", entry.boot, entry.reason);
+    }
+
+    entries.insert(0, entry.clone());
+    entries.truncate(MAX_CRASH_ENTRIES);
+    nvs.set_blob(NVS_KEY, &serde_json::to_vec(&entries)?)
+        .map_err(storage)?;
+    *ENTRIES.lock().unwrap() = entries;
+
+    Ok(entry)
+}
+
+/// Returns the entries of the crash log, the most recent boot first.
+pub fn entries() -> Vec<CrashEntry> {
+    ENTRIES.lock().unwrap().clone()
+}
+
+/// Sends the core dump saved in flash to a server, then erases it.
+///
+/// The dump is sent as the body of a `POST` request, as an ELF file
+/// (`application/octet-stream`), readable with `esp-coredump info_corefile`.
+///
+/// # Parameters
+/// - `url`: The `http://` or `https://` address receiving the dump.
+///
+/// # Returns
+/// `Ok(true)` if a dump was sent and erased, `Ok(false)` if there was none, or an
+/// [AppError] if the server cannot be reached or refuses it.
+fn upload_core_dump(url: &str) -> Result<bool, AppError> {
+    if esp!(unsafe { sys::esp_core_dump_image_check() }).is_err() {
+        return Ok(false);
+    }
+
+    let (mut address, mut size) = (0, 0);
+    esp!(unsafe { sys::esp_core_dump_image_get(&mut address, &mut size) })?;
+
+    let connection = EspHttpConnection::new(&HttpConfiguration {
+        timeout: Some(UPLOAD_TIMEOUT),
+        crt_bundle_attach: Some(sys::esp_crt_bundle_attach),
+        ..Default::default()
+    })?;
+    let mut client = Client::wrap(connection);
+    let length = size.to_string();
+    let headers = [
+        ("Content-Type", "application/octet-stream"),
+        ("Content-Length", length.as_str()),
+    ];
+    let mut request = client.post(url, &headers)?;
+
+    let mut buf = [0u8; UPLOAD_CHUNK_SIZE];
+    for offset in (0..size).step_by(UPLOAD_CHUNK_SIZE) {
+        let len = UPLOAD_CHUNK_SIZE.min(size - offset);
+        esp!(unsafe {
+            sys::esp_flash_read(
+                ptr::null_mut(),
+                buf.as_mut_ptr().cast(),
+                (address + offset) as u32,
+                len as u32,
+            )
+        })?;
+        request.write_all(&buf[..len])?;
+    }
+
+    let status = request.submit()?.status();
+    if !(200..300).contains(&status) {
+        return Err(AppError::Network(format!(
+            "core dump refused by the server with status {status}"
+        )));
+    }
+
+    esp!(unsafe { sys::esp_core_dump_image_erase() })?;
+
+    Ok(true)
+}
+
+/// Starts a thread sending the core dump saved in flash, if any, to `url`.
+///
+/// # Parameters
+/// - `url`: The address from the `coredump_url` setting.
+///
+/// # Returns
+/// `Ok(())` once the thread is running, or an [AppError] if it cannot be spawned.
+pub fn start_upload(url: String) -> Result<(), AppError> {
+    thread::Builder::new()
+        .name("coredump".to_string())
+        .stack_size(UPLOAD_STACK_SIZE)
+        .spawn(move || match upload_core_dump(&url) {
+            Ok(true) => log::info!("Core dump sent to {url}"),
+            Ok(false) => log::info!("No core dump to send"),
+            Err(e) => {
+                log::error!("Failed to send the core dump: {:#?}", e);
+                error::record(&e);
+            }
+        })
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    Ok(())
+}
@@ -0,0 +1,656 @@
+use crate::{
+    alarm::{Alarm, Alarms, Ringing},
+    buzzer::Buzzer,
+    climate,
+    command::Device,
+    countdown::{self, Remaining},
+    crashlog, dilation,
+    display::{self, DisplayMessage, DisplayMode, Tm1637},
+    error::{self, AppError},
+    events::{self, Events, ServiceState},
+    logs,
+    menu::SettingsMenu,
+    rtttl::Tune,
+    settings::{self, Settings},
+    solar,
+    sunrise::Sunrise,
+    time::{self, Sntp, TimeSource},
+    utils, wifi, world,
+};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+use chrono_tz::Tz;
+use esp_idf_svc::{
+    hal::{
+        delay::FreeRtos,
+        gpio::{IOPin, OutputPin},
+        reset,
+    },
+    sys::sntp_restart,
+};
+use log::LevelFilter;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Seconds counted down on the display before the settings are erased.
+const FACTORY_RESET_COUNTDOWN: u8 = 3;
+
+/// Time, in milliseconds, the code of a new error stays on the display.
+const ERROR_DISPLAY_MS: u64 = 10_000;
+
+/// Time, in milliseconds, before the code of an error already shown takes over the
+/// display again, so a service failing over and over leaves the time readable.
+const ERROR_REPEAT_MS: u64 = 10 * 60_000;
+
+/// Handles to every service a [crate::command::Command] can act on.
+///
+/// Cloning is cheap, all the services are shared behind `Arc`s.
+pub struct Clock<CLK, DIO>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    pub display: Tm1637<CLK, DIO>,
+    pub settings: settings::Store,
+    pub events: Events,
+    /// The SNTP client, or `None` if it failed to start.
+    pub sntp: Option<Arc<Sntp>>,
+    pub alarms: Alarms,
+    pub menu: SettingsMenu,
+    pub buzzer: Buzzer,
+    pub sunrise: Sunrise,
+}
+
+impl<CLK, DIO> Clone for Clock<CLK, DIO>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    fn clone(&self) -> Self {
+        Clock {
+            display: self.display.clone(),
+            settings: self.settings.clone(),
+            events: self.events.clone(),
+            sntp: self.sntp.clone(),
+            alarms: self.alarms.clone(),
+            menu: self.menu.clone(),
+            buzzer: self.buzzer.clone(),
+            sunrise: self.sunrise.clone(),
+        }
+    }
+}
+
+impl<CLK, DIO> Device for Clock<CLK, DIO>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    type Error = AppError;
+
+    fn status(&self) -> Result<String, AppError> {
+        let status = self.events.status();
+
+        let mut summary = format!(
+            "Time: {} ({})\nSync: {}\nDisplay: {}, brightness {}, mode {:?}\nWi-Fi: {}",
+            status.time,
+            status.timezone,
+            if status.synced {
+                "completed"
+            } else {
+                "not synchronized"
+            },
+            if status.display.on { "on" } else { "off" },
+            status.display.brightness,
+            status.mode,
+            status.ssid,
+        );
+
+        let source = match status.time_source {
+            Some(TimeSource::Gps) => "GPS",
+            Some(TimeSource::Sntp) => "SNTP",
+            Some(TimeSource::Manual) => "set by hand",
+            Some(TimeSource::Rtc) => "RTC",
+            None => "none",
+        };
+        summary.push_str(&format!("\nTime source: {source}"));
+
+        if let Some(boot) = crashlog::entries().first() {
+            summary.push_str(&format!("\nBoot {} after {}", boot.boot, boot.reason));
+            if let Some(panic) = &boot.panic {
+                summary.push_str(&format!(", {panic}"));
+            }
+        }
+
+        if let Some(fault) = error::latest() {
+            summary.push_str(&format!(
+                "\nLast error: {} {} ({} s ago)",
+                fault.code,
+                fault.message,
+                time::uptime_ms().saturating_sub(fault.uptime_ms) / 1000
+            ));
+        }
+
+        for (name, state) in status.services.impaired() {
+            match state {
+                ServiceState::Failed(reason) => {
+                    summary.push_str(&format!("\nService {name}: failed, {reason}"))
+                }
+                ServiceState::Degraded(reason) => {
+                    summary.push_str(&format!("\nService {name}: degraded, {reason}"))
+                }
+                _ => {}
+            }
+        }
+
+        let location = self.settings.lock().unwrap().get().location;
+        if let Some(location) = location {
+            let now = time::now();
+            let day = solar::today(&location, &now);
+            let local = |time: Option<DateTime<Utc>>| {
+                time.map(|time| {
+                    time.with_timezone(&now.timezone())
+                        .format("%H:%M")
+                        .to_string()
+                })
+                .unwrap_or_else(|| "--:--".to_string())
+            };
+            summary.push_str(&format!(
+                "\nSun: dawn {}, rise {}, set {}, dusk {}",
+                local(day.dawn()),
+                local(day.sunrise()),
+                local(day.sunset()),
+                local(day.dusk())
+            ));
+        }
+
+        for zone in &status.world {
+            summary.push_str(&format!(
+                "\n{}: {} (UTC{}, {})",
+                zone.label, zone.time, zone.offset, zone.timezone
+            ));
+        }
+
+        if let Some(reading) = status.climate {
+            summary.push_str(&format!(
+                "\nClimate: {:.1} °{}",
+                reading.temperature,
+                reading.unit.symbol()
+            ));
+            if let Some(humidity) = reading.humidity {
+                summary.push_str(&format!(", {humidity:.0}% humidity"));
+            }
+        }
+
+        if let Some(rtc) = status.rtc {
+            summary.push_str(&format!("\nRTC: {:.2} °C", rtc.temperature));
+            if rtc.lost_power {
+                summary.push_str(", time lost");
+            }
+            if let Some(drift) = rtc.drift_seconds {
+                summary.push_str(&format!(", drifted {drift} s"));
+            }
+        }
+
+        Ok(summary)
+    }
+
+    fn set_brightness(&self, brightness: u8) -> Result<(), AppError> {
+        self.display.lock().unwrap().set_brightness(brightness)?;
+        log::info!("Brightness updated to level {brightness}");
+
+        Ok(())
+    }
+
+    fn set_timezone(&self, timezone: Tz) -> Result<(), AppError> {
+        self.settings
+            .lock()
+            .unwrap()
+            .update(|settings| settings.timezone = timezone.name().to_string())?;
+        self.events.update(|status| {
+            status.timezone = timezone.to_string();
+            status.time = events::format_time(&time::get_time());
+        });
+        log::info!("Timezone updated to {timezone}");
+
+        Ok(())
+    }
+
+    fn set_wifi(&self, ssid: &str, password: &str) -> Result<(), AppError> {
+        self.settings.lock().unwrap().update(|settings| {
+            settings.wifi_ssid = ssid.to_string();
+            settings.wifi_password = password.to_string();
+        })?;
+        log::info!("Wi-Fi credentials updated for `{ssid}`");
+
+        Ok(())
+    }
+
+    fn set_twelve_hour(&self, twelve_hour: bool) -> Result<(), AppError> {
+        self.settings
+            .lock()
+            .unwrap()
+            .update(|settings| settings.twelve_hour = twelve_hour)?;
+        log::info!("12-hour format {}", if twelve_hour { "on" } else { "off" });
+
+        Ok(())
+    }
+
+    fn set_alarm(&self, index: usize, time: Option<(u8, u8)>) -> Result<(), AppError> {
+        self.settings.lock().unwrap().update(|settings| {
+            if settings.alarms.len() <= index {
+                settings.alarms.resize(index + 1, Alarm::default());
+            }
+            let alarm = &mut settings.alarms[index];
+            alarm.enabled = time.is_some();
+            if let Some((hour, minute)) = time {
+                alarm.hour = hour;
+                alarm.minute = minute;
+            }
+        })?;
+        log::info!("Alarm {} updated to {:?}", index + 1, time);
+
+        Ok(())
+    }
+
+    fn set_time(
+        &self,
+        date: Option<NaiveDate>,
+        time: Option<NaiveTime>,
+    ) -> Result<NaiveDateTime, AppError> {
+        let now = time::now().naive_local();
+        let local = date
+            .unwrap_or(now.date())
+            .and_time(time.unwrap_or(now.time()));
+
+        let set = time::set_manual_time(local)?;
+        self.events.update(|status| {
+            status.time_source = time::time_source();
+            status.synced = time::is_synchronized();
+            status.time = events::format_time(&time::get_time());
+        });
+
+        Ok(set.naive_local())
+    }
+
+    fn sync_time(&self) -> Result<(), AppError> {
+        let sntp = self
+            .sntp
+            .as_ref()
+            .ok_or_else(|| AppError::Sntp("the client is not running".to_string()))?;
+        unsafe { sntp_restart() };
+
+        // Keeps the message up until the sync completes or times out
+        self.events
+            .update(|status| status.mode = DisplayMode::Manual);
+        if let Err(e) = display::write(&self.display, DisplayMessage::Sync.as_bytes()) {
+            log::warn!("Failed to show the sync message: {e}");
+        }
+
+        let result = time::init_sntp(sntp);
+        if result.is_ok() {
+            if let Err(e) = display::update_display_time(&self.display) {
+                log::warn!("Failed to show the time: {e}");
+            }
+        }
+        self.events.update(|status| {
+            status.synced = time::is_synchronized();
+            status.time = events::format_time(&time::get_time());
+            status.mode = DisplayMode::Clock;
+        });
+        result?;
+
+        log::info!("Time sync completed!");
+
+        Ok(())
+    }
+
+    fn cycle_mode(&self) -> Result<String, AppError> {
+        let now = time::now();
+        let timer_running = self.alarms.lock().unwrap().timer_remaining(&now).is_some();
+
+        let settings = self.settings.lock().unwrap().get().clone();
+
+        let status = self.events.status();
+        let available = |mode: DisplayMode| match mode {
+            DisplayMode::Timer => timer_running,
+            DisplayMode::World => !settings.world_clock.is_empty(),
+            DisplayMode::Sun => settings.location.is_some(),
+            DisplayMode::Climate => status.climate.is_some(),
+            DisplayMode::Dilation => settings.dilation.start.is_some(),
+            DisplayMode::Countdown => countdown::next_event(&settings.events, &now).is_some(),
+            _ => true,
+        };
+
+        let mut mode = status.mode.next();
+        while !available(mode) {
+            mode = mode.next();
+        }
+        self.events.update(|status| status.mode = mode);
+
+        Ok(mode.name().to_string())
+    }
+
+    fn start_timer(&self, minutes: u32) -> Result<(), AppError> {
+        self.alarms
+            .lock()
+            .unwrap()
+            .start_timer(&time::now(), minutes);
+        self.events
+            .update(|status| status.mode = DisplayMode::Timer);
+        log::info!("Timer started for {minutes} minute(s)");
+
+        Ok(())
+    }
+
+    fn snooze(&self) -> Result<Option<Ringing>, AppError> {
+        let snoozed = self.alarms.lock().unwrap().snooze(&time::now());
+        self.events.update(|status| status.ringing = None);
+
+        Ok(snoozed)
+    }
+
+    fn dismiss(&self) -> Result<bool, AppError> {
+        let dismissed = self.alarms.lock().unwrap().dismiss();
+        let cancelled = self.cancel_sunrise()?;
+        self.events.update(|status| status.ringing = None);
+
+        Ok(dismissed || cancelled)
+    }
+
+    fn cancel_sunrise(&self) -> Result<bool, AppError> {
+        let cancelled = self.sunrise.lock().unwrap().cancel();
+        self.events.update(|status| status.sunrise = false);
+        if cancelled {
+            log::info!("Sunrise cancelled");
+        }
+
+        Ok(cancelled)
+    }
+
+    fn reboot(&self) -> Result<(), AppError> {
+        log::warn!("Rebooting");
+        reset::restart();
+    }
+
+    fn factory_reset(&self) -> Result<(), AppError> {
+        log::warn!("Factory reset requested");
+
+        for remaining in (1..=FACTORY_RESET_COUNTDOWN).rev() {
+            let message = DisplayMessage::Reset(remaining).as_bytes();
+            if let Err(e) = display::write(&self.display, message) {
+                log::error!("Failed to show the reset countdown: {:#?}", e);
+            }
+            FreeRtos::delay_ms(1000);
+        }
+
+        self.settings.lock().unwrap().erase()?;
+        wifi::erase_credentials()?;
+
+        self.reboot()
+    }
+
+    fn set_log_level(&self, target: &str, level: LevelFilter) -> Result<(), AppError> {
+        logs::set_level(target, level)?;
+        log::info!("Log level of `{target}` set to {level}");
+
+        Ok(())
+    }
+}
+
+/// State kept by the main loop between two calls to [Clock::tick].
+#[derive(Debug, Default)]
+pub struct Ticker {
+    minute: Option<(u32, u32)>,
+    ringing: Option<Ringing>,
+    night: Option<bool>,
+    day_brightness: Option<u8>,
+    /// The error code on the display and the [time::uptime_ms] it appeared at.
+    fault: Option<(String, u64)>,
+    /// The [time::uptime_ms] each error code last appeared at, by code.
+    faults_shown: BTreeMap<String, u64>,
+}
+
+impl Ticker {
+    /// Returns the error code to show instead of the current mode, if any.
+    ///
+    /// The code of an error shows for [ERROR_DISPLAY_MS] when the error occurs, but at
+    /// most once per [ERROR_REPEAT_MS] however often the same error is recorded again.
+    fn fault(&mut self, now_ms: u64) -> Option<String> {
+        if let Some(latest) = error::latest()
+            .filter(|fault| now_ms.saturating_sub(fault.uptime_ms) < ERROR_DISPLAY_MS)
+        {
+            let due = self
+                .faults_shown
+                .get(&latest.code)
+                .map_or(true, |&shown_ms| {
+                    now_ms.saturating_sub(shown_ms) >= ERROR_REPEAT_MS
+                });
+            if due {
+                self.faults_shown.insert(latest.code.clone(), now_ms);
+                self.fault = Some((latest.code, now_ms));
+            }
+        }
+
+        match &self.fault {
+            Some((code, since_ms)) if now_ms.saturating_sub(*since_ms) < ERROR_DISPLAY_MS => {
+                Some(code.clone())
+            }
+            _ => {
+                self.fault = None;
+                None
+            }
+        }
+    }
+}
+
+impl<CLK, DIO> Clock<CLK, DIO>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    /// Advances the alarms and refreshes the display, called a few times per second.
+    ///
+    /// The frame of the current [DisplayMode] is only written when it changes, and
+    /// the display blinks once per second and the buzzer plays while an alarm or the timer rings.
+    /// The countdown page also blinks on the day of its event, without the buzzer.
+    ///
+    /// # Parameters
+    /// - `ticker`: The [Ticker] carried over from the previous call.
+    ///
+    /// # Returns
+    /// `Ok(())` if the display is up to date, or an [AppError] if writing to it fails.
+    pub fn tick(&self, ticker: &mut Ticker) -> Result<(), AppError> {
+        let now = time::now();
+        let settings = self.settings.lock().unwrap().get().clone();
+
+        let (started, ringing, remaining) = {
+            let mut scheduler = self.alarms.lock().unwrap();
+            let started = scheduler.tick(&now, &settings.alarms);
+            (
+                started,
+                scheduler.ringing(),
+                scheduler.timer_remaining(&now),
+            )
+        };
+        if let Some(started) = started {
+            log::info!("{:?} ringing", started);
+        }
+
+        let sunrise = {
+            let mut dawn = self.sunrise.lock().unwrap();
+            let alarm_ringing = matches!(ringing, Some(Ringing::Alarm(_)));
+            if dawn.tick(
+                &now,
+                &settings.alarms,
+                settings.sunrise_minutes,
+                alarm_ringing,
+            ) {
+                log::info!("Sunrise started");
+            }
+            dawn.progress().is_some()
+        };
+
+        if ringing != ticker.ringing {
+            ticker.ringing = ringing;
+            let tune = match ringing {
+                Some(Ringing::Alarm(index)) => settings.alarms.get(index).map(|alarm| alarm.tune),
+                Some(Ringing::Timer) => Some(Tune::default()),
+                None => None,
+            };
+            match tune {
+                Some(tune) => self
+                    .buzzer
+                    .play(tune.melody(), settings.buzzer_volume, true),
+                None => self.buzzer.stop(),
+            }
+        }
+
+        let solar_day = settings
+            .location
+            .map(|location| solar::today(&location, &now));
+        let night = solar_day.map(|day| day.is_night(now.to_utc()));
+        if night != ticker.night {
+            self.follow_night(ticker, &settings, night)?;
+        }
+
+        let event = countdown::next_event(&settings.events, &now);
+
+        let status = self.events.status();
+        let mut mode = status.mode;
+        let minute = (now.hour(), now.minute());
+        if ticker.minute != Some(minute) {
+            ticker.minute = Some(minute);
+            if mode == DisplayMode::Manual {
+                mode = DisplayMode::Clock;
+            }
+        }
+        if (mode == DisplayMode::Timer && remaining.is_none())
+            || (mode == DisplayMode::Climate && status.climate.is_none())
+            || (mode == DisplayMode::World && settings.world_clock.is_empty())
+            || (mode == DisplayMode::Sun && solar_day.is_none())
+            || (mode == DisplayMode::Dilation && settings.dilation.start.is_none())
+            || (mode == DisplayMode::Countdown && event.is_none())
+        {
+            mode = DisplayMode::Clock;
+        }
+
+        self.events.update(|status| {
+            status.time = events::format_time(&time::get_time());
+            status.synced = time::is_synchronized();
+            // SNTP keeps polling after a failed boot sync, and GPS may lock on late
+            if status.synced {
+                status.services.time = ServiceState::Running;
+            }
+            status.world = world::zone_times(&settings.world_clock, now.to_utc());
+            status.mode = mode;
+            status.ringing = ringing;
+            status.sunrise = sunrise;
+        });
+
+        let menu_text = {
+            let mut menu = self.menu.lock().unwrap();
+            if menu.tick(time::uptime_ms()) {
+                log::info!("Settings menu timed out");
+            }
+            menu.text(time::uptime_ms())
+        };
+
+        // The colon blinks while the time is not synchronized by SNTP or GPS
+        let colon = time::is_synchronized() || now.second() % 2 == 0;
+        // A new error shows its code, e.g. `E-11`, for a while
+        let fault = ticker.fault(time::uptime_ms());
+        let frame = match mode {
+            _ if menu_text.is_some() => menu_text.as_deref().map(utils::text_frame),
+            _ if fault.is_some() => fault.as_deref().map(utils::text_frame),
+            DisplayMode::Clock if settings.twelve_hour => {
+                let (_, hour) = now.hour12();
+                let mut frame = display::pair_frame(hour, now.minute(), colon);
+                if hour < 10 {
+                    frame[0] = 0;
+                }
+                Some(frame)
+            }
+            DisplayMode::Clock => Some(display::pair_frame(now.hour(), now.minute(), colon)),
+            DisplayMode::Seconds => Some(display::pair_frame(now.minute(), now.second(), true)),
+            DisplayMode::Date => Some(display::pair_frame(now.day(), now.month(), false)),
+            DisplayMode::World => {
+                world::page_text(&settings.world_clock, now.to_utc(), settings.twelve_hour)
+                    .map(|text| utils::text_frame(&text))
+            }
+            DisplayMode::Sun => {
+                solar_day.map(|day| utils::text_frame(&solar::page_text(&day, &now)))
+            }
+            DisplayMode::Climate => status
+                .climate
+                .map(|reading| utils::text_frame(&climate::page_text(&reading, now.second()))),
+            DisplayMode::Dilation => settings
+                .dilation
+                .earth_seconds(now.to_utc())
+                .map(|seconds| utils::text_frame(&dilation::elapsed_text(seconds))),
+            DisplayMode::Countdown => event.map(|(event, remaining)| {
+                utils::text_frame(&countdown::page_text(event, remaining, now.second()))
+            }),
+            DisplayMode::Timer => remaining.map(|left| {
+                let seconds = left.num_seconds() as u32;
+                display::pair_frame(seconds / 60, seconds % 60, true)
+            }),
+            DisplayMode::Manual => None,
+        };
+
+        let mut display = self.display.lock().unwrap();
+        if let Some(frame) = frame.filter(|frame| *frame != display.state().frame) {
+            display.write(frame)?;
+        }
+
+        // The name of an event flashes on its day, like a ringing alarm
+        let celebrating = mode == DisplayMode::Countdown
+            && menu_text.is_none()
+            && event.is_some_and(|(_, remaining)| remaining == Remaining::Today);
+        let lit = (ringing.is_none() && !celebrating) || now.second() % 2 == 0;
+        if display.state().on != lit {
+            display.set_on(lit)?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the night brightness and the dusk-to-dawn lamp when night falls or ends.
+    ///
+    /// The brightness of the day is restored at dawn; the lamp is only switched on
+    /// actual changes, not on the first tick after boot.
+    ///
+    /// # Parameters
+    /// - `ticker`: The [Ticker] remembering the previous state.
+    /// - `settings`: The current [Settings].
+    /// - `night`: Whether it is night, or `None` without a location.
+    fn follow_night(
+        &self,
+        ticker: &mut Ticker,
+        settings: &Settings,
+        night: Option<bool>,
+    ) -> Result<(), AppError> {
+        let previous = std::mem::replace(&mut ticker.night, night);
+        let night = night.unwrap_or(false);
+        log::info!("{}", if night { "Night fell" } else { "Day broke" });
+
+        match settings.night_brightness {
+            Some(brightness) if night => {
+                let current = self.display.lock().unwrap().state().brightness;
+                ticker.day_brightness.get_or_insert(current);
+                self.set_brightness(brightness)?;
+            }
+            _ => {
+                if let Some(brightness) = ticker.day_brightness.take() {
+                    self.set_brightness(brightness)?;
+                }
+            }
+        }
+
+        if settings.lamp.dusk_to_dawn && previous.is_some() && settings.lamp.on != night {
+            self.settings
+                .lock()
+                .unwrap()
+                .update(|s| s.lamp.on = night)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Seconds in a Julian year, the year used by astronomers.
+const SECONDS_PER_YEAR: f64 = 365.25 * 86_400.0;
+
+/// Earth seconds passing for each second on Miller's planet: one hour there is seven years
+/// on Earth.
+pub const DEFAULT_FACTOR: f64 = 7.0 * SECONDS_PER_YEAR / 3600.0;
+
+/// Largest factor accepted, past it a second is already longer than the display can show.
+pub const MAX_FACTOR: f64 = 1e9;
+
+/// Options of the time-dilation mode, shown as [crate::display::DisplayMode::Dilation].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct Dilation {
+    /// When the clock "landed", in seconds since the Unix epoch; the mode is unavailable
+    /// until one is chosen.
+    pub start: Option<i64>,
+    /// Earth seconds passing for each second of the clock, see [DEFAULT_FACTOR].
+    pub factor: f64,
+}
+
+impl Default for Dilation {
+    fn default() -> Self {
+        Dilation {
+            start: None,
+            factor: DEFAULT_FACTOR,
+        }
+    }
+}
+
+impl Dilation {
+    /// Whether the factor is a number from 1 to [MAX_FACTOR].
+    pub fn is_valid(&self) -> bool {
+        (1.0..=MAX_FACTOR).contains(&self.factor)
+    }
+
+    /// Returns the Earth time elapsed since the start, in seconds.
+    ///
+    /// # Parameters
+    /// - `now`: The current time, from the same clock as [crate::time::get_time].
+    ///
+    /// # Returns
+    /// The dilated time, `0` before the start, or `None` if no start is chosen.
+    pub fn earth_seconds(&self, now: DateTime<Utc>) -> Option<f64> {
+        let start = DateTime::from_timestamp(self.start?, 0)?;
+        let elapsed = (now - start).num_milliseconds().max(0) as f64 / 1000.0;
+
+        Some(elapsed * self.factor)
+    }
+}
+
+/// Returns the text showing an Earth time on the 4-digit display, in the largest unit
+/// it reaches: minutes (` 42n`), hours (` 23h`), days (`364d`) or years (`812y`).
+///
+/// From 1000 years on, the years fill the 4 digits (`2024`), then switch to a power of
+/// ten (`3E4y` for 30 000 years).
+pub fn elapsed_text(earth_seconds: f64) -> String {
+    let seconds = earth_seconds.max(0.0);
+    let years = seconds / SECONDS_PER_YEAR;
+
+    if seconds < 3600.0 {
+        format!("{:>3}n", (seconds / 60.0) as u32)
+    } else if seconds < 86_400.0 {
+        format!("{:>3}h", (seconds / 3600.0) as u32)
+    } else if years < 1.0 {
+        format!("{:>3}d", (seconds / 86_400.0) as u32)
+    } else if years < 1000.0 {
+        format!("{:>3}y", years as u32)
+    } else if years < 10_000.0 {
+        format!("{}", years as u32)
+    } else {
+        let exponent = years.log10().floor();
+        let mantissa = (years / 10f64.powf(exponent)) as u32;
+        if exponent < 10.0 {
+            format!("{mantissa}E{exponent}y")
+        } else {
+            "----".to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: f64 = 3600.0;
+    const DAY: f64 = 86_400.0;
+
+    fn dilation(start: i64, factor: f64) -> Dilation {
+        Dilation {
+            start: Some(start),
+            factor,
+        }
+    }
+
+    fn at(seconds: i64, millis: u32) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, millis * 1_000_000).unwrap()
+    }
+
+    #[test]
+    fn defaults_to_millers_planet() {
+        // 7 years of 365.25 days in an hour
+        assert_eq!(DEFAULT_FACTOR, 61_362.0);
+        assert_eq!(Dilation::default().start, None);
+        assert!(Dilation::default().is_valid());
+    }
+
+    #[test]
+    fn validates_the_factor() {
+        assert!(dilation(0, 1.0).is_valid());
+        assert!(dilation(0, MAX_FACTOR).is_valid());
+        assert!(!dilation(0, 0.5).is_valid());
+        assert!(!dilation(0, 0.0).is_valid());
+        assert!(!dilation(0, -2.0).is_valid());
+        assert!(!dilation(0, MAX_FACTOR * 2.0).is_valid());
+        assert!(!dilation(0, f64::NAN).is_valid());
+        assert!(!dilation(0, f64::INFINITY).is_valid());
+    }
+
+    #[test]
+    fn fills_missing_fields_with_defaults() {
+        let dilation: Dilation = serde_json::from_str(r#"{"start": 1000}"#).unwrap();
+        assert_eq!(dilation.start, Some(1000));
+        assert_eq!(dilation.factor, DEFAULT_FACTOR);
+    }
+
+    #[test]
+    fn dilates_the_elapsed_time() {
+        let start = 1_792_300_000;
+        assert_eq!(
+            dilation(start, 2.0).earth_seconds(at(start + 1, 0)),
+            Some(2.0)
+        );
+        assert_eq!(
+            dilation(start, 10.0).earth_seconds(at(start + 1, 500)),
+            Some(15.0)
+        );
+        // An hour on the planet is seven years on Earth
+        assert_eq!(
+            dilation(start, DEFAULT_FACTOR).earth_seconds(at(start + 3600, 0)),
+            Some(7.0 * SECONDS_PER_YEAR)
+        );
+    }
+
+    #[test]
+    fn stays_at_zero_before_the_start() {
+        let start = 1_792_300_000;
+        assert_eq!(dilation(start, 2.0).earth_seconds(at(start, 0)), Some(0.0));
+        assert_eq!(
+            dilation(start, 2.0).earth_seconds(at(start - 60, 0)),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn needs_a_start() {
+        let now = at(1_792_300_000, 0);
+        assert_eq!(Dilation::default().earth_seconds(now), None);
+        assert_eq!(dilation(i64::MAX, 2.0).earth_seconds(now), None);
+    }
+
+    #[test]
+    fn shows_minutes_hours_and_days() {
+        assert_eq!(elapsed_text(0.0), "  0n");
+        assert_eq!(elapsed_text(59.0), "  0n");
+        assert_eq!(elapsed_text(60.0), "  1n");
+        assert_eq!(elapsed_text(HOUR - 1.0), " 59n");
+        assert_eq!(elapsed_text(HOUR), "  1h");
+        assert_eq!(elapsed_text(DAY - 1.0), " 23h");
+        assert_eq!(elapsed_text(DAY), "  1d");
+        assert_eq!(elapsed_text(SECONDS_PER_YEAR - 1.0), "365d");
+    }
+
+    #[test]
+    fn shows_years() {
+        assert_eq!(elapsed_text(SECONDS_PER_YEAR), "  1y");
+        assert_eq!(elapsed_text(7.0 * SECONDS_PER_YEAR), "  7y");
+        assert_eq!(elapsed_text(999.5 * SECONDS_PER_YEAR), "999y");
+        assert_eq!(elapsed_text(1000.5 * SECONDS_PER_YEAR), "1000");
+        assert_eq!(elapsed_text(2024.5 * SECONDS_PER_YEAR), "2024");
+        assert_eq!(elapsed_text(9999.5 * SECONDS_PER_YEAR), "9999");
+    }
+
+    #[test]
+    fn shows_powers_of_ten() {
+        assert_eq!(elapsed_text(35_000.0 * SECONDS_PER_YEAR), "3E4y");
+        assert_eq!(elapsed_text(1.5e6 * SECONDS_PER_YEAR), "1E6y");
+        assert_eq!(elapsed_text(9.5e9 * SECONDS_PER_YEAR), "9E9y");
+        assert_eq!(elapsed_text(2e10 * SECONDS_PER_YEAR), "----");
+    }
+
+    #[test]
+    fn handles_odd_inputs() {
+        assert_eq!(elapsed_text(-5.0), "  0n");
+        assert_eq!(elapsed_text(f64::NAN), "  0n");
+        assert_eq!(elapsed_text(f64::INFINITY), "----");
+        assert_eq!(elapsed_text(f64::MAX), "----");
+    }
+
+    #[test]
+    fn always_fits_the_display() {
+        let mut seconds = 1.0;
+        while seconds < 1e30 {
+            assert_eq!(elapsed_text(seconds).chars().count(), 4, "{seconds}");
+            seconds *= 1.7;
+        }
+    }
+}
@@ -1,29 +1,215 @@
-use crate::{error::AppError, time, utils::DISPLAY_DIGIT};
+use crate::{error::AppError, events::Events, time, utils::DISPLAY_DIGIT};
 use esp_idf_svc::hal::{
     delay::FreeRtos,
     gpio::{IOPin, InputOutput, Output, OutputPin, PinDriver},
 };
+use serde::Serialize;
 use std::sync::{Arc, Mutex};
 use tm1637::TM1637;
 
-/// Type alias for the [TM1637] display using pin drivers and [FreeRtos] as time control.
-/// This is an ´Arc<Mutex<>>´ to ensure thread safety and shared access to the display.
-pub type Tm1637<CLK, DIO> = Arc<
-    Mutex<
-        TM1637<
-            'static,
-            PinDriver<'static, CLK, Output>,
-            PinDriver<'static, DIO, InputOutput>,
-            FreeRtos,
-        >,
-    >,
+/// Brightness level (0 to 7) applied when the display is initialized.
+pub const DEFAULT_BRIGHTNESS: u8 = 5;
+
+/// Type alias for the [TM1637] driver using pin drivers and [FreeRtos] as time control.
+type Driver<CLK, DIO> = TM1637<
+    'static,
+    PinDriver<'static, CLK, Output>,
+    PinDriver<'static, DIO, InputOutput>,
+    FreeRtos,
 >;
 
+/// Type alias for the [Display] wrapping the [TM1637] driver.
+/// This is an ´Arc<Mutex<>>´ to ensure thread safety and shared access to the display.
+pub type Tm1637<CLK, DIO> = Arc<Mutex<Display<CLK, DIO>>>;
+
+/// What the display is physically showing, mirrored since the TM1637 cannot be read back.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct DisplayState {
+    /// The last 4-byte frame written, one byte of segments per digit.
+    pub frame: [u8; 4],
+    /// The current brightness level (0 to 7).
+    pub brightness: u8,
+    /// Whether the digits are lit, the frame is kept while the display is off.
+    pub on: bool,
+}
+
+impl Default for DisplayState {
+    fn default() -> Self {
+        DisplayState {
+            frame: [0; 4],
+            brightness: DEFAULT_BRIGHTNESS,
+            on: true,
+        }
+    }
+}
+
+/// The [TM1637] driver together with a mirror of its [DisplayState].
+///
+/// Every change is published to the [Events] hub, so the web interface can
+/// render a live replica of the display.
+pub struct Display<CLK, DIO>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    /// `None` for a [headless_display], which only keeps the mirror.
+    driver: Option<Driver<CLK, DIO>>,
+    state: DisplayState,
+    events: Events,
+}
+
+impl<CLK, DIO> Display<CLK, DIO>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    /// Returns the mirrored state of the display.
+    pub fn state(&self) -> DisplayState {
+        self.state
+    }
+
+    /// Whether the display has no driver, see [headless_display].
+    pub fn is_headless(&self) -> bool {
+        self.driver.is_none()
+    }
+
+    /// Initializes the driver and applies the mirrored brightness.
+    pub fn init(&mut self) -> Result<(), AppError> {
+        if let Some(driver) = &mut self.driver {
+            driver.init()?;
+            driver.set_brightness(self.state.brightness)?;
+        }
+
+        Ok(())
+    }
+
+    /// Shows a 4-byte frame, or only keeps it in the mirror while the display is off.
+    pub fn write(&mut self, frame: [u8; 4]) -> Result<(), AppError> {
+        if let Some(driver) = self.driver.as_mut().filter(|_| self.state.on) {
+            driver.clear()?;
+            driver.print_raw(0, &frame)?;
+        }
+        self.state.frame = frame;
+        self.publish();
+
+        Ok(())
+    }
+
+    /// Sets the brightness level (0 to 7).
+    pub fn set_brightness(&mut self, brightness: u8) -> Result<(), AppError> {
+        if let Some(driver) = &mut self.driver {
+            driver.set_brightness(brightness)?;
+        }
+        self.state.brightness = brightness;
+        self.publish();
+
+        Ok(())
+    }
+
+    /// Turns the digits on, restoring the last frame, or off, blanking them.
+    pub fn set_on(&mut self, on: bool) -> Result<(), AppError> {
+        match &mut self.driver {
+            Some(driver) if on => driver.print_raw(0, &self.state.frame)?,
+            Some(driver) => driver.clear()?,
+            None => {}
+        }
+        self.state.on = on;
+        self.publish();
+
+        Ok(())
+    }
+
+    /// Sends the mirrored state to the [Events] hub.
+    fn publish(&self) {
+        let state = self.state;
+        self.events.update(|status| status.display = state);
+    }
+}
+
+/// Enum representing what the display is currently showing.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayMode {
+    /// The current time, as `HH:MM`.
+    Clock,
+    /// The minutes and seconds of the current time, as `MM:SS`.
+    Seconds,
+    /// The current date, as `DD MM`.
+    Date,
+    /// The time in each secondary zone, after its label, e.g. `NYC` then `08:30`.
+    World,
+    /// The sunrise and the sunset of the day, each after its label, e.g. `rISE` then `06:42`.
+    Sun,
+    /// The temperature and the humidity from the climate sensor, in turns.
+    Climate,
+    /// The Earth time elapsed since landing on Miller's planet, e.g. `  7y`.
+    Dilation,
+    /// The days left until the next event, or `HH:MM` on the final day, after its name.
+    Countdown,
+    /// The time left on the countdown timer, as `MM:SS`.
+    Timer,
+    /// Digits set manually through the web interface, until the next minute.
+    Manual,
+}
+
+impl DisplayMode {
+    /// Returns the mode following this one when cycling through them from the buttons.
+    pub fn next(self) -> DisplayMode {
+        match self {
+            DisplayMode::Clock => DisplayMode::Seconds,
+            DisplayMode::Seconds => DisplayMode::Date,
+            DisplayMode::Date => DisplayMode::World,
+            DisplayMode::World => DisplayMode::Sun,
+            DisplayMode::Sun => DisplayMode::Climate,
+            DisplayMode::Climate => DisplayMode::Dilation,
+            DisplayMode::Dilation => DisplayMode::Countdown,
+            DisplayMode::Countdown => DisplayMode::Timer,
+            DisplayMode::Timer | DisplayMode::Manual => DisplayMode::Clock,
+        }
+    }
+
+    /// Returns the name of the mode, as serialized in the status.
+    pub fn name(self) -> &'static str {
+        match self {
+            DisplayMode::Clock => "clock",
+            DisplayMode::Seconds => "seconds",
+            DisplayMode::Date => "date",
+            DisplayMode::World => "world",
+            DisplayMode::Sun => "sun",
+            DisplayMode::Climate => "climate",
+            DisplayMode::Dilation => "dilation",
+            DisplayMode::Countdown => "countdown",
+            DisplayMode::Timer => "timer",
+            DisplayMode::Manual => "manual",
+        }
+    }
+
+    /// Returns the mode with the given [DisplayMode::name], if any.
+    pub fn from_name(name: &str) -> Option<DisplayMode> {
+        [
+            DisplayMode::Clock,
+            DisplayMode::Seconds,
+            DisplayMode::Date,
+            DisplayMode::World,
+            DisplayMode::Sun,
+            DisplayMode::Climate,
+            DisplayMode::Dilation,
+            DisplayMode::Countdown,
+            DisplayMode::Timer,
+            DisplayMode::Manual,
+        ]
+        .into_iter()
+        .find(|mode| mode.name() == name)
+    }
+}
+
 /// Enum representing different display messages.
 /// Used to send specific byte patterns to the display.
 pub enum DisplayMessage {
     Init,
     Sync,
+    /// Factory reset countdown, showing the remaining seconds.
+    Reset(u8),
 }
 
 impl DisplayMessage {
@@ -50,6 +236,12 @@ impl DisplayMessage {
                 0b00110111, // n
                 0b00111001, // c
             ],
+            DisplayMessage::Reset(remaining) => [
+                0b01010000, // r
+                0b01101101, // S
+                0b01111000, // t
+                DISPLAY_DIGIT[(*remaining % 10) as usize],
+            ],
         }
     }
 }
@@ -59,15 +251,20 @@ impl DisplayMessage {
 /// # Parameters
 /// - `clk`: Pin for the clock of the display (implements [OutputPin]).
 /// - `dio`: Pin for the data of the display (implements [IOPin]).
+/// - `events`: The [Events] hub receiving the [DisplayState] changes.
 ///
 /// # Returns
 /// The result is an instance of the [TM1637] ready for interaction.
 ///
 /// # Example
 /// ```
-/// let display = get_display(clk_pin, dio_pin).expect("Failed to create display");
+/// let display = get_display(clk_pin, dio_pin, events.clone()).expect("Failed to create display");
 /// ```
-pub fn get_display<CLK, DIO>(clk: CLK, dio: DIO) -> Result<Tm1637<CLK, DIO>, AppError>
+pub fn get_display<CLK, DIO>(
+    clk: CLK,
+    dio: DIO,
+    events: Events,
+) -> Result<Tm1637<CLK, DIO>, AppError>
 where
     CLK: OutputPin,
     DIO: IOPin,
@@ -76,9 +273,29 @@ where
     let dio = Box::new(PinDriver::input_output(dio)?);
     let delay = Box::new(FreeRtos {});
 
-    let display = TM1637::new(Box::leak(clk), Box::leak(dio), Box::leak(delay));
+    let driver = TM1637::new(Box::leak(clk), Box::leak(dio), Box::leak(delay));
 
-    Ok(Arc::new(Mutex::new(display)))
+    Ok(Arc::new(Mutex::new(Display {
+        driver: Some(driver),
+        state: DisplayState::default(),
+        events,
+    })))
+}
+
+/// Creates a [Display] without a driver, for a clock whose display pins cannot be set up.
+///
+/// The mirrored state is still kept and published, so the web interface shows what the
+/// display would.
+pub fn headless_display<CLK, DIO>(events: Events) -> Tm1637<CLK, DIO>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    Arc::new(Mutex::new(Display {
+        driver: None,
+        state: DisplayState::default(),
+        events,
+    }))
 }
 
 /// Initializes the display with an initial message and sets its brightness.
@@ -101,7 +318,6 @@ where
     let init_message = DisplayMessage::Init.as_bytes();
 
     display.lock().unwrap().init()?;
-    display.lock().unwrap().set_brightness(5)?;
     write(display, init_message)?;
 
     Ok(())
@@ -127,19 +343,63 @@ where
 {
     let time = time::get_time();
 
-    let digits = [
-        DISPLAY_DIGIT[time[0] as usize],
-        DISPLAY_DIGIT[time[1] as usize] | 0b10000000,
-        DISPLAY_DIGIT[time[2] as usize],
-        DISPLAY_DIGIT[time[3] as usize],
-    ];
-
-    write(display, [digits[0], digits[1], digits[2], digits[3]])?;
+    write(
+        display,
+        digits_frame([time[0], time[1], time[2], time[3]], true),
+    )?;
 
     Ok(())
 }
 
+/// Converts 4 decimal digits into a frame, optionally lighting the colon.
+///
+/// # Example
+/// ```rust
+/// let frame = digits_frame([1, 2, 3, 4], true);
+/// assert_eq!(frame[1], DISPLAY_DIGIT[2] | 0b10000000);
+/// ```
+pub fn digits_frame(digits: [u8; 4], colon: bool) -> [u8; 4] {
+    let mut frame = digits.map(|digit| DISPLAY_DIGIT[(digit % 10) as usize]);
+    if colon {
+        frame[1] |= 0b10000000;
+    }
+
+    frame
+}
+
+/// Converts a pair of two-digit numbers, such as hours and minutes, into a frame.
+///
+/// # Example
+/// ```rust
+/// let frame = pair_frame(12, 34, true);
+/// assert_eq!(frame, digits_frame([1, 2, 3, 4], true));
+/// ```
+pub fn pair_frame(left: u32, right: u32, colon: bool) -> [u8; 4] {
+    let digits = [left / 10, left % 10, right / 10, right % 10];
+
+    digits_frame(digits.map(|digit| digit as u8), colon)
+}
+
+/// Converts up to 4 hexadecimal digits into a frame, leaving the missing positions blank.
+///
+/// Only the low nibble of each byte is used, so ASCII digits such as `b"1234"` are accepted.
+///
+/// # Example
+/// ```rust
+/// let frame = hex_frame(b"12");
+/// assert_eq!(frame, [DISPLAY_DIGIT[1], DISPLAY_DIGIT[2], 0, 0]);
+/// ```
+pub fn hex_frame(digits: &[u8]) -> [u8; 4] {
+    let mut frame = [0; 4];
+    for (segments, digit) in frame.iter_mut().zip(digits) {
+        *segments = DISPLAY_DIGIT[(digit & 0xf) as usize];
+    }
+
+    frame
+}
+
 /// Writes a 4-byte message to the display, clearing it first and then printing the message.
+/// The message is kept as the mirrored [DisplayState::frame].
 ///
 /// # Parameters
 /// - `display`: A reference to [Tm1637].
@@ -158,9 +418,5 @@ where
     CLK: OutputPin,
     DIO: IOPin,
 {
-    let mut locked_display = display.lock().unwrap();
-    locked_display.clear()?;
-    locked_display.print_raw(0, &message)?;
-
-    Ok(())
+    display.lock().unwrap().write(message)
 }
@@ -0,0 +1,217 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+
+/// I2C address of the DS3231.
+pub const ADDRESS: u8 = 0x68;
+
+/// First of the 7 timekeeping registers, the seconds.
+pub const TIME_REGISTER: u8 = 0x00;
+
+/// The status register, holding the oscillator stop flag.
+pub const STATUS_REGISTER: u8 = 0x0f;
+
+/// The most significant byte of the temperature, the least significant follows.
+pub const TEMPERATURE_REGISTER: u8 = 0x11;
+
+/// Set in the status register when the oscillator stopped, e.g. the battery ran out.
+pub const OSCILLATOR_STOPPED: u8 = 0b1000_0000;
+
+/// Set in the hours register when the hours are in 12-hour format.
+const TWELVE_HOUR: u8 = 0b0100_0000;
+
+/// Set in the hours register for PM in 12-hour format.
+const PM: u8 = 0b0010_0000;
+
+/// Set in the month register for the years from 2100.
+const CENTURY: u8 = 0b1000_0000;
+
+/// Encodes a value from 0 to 99 as binary-coded decimal.
+fn to_bcd(value: u32) -> u8 {
+    (value / 10 * 16 + value % 10) as u8
+}
+
+/// Decodes a binary-coded decimal byte, or `None` if a digit is not decimal.
+fn from_bcd(value: u8) -> Option<u32> {
+    let (tens, units) = (value >> 4, value & 0x0f);
+
+    (tens < 10 && units < 10).then_some(u32::from(tens) * 10 + u32::from(units))
+}
+
+/// Encodes a UTC date and time into the timekeeping registers, in 24-hour format.
+///
+/// # Returns
+/// The values of the registers `0x00` to `0x06`, or `None` if the year is outside of
+/// the 2000 to 2199 range the DS3231 can hold.
+///
+/// # Example
+/// ```rust
+/// let registers = encode_time(&Utc::now().naive_utc()).expect("Year out of range");
+/// ```
+pub fn encode_time(time: &NaiveDateTime) -> Option<[u8; 7]> {
+    let years = u32::try_from(time.year() - 2000)
+        .ok()
+        .filter(|years| *years < 200)?;
+    let century = if years >= 100 { CENTURY } else { 0 };
+
+    Some([
+        to_bcd(time.second()),
+        to_bcd(time.minute()),
+        to_bcd(time.hour()),
+        time.weekday().number_from_monday() as u8,
+        to_bcd(time.day()),
+        to_bcd(time.month()) | century,
+        to_bcd(years % 100),
+    ])
+}
+
+/// Decodes the timekeeping registers, in 12 or 24-hour format.
+///
+/// # Parameters
+/// - `registers`: The values of the registers `0x00` to `0x06`.
+///
+/// # Returns
+/// The date and time held by the RTC, or `None` if the registers do not hold a valid one.
+pub fn decode_time(registers: &[u8; 7]) -> Option<NaiveDateTime> {
+    let second = from_bcd(registers[0] & 0x7f)?;
+    let minute = from_bcd(registers[1] & 0x7f)?;
+
+    let hours = registers[2];
+    let hour = if hours & TWELVE_HOUR != 0 {
+        let hour = from_bcd(hours & 0x1f).filter(|hour| (1..=12).contains(hour))?;
+        hour % 12 + if hours & PM != 0 { 12 } else { 0 }
+    } else {
+        from_bcd(hours & 0x3f)?
+    };
+
+    let day = from_bcd(registers[4] & 0x3f)?;
+    let month = from_bcd(registers[5] & 0x1f)?;
+    let century = if registers[5] & CENTURY != 0 { 100 } else { 0 };
+    let year = 2000 + century + from_bcd(registers[6])?;
+
+    NaiveDate::from_ymd_opt(year as i32, month, day)?.and_hms_opt(hour, minute, second)
+}
+
+/// Decodes the temperature registers, in steps of 0.25 °C.
+///
+/// # Parameters
+/// - `msb`: The signed integer part, register `0x11`.
+/// - `lsb`: The quarters in its two upper bits, register `0x12`.
+pub fn decode_temperature(msb: u8, lsb: u8) -> f32 {
+    f32::from(i16::from_be_bytes([msb, lsb]) >> 6) * 0.25
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, s)
+            .unwrap()
+    }
+
+    #[test]
+    fn converts_bcd() {
+        assert_eq!(to_bcd(0), 0x00);
+        assert_eq!(to_bcd(7), 0x07);
+        assert_eq!(to_bcd(59), 0x59);
+        assert_eq!(to_bcd(99), 0x99);
+        assert_eq!(from_bcd(0x42), Some(42));
+        for value in 0..100 {
+            assert_eq!(from_bcd(to_bcd(value)), Some(value));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_bcd() {
+        assert_eq!(from_bcd(0x0a), None);
+        assert_eq!(from_bcd(0xa0), None);
+        assert_eq!(from_bcd(0xff), None);
+
+        let mut registers = encode_time(&datetime(2026, 10, 18, 14, 30, 5)).unwrap();
+        registers[1] = 0x5a;
+        assert_eq!(decode_time(&registers), None);
+    }
+
+    #[test]
+    fn encodes_the_registers() {
+        // A Sunday, day 7 counted from Monday
+        assert_eq!(
+            encode_time(&datetime(2026, 10, 18, 14, 30, 5)),
+            Some([0x05, 0x30, 0x14, 7, 0x18, 0x10, 0x26])
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        for time in [
+            datetime(2000, 1, 1, 0, 0, 0),
+            datetime(2024, 2, 29, 23, 59, 59),
+            datetime(2099, 12, 31, 12, 0, 0),
+            datetime(2100, 3, 1, 6, 7, 8),
+            datetime(2199, 12, 31, 23, 59, 59),
+        ] {
+            let registers = encode_time(&time).unwrap();
+            assert_eq!(decode_time(&registers), Some(time), "{time}");
+        }
+    }
+
+    #[test]
+    fn sets_the_century_bit() {
+        let registers = encode_time(&datetime(2100, 1, 1, 0, 0, 0)).unwrap();
+        assert_eq!(registers[5], CENTURY | 0x01);
+        assert_eq!(registers[6], 0x00);
+
+        let registers = encode_time(&datetime(2099, 1, 1, 0, 0, 0)).unwrap();
+        assert_eq!(registers[5] & CENTURY, 0);
+    }
+
+    #[test]
+    fn rejects_years_out_of_range() {
+        assert_eq!(encode_time(&datetime(1999, 12, 31, 23, 59, 59)), None);
+        assert_eq!(encode_time(&datetime(2200, 1, 1, 0, 0, 0)), None);
+    }
+
+    #[test]
+    fn decodes_twelve_hour_format() {
+        let at = |hours: u8| decode_time(&[0x00, 0x00, hours, 1, 0x01, 0x01, 0x26]);
+
+        assert_eq!(at(TWELVE_HOUR | 0x12), Some(datetime(2026, 1, 1, 0, 0, 0)));
+        assert_eq!(at(TWELVE_HOUR | 0x01), Some(datetime(2026, 1, 1, 1, 0, 0)));
+        assert_eq!(
+            at(TWELVE_HOUR | PM | 0x12),
+            Some(datetime(2026, 1, 1, 12, 0, 0))
+        );
+        assert_eq!(
+            at(TWELVE_HOUR | PM | 0x11),
+            Some(datetime(2026, 1, 1, 23, 0, 0))
+        );
+        assert_eq!(at(TWELVE_HOUR), None);
+        assert_eq!(at(TWELVE_HOUR | 0x13), None);
+    }
+
+    #[test]
+    fn decodes_twenty_four_hour_format() {
+        let at = |hours: u8| decode_time(&[0x00, 0x00, hours, 1, 0x01, 0x01, 0x26]);
+
+        assert_eq!(at(0x00), Some(datetime(2026, 1, 1, 0, 0, 0)));
+        assert_eq!(at(0x23), Some(datetime(2026, 1, 1, 23, 0, 0)));
+        assert_eq!(at(0x24), None);
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        // 30 February, and a zero month
+        assert_eq!(decode_time(&[0, 0, 0, 1, 0x30, 0x02, 0x26]), None);
+        assert_eq!(decode_time(&[0, 0, 0, 1, 0x01, 0x00, 0x26]), None);
+    }
+
+    #[test]
+    fn decodes_temperatures() {
+        assert_eq!(decode_temperature(0x19, 0x00), 25.0);
+        assert_eq!(decode_temperature(0x19, 0x40), 25.25);
+        assert_eq!(decode_temperature(0x19, 0xc0), 25.75);
+        assert_eq!(decode_temperature(0xff, 0xc0), -0.25);
+        assert_eq!(decode_temperature(0xe7, 0x00), -25.0);
+    }
+}
@@ -1,6 +1,31 @@
+use crate::time;
+use chrono::{DateTime, Utc};
 use esp_idf_svc::{io::EspIOError, sys::EspError};
+use serde::Serialize;
+use std::{collections::VecDeque, sync::Mutex};
+
+/// Number of errors kept by [record], the oldest are dropped first.
+pub const MAX_RECENT_ERRORS: usize = 16;
 
 /// Represents errors that can occur in the application.
+///
+/// Each variant carries a short [code](AppError::code), shown on the display as `E-03`:
+///
+/// | Code   | Variant        | Meaning                                              |
+/// |--------|----------------|------------------------------------------------------|
+/// | `E-01` | `IO`           | reading or writing a connection failed               |
+/// | `E-02` | `System`       | an ESP-IDF call failed                               |
+/// | `E-03` | `Display`      | the TM1637 display does not answer                   |
+/// | `E-04` | `Json`         | a JSON document cannot be parsed or written          |
+/// | `E-05` | `Settings`     | a setting is out of range                            |
+/// | `E-06` | `BadRequest`   | a request or a command is invalid                    |
+/// | `E-07` | `Unauthorized` | the API token is missing or wrong                    |
+/// | `E-08` | `Thread`       | a background task cannot be started                  |
+/// | `E-09` | `Sensor`       | the climate sensor, the RTC or the GPS failed        |
+/// | `E-10` | `Network`      | a UDP service or the clock group failed              |
+/// | `E-11` | `Wifi`         | the configured Wi-Fi cannot be joined                |
+/// | `E-12` | `Sntp`         | the SNTP server does not answer                      |
+/// | `E-13` | `Storage`      | the settings cannot be read from or written to flash |
 #[derive(thiserror::Error, Debug)]
 pub enum AppError {
     #[error("I/O error: {0}")]
@@ -11,6 +36,82 @@ pub enum AppError {
 
     #[error("Display error: {0}")]
     Display(String),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid settings: {0}")]
+    Settings(String),
+
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Thread error: {0}")]
+    Thread(String),
+
+    #[error("Sensor error: {0}")]
+    Sensor(String),
+
+    #[error("Network error: {0}")]
+    Network(String),
+
+    #[error("Wi-Fi error: {0}")]
+    Wifi(String),
+
+    #[error("SNTP error: {0}")]
+    Sntp(String),
+
+    #[error("Settings storage error: {0}")]
+    Storage(String),
+}
+
+impl AppError {
+    /// Whether the error was caused by invalid client input rather than by the device.
+    pub fn is_client_error(&self) -> bool {
+        matches!(
+            self,
+            AppError::Json(_)
+                | AppError::Settings(_)
+                | AppError::BadRequest(_)
+                | AppError::Unauthorized(_)
+        )
+    }
+
+    /// The HTTP status code reported to the client for this error.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            AppError::Unauthorized(_) => 401,
+            _ if self.is_client_error() => 400,
+            _ => 500,
+        }
+    }
+
+    /// The number identifying the kind of error, see the table on [AppError].
+    pub fn code(&self) -> u8 {
+        match self {
+            AppError::IO(_) => 1,
+            AppError::System(_) => 2,
+            AppError::Display(_) => 3,
+            AppError::Json(_) => 4,
+            AppError::Settings(_) => 5,
+            AppError::BadRequest(_) => 6,
+            AppError::Unauthorized(_) => 7,
+            AppError::Thread(_) => 8,
+            AppError::Sensor(_) => 9,
+            AppError::Network(_) => 10,
+            AppError::Wifi(_) => 11,
+            AppError::Sntp(_) => 12,
+            AppError::Storage(_) => 13,
+        }
+    }
+
+    /// The code as shown on the 4-digit display, e.g. `E-03`.
+    pub fn display_code(&self) -> String {
+        format!("E-{:02}", self.code())
+    }
 }
 
 impl From<tm1637::Error<EspError>> for AppError {
@@ -18,3 +119,76 @@ impl From<tm1637::Error<EspError>> for AppError {
         AppError::Display(format!("{:?}", value))
     }
 }
+
+/// An error of the device kept by [record], as listed by the API.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ErrorRecord {
+    /// The code shown on the display, e.g. `E-03`.
+    pub code: String,
+    /// The error message.
+    pub message: String,
+    /// How many times in a row the same error occurred.
+    pub count: u32,
+    /// The [time::uptime_ms] of the last occurrence.
+    pub uptime_ms: u64,
+    /// The system time of the last occurrence, only meaningful once the time is set.
+    pub time: DateTime<Utc>,
+}
+
+/// The errors kept by [record], the most recent last.
+static RECENT: Mutex<VecDeque<ErrorRecord>> = Mutex::new(VecDeque::new());
+
+/// Keeps an error of the device for [recent] and the display.
+///
+/// Client errors, such as an invalid request, are not kept. An error repeating the
+/// previous one only updates its count and time.
+///
+/// # Example
+/// ```rust
+/// if let Err(e) = wifi::init_wifi(&mut wifi) {
+///     log::error!("Failed to initialize Wi-Fi: {:#?}", e);
+///     error::record(&e);
+/// }
+/// ```
+pub fn record(error: &AppError) {
+    if error.is_client_error() {
+        return;
+    }
+
+    let code = error.display_code();
+    let message = error.to_string();
+    let uptime_ms = time::uptime_ms();
+    let now = Utc::now();
+
+    let mut recent = RECENT.lock().unwrap();
+    if let Some(last) = recent
+        .back_mut()
+        .filter(|last| last.code == code && last.message == message)
+    {
+        last.count += 1;
+        last.uptime_ms = uptime_ms;
+        last.time = now;
+        return;
+    }
+
+    if recent.len() == MAX_RECENT_ERRORS {
+        recent.pop_front();
+    }
+    recent.push_back(ErrorRecord {
+        code,
+        message,
+        count: 1,
+        uptime_ms,
+        time: now,
+    });
+}
+
+/// Returns the errors kept by [record], the most recent first.
+pub fn recent() -> Vec<ErrorRecord> {
+    RECENT.lock().unwrap().iter().rev().cloned().collect()
+}
+
+/// Returns the most recent error kept by [record], if any.
+pub fn latest() -> Option<ErrorRecord> {
+    RECENT.lock().unwrap().back().cloned()
+}
@@ -0,0 +1,285 @@
+use crate::{
+    alarm::Ringing,
+    climate::Reading,
+    display::{DisplayMode, DisplayState},
+    error::AppError,
+    health,
+    rtc::RtcStatus,
+    time::{self, TimeSource},
+    world::ZoneTime,
+};
+use esp_idf_svc::{http::server::ws::EspHttpWsDetachedSender, ws::FrameType};
+use serde::Serialize;
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+/// Stack size of the thread pushing status updates, serializing JSON needs some room.
+const BROADCASTER_STACK_SIZE: usize = 6144;
+
+/// Type alias for the shared [StatusEvents] hub.
+pub type Events = Arc<StatusEvents>;
+
+/// The health of a service started at boot.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum ServiceState {
+    /// The service is still being started.
+    #[default]
+    Starting,
+    /// The service works as configured.
+    Running,
+    /// The service runs with reduced features, for the given reason.
+    Degraded(String),
+    /// The service could not start, for the given reason; the others keep running.
+    Failed(String),
+}
+
+impl ServiceState {
+    /// Whether the service is degraded or failed.
+    pub fn is_impaired(&self) -> bool {
+        matches!(self, ServiceState::Degraded(_) | ServiceState::Failed(_))
+    }
+}
+
+/// The state of each group of services, started independently of each other so a
+/// failing one does not stop the clock.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Services {
+    /// The settings stored in NVS.
+    pub settings: ServiceState,
+    /// The thread pushing the status to the web interface.
+    pub events: ServiceState,
+    /// The 7-segment display.
+    pub display: ServiceState,
+    /// The I2C bus of the RTC and the climate sensor.
+    pub i2c: ServiceState,
+    /// The GPS receiver.
+    pub gps: ServiceState,
+    /// The piezo buzzer.
+    pub buzzer: ServiceState,
+    /// The climate sensor.
+    pub sensor: ServiceState,
+    /// The LED strip of the lamp and the sunrise.
+    pub strip: ServiceState,
+    /// The serial shell.
+    pub shell: ServiceState,
+    /// The buttons and the rotary encoder.
+    pub input: ServiceState,
+    /// The Wi-Fi, mDNS, the SNTP server, the clock group and MQTT.
+    pub network: ServiceState,
+    /// The synchronization of the time with SNTP or GPS.
+    pub time: ServiceState,
+    /// The HTTP server of the web interface and the API.
+    pub server: ServiceState,
+}
+
+impl Services {
+    /// Lists the degraded or failed services with their state.
+    pub fn impaired(&self) -> Vec<(&'static str, &ServiceState)> {
+        [
+            ("settings", &self.settings),
+            ("events", &self.events),
+            ("display", &self.display),
+            ("i2c", &self.i2c),
+            ("gps", &self.gps),
+            ("buzzer", &self.buzzer),
+            ("sensor", &self.sensor),
+            ("strip", &self.strip),
+            ("shell", &self.shell),
+            ("input", &self.input),
+            ("network", &self.network),
+            ("time", &self.time),
+            ("server", &self.server),
+        ]
+        .into_iter()
+        .filter(|(_, state)| state.is_impaired())
+        .collect()
+    }
+}
+
+/// Snapshot of the clock state pushed to the web interface.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Status {
+    pub ssid: String,
+    pub timezone: String,
+    pub time: String,
+    /// The time in the secondary zones of the world clock.
+    pub world: Vec<ZoneTime>,
+    pub display: DisplayState,
+    pub mode: DisplayMode,
+    pub synced: bool,
+    /// The source that last set the time, if any.
+    pub time_source: Option<TimeSource>,
+    /// The alarm or timer ringing, if any.
+    pub ringing: Option<Ringing>,
+    /// Whether the lamp is simulating a sunrise before an alarm.
+    pub sunrise: bool,
+    /// The latest sample of the climate sensor, if one is fitted.
+    pub climate: Option<Reading>,
+    /// The state of the RTC, if one is fitted.
+    pub rtc: Option<RtcStatus>,
+    /// The state of the services started at boot.
+    pub services: Services,
+}
+
+impl Status {
+    /// Serializes the status into the JSON text sent to the clients.
+    pub fn to_json(&self) -> Result<String, AppError> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+/// Formats the digits returned by [time::get_time] as `HH:MM`.
+pub fn format_time(time: &[u8]) -> String {
+    format!("{}{}:{}{}", time[0], time[1], time[2], time[3])
+}
+
+/// State shared between the publishers and the broadcaster thread.
+struct Inner {
+    status: Status,
+    subscribers: Vec<EspHttpWsDetachedSender>,
+    changed: bool,
+}
+
+/// Keeps the latest [Status] and pushes every change to the connected WebSocket clients.
+///
+/// Updates are only recorded by the publishers; the frames are sent by a dedicated
+/// thread, because a detached sender waits for the HTTP server task, which would
+/// deadlock when publishing from inside a request handler.
+pub struct StatusEvents {
+    inner: Mutex<Inner>,
+    condvar: Condvar,
+}
+
+impl StatusEvents {
+    /// Returns a copy of the latest status.
+    pub fn status(&self) -> Status {
+        self.inner.lock().unwrap().status.clone()
+    }
+
+    /// Modifies the status and wakes the broadcaster if anything changed.
+    ///
+    /// # Parameters
+    /// - `update`: A closure applying the changes to the current [Status].
+    ///
+    /// # Example
+    /// ```rust
+    /// events.update(|status| status.synced = true);
+    /// ```
+    pub fn update(&self, update: impl FnOnce(&mut Status)) {
+        let mut inner = self.inner.lock().unwrap();
+        let previous = inner.status.clone();
+
+        update(&mut inner.status);
+
+        if inner.status != previous {
+            inner.changed = true;
+            self.condvar.notify_one();
+        }
+    }
+
+    /// Registers a WebSocket client to receive the following status changes.
+    pub fn subscribe(&self, sender: EspHttpWsDetachedSender) {
+        self.inner.lock().unwrap().subscribers.push(sender);
+    }
+
+    /// Waits for status changes and sends them to every subscriber, dropping the closed ones.
+    fn broadcast_forever(&self) {
+        let _heartbeat = health::track();
+        loop {
+            let (json, mut subscribers) = {
+                let mut inner = self.inner.lock().unwrap();
+                while !inner.changed {
+                    inner = self.condvar.wait(inner).unwrap();
+                }
+                inner.changed = false;
+
+                (inner.status.to_json(), inner.subscribers.clone())
+            };
+            health::beat();
+
+            let json = match json {
+                Ok(json) => json,
+                Err(e) => {
+                    log::error!("Failed to serialize status: {:#?}", e);
+                    continue;
+                }
+            };
+
+            let closed: Vec<i32> = subscribers
+                .iter_mut()
+                .filter_map(|sender| {
+                    let failed = sender.is_closed()
+                        || sender
+                            .send(FrameType::Text(false), json.as_bytes())
+                            .is_err();
+                    failed.then(|| sender.session())
+                })
+                .collect();
+
+            if !closed.is_empty() {
+                log::info!("Dropping {} closed status subscriber(s)", closed.len());
+                self.inner
+                    .lock()
+                    .unwrap()
+                    .subscribers
+                    .retain(|sender| !closed.contains(&sender.session()));
+            }
+        }
+    }
+}
+
+/// Creates the [Events] hub, without the thread broadcasting its changes.
+///
+/// The status stays readable by the API, but is not pushed to the WebSocket clients;
+/// used when [get_events] cannot start its thread.
+pub fn local_events() -> Events {
+    Arc::new(StatusEvents {
+        inner: Mutex::new(Inner {
+            status: Status {
+                ssid: String::new(),
+                timezone: time::timezone().to_string(),
+                time: format_time(&time::get_time()),
+                world: Vec::new(),
+                display: DisplayState::default(),
+                mode: DisplayMode::Clock,
+                synced: false,
+                time_source: None,
+                ringing: None,
+                sunrise: false,
+                climate: None,
+                rtc: None,
+                services: Services::default(),
+            },
+            subscribers: Vec::new(),
+            changed: false,
+        }),
+        condvar: Condvar::new(),
+    })
+}
+
+/// Creates the [Events] hub and starts the thread broadcasting its changes.
+///
+/// The hub is created before any other service, so they can publish their state from the start.
+///
+/// # Returns
+/// The shared [Events] hub, or an [AppError] if the broadcaster thread cannot be spawned.
+///
+/// # Example
+/// ```rust
+/// let events = get_events().unwrap_or_else(|_| local_events());
+/// ```
+pub fn get_events() -> Result<Events, AppError> {
+    let events = local_events();
+
+    let broadcaster = events.clone();
+    thread::Builder::new()
+        .name("status-events".to_string())
+        .stack_size(BROADCASTER_STACK_SIZE)
+        .spawn(move || broadcaster.broadcast_forever())
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    Ok(events)
+}
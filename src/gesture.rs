@@ -0,0 +1,450 @@
+use crate::{
+    command::Command,
+    menu::{Change, Key},
+};
+
+/// Time, in milliseconds, a level must stay stable before it is accepted.
+pub const DEBOUNCE_MS: u64 = 30;
+
+/// Time, in milliseconds, a button must be held to count as a long press.
+pub const LONG_PRESS_MS: u64 = 800;
+
+/// Maximum time, in milliseconds, between two presses of a double press.
+pub const DOUBLE_PRESS_MS: u64 = 300;
+
+/// Time, in milliseconds, the BOOT button must be held to trigger a factory reset.
+pub const FACTORY_RESET_HOLD_MS: u64 = 5000;
+
+/// Duration, in minutes, of the timer started from the buttons.
+pub const DEFAULT_TIMER_MINUTES: u32 = 5;
+
+/// Filters out the bounces of a mechanical contact.
+///
+/// The raw level is sampled periodically and only reported once it stayed
+/// the same for [DEBOUNCE_MS].
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    stable: bool,
+    candidate: bool,
+    candidate_since: u64,
+}
+
+impl Debouncer {
+    /// Creates a debouncer starting at the given level.
+    pub fn new(level: bool) -> Self {
+        Debouncer {
+            stable: level,
+            candidate: level,
+            candidate_since: 0,
+        }
+    }
+
+    /// Returns the debounced level.
+    pub fn level(&self) -> bool {
+        self.stable
+    }
+
+    /// Feeds a raw sample taken at `now_ms`.
+    ///
+    /// # Returns
+    /// The new debounced level when it changes, `None` otherwise.
+    pub fn update(&mut self, raw: bool, now_ms: u64) -> Option<bool> {
+        if raw != self.candidate {
+            self.candidate = raw;
+            self.candidate_since = now_ms;
+        }
+
+        if self.candidate != self.stable
+            && now_ms.saturating_sub(self.candidate_since) >= DEBOUNCE_MS
+        {
+            self.stable = self.candidate;
+            return Some(self.stable);
+        }
+
+        None
+    }
+}
+
+/// A gesture recognized on a button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gesture {
+    /// Pressed and released once, reported after [DOUBLE_PRESS_MS] without a second press.
+    Short,
+    /// Pressed twice within [DOUBLE_PRESS_MS].
+    Double,
+    /// Held for [LONG_PRESS_MS], reported while still held.
+    Long,
+    /// Held for [FACTORY_RESET_HOLD_MS], reported while still held.
+    Hold,
+}
+
+/// Turns the debounced level of a button into [Gesture]s.
+///
+/// A long press reports [Gesture::Long] and, if the button is kept down,
+/// [Gesture::Hold]; releasing it afterwards reports nothing.
+#[derive(Debug, Clone, Default)]
+pub struct GestureRecognizer {
+    pressed_since: Option<u64>,
+    long_fired: bool,
+    hold_fired: bool,
+    released_at: Option<u64>,
+}
+
+impl GestureRecognizer {
+    /// Feeds the debounced state of the button at `now_ms`.
+    ///
+    /// # Returns
+    /// The gesture completed by this sample, if any.
+    pub fn update(&mut self, pressed: bool, now_ms: u64) -> Option<Gesture> {
+        match (pressed, self.pressed_since) {
+            (true, None) => {
+                self.pressed_since = Some(now_ms);
+                None
+            }
+            (true, Some(since)) => {
+                let held = now_ms.saturating_sub(since);
+                if !self.long_fired && held >= LONG_PRESS_MS {
+                    self.long_fired = true;
+                    self.released_at = None;
+                    Some(Gesture::Long)
+                } else if !self.hold_fired && held >= FACTORY_RESET_HOLD_MS {
+                    self.hold_fired = true;
+                    Some(Gesture::Hold)
+                } else {
+                    None
+                }
+            }
+            (false, Some(_)) => {
+                self.pressed_since = None;
+                if self.long_fired {
+                    self.long_fired = false;
+                    self.hold_fired = false;
+                    None
+                } else if self.released_at.take().is_some() {
+                    Some(Gesture::Double)
+                } else {
+                    self.released_at = Some(now_ms);
+                    None
+                }
+            }
+            (false, None) => match self.released_at {
+                Some(at) if now_ms.saturating_sub(at) >= DOUBLE_PRESS_MS => {
+                    self.released_at = None;
+                    Some(Gesture::Short)
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Decodes the two quadrature signals of a rotary encoder into detents.
+///
+/// Invalid transitions, caused by bounces or missed samples, are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct Quadrature {
+    state: u8,
+    steps: i8,
+}
+
+impl Quadrature {
+    /// Quarter steps per detent of the usual mechanical encoders.
+    const STEPS_PER_DETENT: i8 = 4;
+
+    /// Direction of each transition, indexed by `previous << 2 | current` state.
+    const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+    /// Creates a decoder starting at the given levels of the `A` and `B` signals.
+    pub fn new(a: bool, b: bool) -> Self {
+        Quadrature {
+            state: (u8::from(a) << 1) | u8::from(b),
+            steps: 0,
+        }
+    }
+
+    /// Feeds the current levels of the `A` and `B` signals.
+    ///
+    /// # Returns
+    /// `1` for a detent clockwise, `-1` counter-clockwise, or `None` in between.
+    pub fn update(&mut self, a: bool, b: bool) -> Option<i8> {
+        let state = (u8::from(a) << 1) | u8::from(b);
+        self.steps += Self::TRANSITIONS[usize::from((self.state << 2) | state)];
+        self.state = state;
+
+        if self.steps.abs() >= Self::STEPS_PER_DETENT {
+            let direction = self.steps.signum();
+            self.steps = 0;
+            return Some(direction);
+        }
+
+        None
+    }
+}
+
+/// The buttons the clock knows about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    /// The BOOT button found on most boards, used for the factory reset.
+    Boot,
+    /// Switches between what the display shows.
+    Mode,
+    /// Snoozes and dismisses the alarms, the timer and the sunrise.
+    Action,
+}
+
+/// An event produced by the inputs, queued until its action runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    Button(Button, Gesture),
+    /// Detents turned on the rotary encoder, positive clockwise.
+    Rotate(i8),
+}
+
+/// Maps an input event to the [Command] it triggers while the menu is closed.
+///
+/// | Input          | Gesture | Command                               |
+/// |----------------|---------|---------------------------------------|
+/// | Mode button    | short   | cycle the display mode                |
+/// | Mode button    | long    | open the settings menu ([menu_key])   |
+/// | Mode button    | double  | start a [DEFAULT_TIMER_MINUTES] timer |
+/// | Action button  | short   | snooze the alarm                      |
+/// | Action button  | long    | dismiss the alarm, timer and sunrise  |
+/// | BOOT button    | hold    | factory reset                         |
+/// | Rotary encoder | turn    | adjust the brightness                 |
+///
+/// # Parameters
+/// - `event`: The [InputEvent] to map.
+/// - `brightness`: The current brightness, the encoder changes it relatively.
+///
+/// # Returns
+/// The [Command] to run, or `None` if the event is not mapped.
+pub fn action(event: InputEvent, brightness: u8) -> Option<Command> {
+    match event {
+        InputEvent::Button(Button::Mode, Gesture::Short) => Some(Command::CycleMode),
+        InputEvent::Button(Button::Mode, Gesture::Double) => {
+            Some(Command::StartTimer(DEFAULT_TIMER_MINUTES))
+        }
+        InputEvent::Button(Button::Action, Gesture::Short) => Some(Command::Snooze),
+        InputEvent::Button(Button::Action, Gesture::Long) => Some(Command::Dismiss),
+        InputEvent::Button(Button::Boot, Gesture::Hold) => Some(Command::FactoryReset),
+        InputEvent::Rotate(detents) => {
+            let brightness = (i16::from(brightness) + i16::from(detents)).clamp(1, 7) as u8;
+            Some(Command::SetBrightness(brightness))
+        }
+        InputEvent::Button(..) => None,
+    }
+}
+
+/// Maps an input event to the menu [Key] it presses.
+///
+/// The long press of the Mode button opens and closes the menu; inside it, the Mode
+/// button selects, and the Action button or the encoder move up and down.
+pub fn menu_key(event: InputEvent) -> Option<Key> {
+    match event {
+        InputEvent::Button(Button::Mode, Gesture::Long) => Some(Key::Menu),
+        InputEvent::Button(Button::Mode, Gesture::Short) => Some(Key::Select),
+        InputEvent::Button(Button::Action, Gesture::Short) => Some(Key::Up),
+        InputEvent::Button(Button::Action, Gesture::Long) => Some(Key::Down),
+        InputEvent::Rotate(detents) if detents > 0 => Some(Key::Up),
+        InputEvent::Rotate(detents) if detents < 0 => Some(Key::Down),
+        _ => None,
+    }
+}
+
+/// Maps a setting confirmed in the menu to the [Command] applying it.
+pub fn change_command(change: Change) -> Command {
+    match change {
+        Change::Brightness(brightness) => Command::SetBrightness(brightness),
+        Change::TwelveHour(twelve_hour) => Command::SetTwelveHour(twelve_hour),
+        Change::Timezone(timezone) => Command::SetTimezone(timezone),
+        Change::Time(time) => Command::SetTime {
+            date: None,
+            time: Some(time),
+        },
+        Change::Date(date) => Command::SetTime {
+            date: Some(date),
+            time: None,
+        },
+        Change::Alarm { index, time } => Command::SetAlarm { index, time },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sampling period of the synthetic timelines, as in the poll loop of the inputs.
+    const STEP_MS: u64 = 10;
+
+    /// Samples a button pressed during the `presses` intervals, bouncing for 20 ms at
+    /// every edge, through a [Debouncer] and a [GestureRecognizer] until `end_ms`.
+    ///
+    /// # Returns
+    /// Each recognized gesture with the time it was reported at.
+    fn gestures(presses: &[(u64, u64)], end_ms: u64) -> Vec<(Gesture, u64)> {
+        let mut debouncer = Debouncer::new(false);
+        let mut recognizer = GestureRecognizer::default();
+        let mut recognized = Vec::new();
+
+        for now_ms in (0..=end_ms).step_by(STEP_MS as usize) {
+            let pressed = presses
+                .iter()
+                .any(|&(down, up)| (down..up).contains(&now_ms));
+            let bouncing = presses.iter().any(|&(down, up)| {
+                (down..down + 20).contains(&now_ms) || (up..up + 20).contains(&now_ms)
+            });
+            let raw = if bouncing {
+                (now_ms / STEP_MS) % 2 == 1
+            } else {
+                pressed
+            };
+
+            debouncer.update(raw, now_ms);
+            if let Some(gesture) = recognizer.update(debouncer.level(), now_ms) {
+                recognized.push((gesture, now_ms));
+            }
+        }
+
+        recognized
+    }
+
+    #[test]
+    fn debouncer_ignores_short_glitches() {
+        let mut debouncer = Debouncer::new(false);
+        assert_eq!(debouncer.update(true, 0), None);
+        assert_eq!(debouncer.update(false, 10), None);
+        assert_eq!(debouncer.update(true, 20), None);
+        assert_eq!(debouncer.update(false, 30), None);
+        assert_eq!(debouncer.update(false, 100), None);
+        assert!(!debouncer.level());
+    }
+
+    #[test]
+    fn debouncer_accepts_stable_levels() {
+        let mut debouncer = Debouncer::new(false);
+        assert_eq!(debouncer.update(true, 100), None);
+        assert_eq!(debouncer.update(true, 100 + DEBOUNCE_MS - 1), None);
+        assert_eq!(debouncer.update(true, 100 + DEBOUNCE_MS), Some(true));
+        assert_eq!(debouncer.update(true, 200), None);
+        assert!(debouncer.level());
+
+        assert_eq!(debouncer.update(false, 300), None);
+        assert_eq!(debouncer.update(false, 300 + DEBOUNCE_MS), Some(false));
+    }
+
+    #[test]
+    fn recognizes_short_press() {
+        let recognized = gestures(&[(100, 250)], 1000);
+        assert_eq!(recognized.len(), 1);
+        let (gesture, at) = recognized[0];
+        assert_eq!(gesture, Gesture::Short);
+        // Only once no second press came
+        assert!(at >= 250 + DOUBLE_PRESS_MS);
+    }
+
+    #[test]
+    fn recognizes_double_press() {
+        let recognized = gestures(&[(100, 200), (350, 450)], 1500);
+        assert_eq!(
+            recognized.iter().map(|r| r.0).collect::<Vec<_>>(),
+            [Gesture::Double]
+        );
+    }
+
+    #[test]
+    fn slow_presses_are_two_short_ones() {
+        let recognized = gestures(&[(100, 200), (700, 800)], 2000);
+        assert_eq!(
+            recognized.iter().map(|r| r.0).collect::<Vec<_>>(),
+            [Gesture::Short, Gesture::Short]
+        );
+    }
+
+    #[test]
+    fn recognizes_long_press_while_held() {
+        let recognized = gestures(&[(100, 1500)], 3000);
+        assert_eq!(recognized.len(), 1);
+        let (gesture, at) = recognized[0];
+        assert_eq!(gesture, Gesture::Long);
+        assert!((100 + LONG_PRESS_MS..1500).contains(&at));
+    }
+
+    #[test]
+    fn recognizes_hold_after_long() {
+        let recognized = gestures(&[(100, 6000)], 7000);
+        assert_eq!(
+            recognized.iter().map(|r| r.0).collect::<Vec<_>>(),
+            [Gesture::Long, Gesture::Hold]
+        );
+        assert!(recognized[1].1 >= 100 + FACTORY_RESET_HOLD_MS);
+    }
+
+    #[test]
+    fn release_after_long_press_reports_nothing() {
+        let mut recognizer = GestureRecognizer::default();
+        assert_eq!(recognizer.update(true, 0), None);
+        assert_eq!(recognizer.update(true, LONG_PRESS_MS), Some(Gesture::Long));
+        assert_eq!(recognizer.update(false, LONG_PRESS_MS + 10), None);
+        assert_eq!(recognizer.update(false, LONG_PRESS_MS + 10_000), None);
+
+        // The next press starts afresh
+        assert_eq!(recognizer.update(true, 20_000), None);
+        assert_eq!(recognizer.update(false, 20_100), None);
+        assert_eq!(
+            recognizer.update(false, 20_100 + DOUBLE_PRESS_MS),
+            Some(Gesture::Short)
+        );
+    }
+
+    /// Levels of `A` and `B` through one detent clockwise, from the rest state.
+    const CLOCKWISE: [(bool, bool); 4] =
+        [(true, false), (true, true), (false, true), (false, false)];
+
+    #[test]
+    fn quadrature_counts_detents() {
+        let mut decoder = Quadrature::new(false, false);
+        let steps: Vec<_> = CLOCKWISE
+            .iter()
+            .map(|&(a, b)| decoder.update(a, b))
+            .collect();
+        assert_eq!(steps, [None, None, None, Some(1)]);
+
+        let steps: Vec<_> = CLOCKWISE
+            .iter()
+            .rev()
+            .skip(1)
+            .chain([(false, false)].iter())
+            .map(|&(a, b)| decoder.update(a, b))
+            .collect();
+        assert_eq!(steps, [None, None, None, Some(-1)]);
+    }
+
+    #[test]
+    fn quadrature_ignores_invalid_transitions() {
+        let mut decoder = Quadrature::new(false, false);
+        // Both signals changing at once is a missed sample, not a step
+        for _ in 0..8 {
+            assert_eq!(decoder.update(true, true), None);
+            assert_eq!(decoder.update(false, false), None);
+        }
+        // Repeated samples of the same state do not move either
+        for _ in 0..8 {
+            assert_eq!(decoder.update(false, false), None);
+        }
+    }
+
+    #[test]
+    fn quadrature_ignores_bounces() {
+        let mut decoder = Quadrature::new(false, false);
+        // A bounce on one contact steps back and forth without completing a detent
+        for _ in 0..8 {
+            assert_eq!(decoder.update(true, false), None);
+            assert_eq!(decoder.update(false, false), None);
+        }
+        let steps: Vec<_> = CLOCKWISE
+            .iter()
+            .map(|&(a, b)| decoder.update(a, b))
+            .collect();
+        assert_eq!(steps, [None, None, None, Some(1)]);
+    }
+}
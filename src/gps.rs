@@ -0,0 +1,191 @@
+use crate::{
+    error::{self, AppError},
+    events::Events,
+    health,
+    nmea::{self, NmeaError, SentenceBuffer},
+    time::{self, TimeSource},
+};
+use chrono::{TimeDelta, Timelike};
+use esp_idf_svc::{
+    hal::{
+        delay::BLOCK,
+        gpio::{AnyIOPin, AnyInputPin, Input, InputPin, InterruptType, OutputPin, PinDriver},
+        peripheral::Peripheral,
+        prelude::*,
+        uart::{self, Uart, UartDriver},
+    },
+    sys::esp_timer_get_time,
+};
+use std::{
+    sync::atomic::{AtomicI64, Ordering},
+    thread,
+};
+
+/// Baud rate of the NMEA output of most GPS receivers.
+const GPS_BAUDRATE: u32 = 9600;
+
+/// Stack size of the thread reading the receiver.
+const GPS_STACK_SIZE: usize = 4096;
+
+/// Difference, in microseconds, from one second allowed between two PPS pulses, so a
+/// floating pin is not mistaken for a PPS signal.
+const PPS_TOLERANCE_US: i64 = 10_000;
+
+/// Time since boot, in microseconds, of the last PPS rising edge.
+static PPS_EDGE_US: AtomicI64 = AtomicI64::new(0);
+
+/// Time, in microseconds, between the last two PPS rising edges.
+static PPS_PERIOD_US: AtomicI64 = AtomicI64::new(0);
+
+/// Returns the time since boot, in microseconds.
+fn now_us() -> i64 {
+    unsafe { esp_timer_get_time() }
+}
+
+/// Returns the time elapsed since the last PPS pulse, or `None` without a steady PPS signal.
+///
+/// The receiver raises PPS at the start of each second, then sends the sentences
+/// carrying that second.
+fn since_pps() -> Option<TimeDelta> {
+    let period = PPS_PERIOD_US.load(Ordering::Relaxed);
+    let elapsed = now_us() - PPS_EDGE_US.load(Ordering::Relaxed);
+
+    ((period - 1_000_000).abs() < PPS_TOLERANCE_US && (0..1_000_000).contains(&elapsed))
+        .then(|| TimeDelta::microseconds(elapsed))
+}
+
+/// Listens for the PPS rising edges, recording their time.
+fn get_pps(pin: AnyInputPin) -> Result<PinDriver<'static, AnyInputPin, Input>, AppError> {
+    let mut pps = PinDriver::input(pin)?;
+    pps.set_interrupt_type(InterruptType::PosEdge)?;
+    unsafe {
+        pps.subscribe(|| {
+            let now = now_us();
+            let previous = PPS_EDGE_US.swap(now, Ordering::Relaxed);
+            PPS_PERIOD_US.store(now - previous, Ordering::Relaxed);
+        })?;
+    }
+    pps.enable_interrupt()?;
+
+    Ok(pps)
+}
+
+/// Creates the [UartDriver] the GPS receiver is wired to, at 9600 baud 8N1.
+///
+/// # Parameters
+/// - `uart`: The UART peripheral of the receiver.
+/// - `tx`: The TX pin, wired to the RX pin of the receiver (implements [OutputPin]).
+/// - `rx`: The RX pin, wired to the TX pin of the receiver (implements [InputPin]).
+///
+/// # Returns
+/// The [UartDriver] ready to be passed to [start_gps], or an [AppError] if it fails.
+///
+/// # Example
+/// ```rust
+/// let uart = get_gps_uart(peripherals.uart2, pins.gpio17, pins.gpio16)?;
+/// ```
+pub fn get_gps_uart<UART: Uart>(
+    uart: impl Peripheral<P = UART> + 'static,
+    tx: impl Peripheral<P = impl OutputPin> + 'static,
+    rx: impl Peripheral<P = impl InputPin> + 'static,
+) -> Result<UartDriver<'static>, AppError> {
+    Ok(UartDriver::new(
+        uart,
+        tx,
+        rx,
+        Option::<AnyIOPin>::None,
+        Option::<AnyIOPin>::None,
+        &uart::config::Config::default().baudrate(GPS_BAUDRATE.Hz()),
+    )?)
+}
+
+/// Starts the thread reading the time from the optional GPS receiver.
+///
+/// Each `RMC` or `ZDA` sentence received while the receiver has a fix is offered with
+/// [time::offer_time], where GPS takes priority over SNTP and the RTC. With a steady PPS
+/// signal, the time elapsed since the pulse is added, otherwise the sentence is used as
+/// received, late by its transmission time.
+///
+/// # Parameters
+/// - `uart`: The [UartDriver] of the receiver, see [get_gps_uart].
+/// - `pps`: The pin the PPS output may be wired to.
+/// - `events`: The [Events] hub publishing the time source.
+///
+/// # Returns
+/// `Ok(())` once the thread is running, or an [AppError] if it cannot be spawned.
+///
+/// # Example
+/// ```rust
+/// start_gps(uart, peripherals.pins.gpio34.downgrade_input(), events.clone())?;
+/// ```
+pub fn start_gps(
+    uart: UartDriver<'static>,
+    pps: AnyInputPin,
+    events: Events,
+) -> Result<(), AppError> {
+    thread::Builder::new()
+        .name("gps".to_string())
+        .stack_size(GPS_STACK_SIZE)
+        .spawn(move || {
+            let mut pps = get_pps(pps)
+                .inspect_err(|e| log::error!("Failed to listen for PPS: {:#?}", e))
+                .ok();
+            let mut sentences = SentenceBuffer::default();
+            let mut last_second = None;
+            let mut byte = [0];
+            let _heartbeat = health::track();
+
+            loop {
+                match uart.read(&mut byte, BLOCK) {
+                    Ok(1) => {}
+                    Ok(_) => continue,
+                    Err(e) => {
+                        log::error!("Failed to read GPS: {:#?}", e);
+                        continue;
+                    }
+                }
+                let Some(line) = sentences.push(byte[0]) else {
+                    continue;
+                };
+                health::beat();
+
+                let fix = match nmea::parse(&line) {
+                    Ok(fix) => fix,
+                    Err(NmeaError::Unsupported) => continue,
+                    Err(e) => {
+                        log::debug!("Ignored GPS sentence: {e}");
+                        continue;
+                    }
+                };
+
+                // The interrupt is disabled after each pulse until enabled again
+                if let Some(pps) = pps.as_mut() {
+                    if let Err(e) = pps.enable_interrupt() {
+                        log::error!("Failed to listen for PPS: {:#?}", e);
+                    }
+                }
+
+                // Receivers send several sentences for the same second, only the first is used
+                let utc = fix.utc.and_utc();
+                if last_second == Some(utc.timestamp()) {
+                    continue;
+                }
+                last_second = Some(utc.timestamp());
+
+                let time = match since_pps() {
+                    Some(elapsed) => utc.with_nanosecond(0).unwrap_or(utc) + elapsed,
+                    None => utc,
+                };
+                match time::offer_time(TimeSource::Gps, time) {
+                    Ok(_) => events.update(|s| s.time_source = time::time_source()),
+                    Err(e) => {
+                        log::error!("Failed to apply GPS time: {:#?}", e);
+                        error::record(&e);
+                    }
+                }
+            }
+        })
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    Ok(())
+}
@@ -0,0 +1,390 @@
+use hmac_sha256::HMAC;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{fmt, net::Ipv4Addr};
+
+/// Version of the group protocol, bumped on any incompatible change of the frames or [Message].
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Multicast group the leader sends its messages to.
+pub const MULTICAST_ADDRESS: Ipv4Addr = Ipv4Addr::new(239, 255, 73, 67);
+
+/// UDP port of the group messages.
+pub const GROUP_PORT: u16 = 7367;
+
+/// Largest frame sent or accepted, so it fits a single Wi-Fi packet.
+pub const MAX_FRAME_SIZE: usize = 1400;
+
+/// The settings a leader shares with its followers; the network options, the secrets
+/// and the group options stay local to each clock.
+pub const SHARED_SETTINGS: [&str; 7] = [
+    "timezone",
+    "twelve_hour",
+    "alarms",
+    "buzzer_volume",
+    "lamp",
+    "sunrise_minutes",
+    "temperature_unit",
+];
+
+/// Bytes opening every frame.
+const MAGIC: [u8; 4] = *b"ICGP";
+
+/// Size of the magic, the version and the sequence number.
+const HEADER_SIZE: usize = 13;
+
+/// Size of the HMAC-SHA256 closing every frame.
+const MAC_SIZE: usize = 32;
+
+/// The part a clock plays in its group.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupRole {
+    /// The clock neither sends nor applies group messages.
+    #[default]
+    Off,
+    /// The clock sends its display state and its shared settings.
+    Leader,
+    /// The clock applies the messages of the leader.
+    Follower,
+}
+
+/// The messages sent by the leader, serialized as JSON inside a frame.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// What the display of the leader shows.
+    Display {
+        /// The display mode, by its name in the status, e.g. `clock`.
+        mode: String,
+        /// The brightness level (0 to 7).
+        brightness: u8,
+        /// The digits shown in the `manual` mode.
+        frame: Option<[u8; 4]>,
+    },
+    /// The [SHARED_SETTINGS] of the leader.
+    Settings { settings: Map<String, Value> },
+}
+
+/// Errors returned by [decode] for frames that must be ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupError {
+    /// The frame is too short, too long or does not start with the magic bytes.
+    Framing,
+    /// The frame uses another version of the protocol.
+    Version(u8),
+    /// The frame was not signed with the shared key.
+    Authentication,
+    /// The signed message cannot be parsed.
+    Payload(String),
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupError::Framing => write!(f, "not a group frame"),
+            GroupError::Version(version) => {
+                write!(f, "protocol version {version}, expected {PROTOCOL_VERSION}")
+            }
+            GroupError::Authentication => write!(f, "invalid signature"),
+            GroupError::Payload(e) => write!(f, "invalid message: {e}"),
+        }
+    }
+}
+
+/// Keeps only the [SHARED_SETTINGS] of the serialized settings.
+///
+/// # Parameters
+/// - `settings`: The settings, serialized as a JSON object.
+pub fn shared_settings(settings: &Value) -> Map<String, Value> {
+    settings
+        .as_object()
+        .map(|settings| {
+            settings
+                .iter()
+                .filter(|(name, _)| SHARED_SETTINGS.contains(&name.as_str()))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Compares two signatures in constant time, so their bytes cannot be guessed from timing.
+fn same_mac(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Serializes a message into a signed frame.
+///
+/// The frame holds the magic bytes `ICGP`, the [PROTOCOL_VERSION], the sequence number
+/// (big-endian), the message as JSON, then the HMAC-SHA256 of all the previous bytes.
+///
+/// # Parameters
+/// - `key`: The key shared by the clocks of the group.
+/// - `sequence`: A number greater than the one of the previous frame, see [Sequence].
+/// - `message`: The [Message] to send.
+///
+/// # Returns
+/// The frame, or a [GroupError::Payload] if it would exceed [MAX_FRAME_SIZE].
+///
+/// # Example
+/// ```rust
+/// let frame = encode(key.as_bytes(), sequence, &Message::Settings { settings })?;
+/// socket.send_to(&frame, (MULTICAST_ADDRESS, GROUP_PORT))?;
+/// ```
+pub fn encode(key: &[u8], sequence: u64, message: &Message) -> Result<Vec<u8>, GroupError> {
+    let payload = serde_json::to_vec(message).map_err(|e| GroupError::Payload(e.to_string()))?;
+    if HEADER_SIZE + payload.len() + MAC_SIZE > MAX_FRAME_SIZE {
+        return Err(GroupError::Payload("message too long".to_string()));
+    }
+
+    let mut frame = Vec::with_capacity(HEADER_SIZE + payload.len() + MAC_SIZE);
+    frame.extend_from_slice(&MAGIC);
+    frame.push(PROTOCOL_VERSION);
+    frame.extend_from_slice(&sequence.to_be_bytes());
+    frame.extend_from_slice(&payload);
+    let mac = HMAC::mac(&frame, key);
+    frame.extend_from_slice(&mac);
+
+    Ok(frame)
+}
+
+/// Checks and parses a frame built by [encode].
+///
+/// The function never panics, whatever the bytes received.
+///
+/// # Parameters
+/// - `key`: The key shared by the clocks of the group.
+/// - `frame`: The datagram received.
+///
+/// # Returns
+/// The sequence number and the [Message], or the [GroupError] the frame is ignored for.
+pub fn decode(key: &[u8], frame: &[u8]) -> Result<(u64, Message), GroupError> {
+    if frame.len() < HEADER_SIZE + MAC_SIZE || frame.len() > MAX_FRAME_SIZE {
+        return Err(GroupError::Framing);
+    }
+    if frame[..4] != MAGIC {
+        return Err(GroupError::Framing);
+    }
+    if frame[4] != PROTOCOL_VERSION {
+        return Err(GroupError::Version(frame[4]));
+    }
+
+    let (signed, mac) = frame.split_at(frame.len() - MAC_SIZE);
+    if !same_mac(&HMAC::mac(signed, key), mac) {
+        return Err(GroupError::Authentication);
+    }
+
+    let mut sequence = [0; 8];
+    sequence.copy_from_slice(&signed[5..HEADER_SIZE]);
+    let message = serde_json::from_slice(&signed[HEADER_SIZE..])
+        .map_err(|e| GroupError::Payload(e.to_string()))?;
+
+    Ok((u64::from_be_bytes(sequence), message))
+}
+
+/// Numbers the frames of a leader, growing across its restarts whatever its time.
+///
+/// The upper 32 bits hold the epoch, kept in NVS and bumped at every start of the
+/// leader, the lower 32 bits count the frames sent since.
+#[derive(Debug)]
+pub struct Sequence {
+    epoch: u32,
+    count: u32,
+}
+
+impl Sequence {
+    /// Starts numbering the frames of an epoch.
+    ///
+    /// # Parameters
+    /// - `epoch`: A number greater than the one of the previous start of the leader.
+    pub fn new(epoch: u32) -> Self {
+        Sequence { epoch, count: 0 }
+    }
+
+    /// Returns the number of the next frame.
+    ///
+    /// # Returns
+    /// The sequence number, or `None` once the epoch is exhausted and a new one is needed.
+    pub fn advance(&mut self) -> Option<u64> {
+        self.count = self.count.checked_add(1)?;
+
+        Some(u64::from(self.epoch) << 32 | u64::from(self.count))
+    }
+}
+
+/// Rejects frames replayed by a third party, which are signed but not newer.
+#[derive(Debug, Default)]
+pub struct ReplayGuard {
+    last: Option<u64>,
+}
+
+impl ReplayGuard {
+    /// Accepts a sequence number if it is greater than every one accepted before.
+    ///
+    /// # Returns
+    /// `true` if the frame is new and must be applied, `false` if it is a replay.
+    pub fn accept(&mut self, sequence: u64) -> bool {
+        if self.last.is_some_and(|last| sequence <= last) {
+            return false;
+        }
+        self.last = Some(sequence);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const KEY: &[u8] = b"correct horse battery staple";
+
+    fn display() -> Message {
+        Message::Display {
+            mode: "clock".to_string(),
+            brightness: 3,
+            frame: None,
+        }
+    }
+
+    #[test]
+    fn round_trips_messages() {
+        let settings = shared_settings(&json!({"timezone": "Europe/Paris", "lamp": {"on": true}}));
+        for message in [display(), Message::Settings { settings }] {
+            let frame = encode(KEY, 42, &message).unwrap();
+            assert_eq!(decode(KEY, &frame), Ok((42, message)));
+        }
+    }
+
+    #[test]
+    fn lays_out_the_frame() {
+        let frame = encode(KEY, 0x0102_0304_0506_0708, &display()).unwrap();
+        assert_eq!(&frame[..4], b"ICGP");
+        assert_eq!(frame[4], PROTOCOL_VERSION);
+        assert_eq!(&frame[5..13], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(frame[13], b'{');
+    }
+
+    #[test]
+    fn keeps_only_shared_settings() {
+        let settings = shared_settings(&json!({
+            "timezone": "Europe/Paris",
+            "wifi_password": "secret",
+            "group_key": "secret",
+            "alarms": [],
+        }));
+        let mut names: Vec<_> = settings.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["alarms", "timezone"]);
+        assert!(shared_settings(&json!("not an object")).is_empty());
+    }
+
+    #[test]
+    fn rejects_tampered_frames() {
+        let frame = encode(KEY, 1, &display()).unwrap();
+        for index in [5, HEADER_SIZE, frame.len() - MAC_SIZE - 1, frame.len() - 1] {
+            let mut tampered = frame.clone();
+            tampered[index] ^= 0x01;
+            assert_eq!(
+                decode(KEY, &tampered),
+                Err(GroupError::Authentication),
+                "byte {index}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_another_key() {
+        let frame = encode(KEY, 1, &display()).unwrap();
+        assert_eq!(
+            decode(b"wrong key", &frame),
+            Err(GroupError::Authentication)
+        );
+        assert_eq!(decode(b"", &frame), Err(GroupError::Authentication));
+    }
+
+    #[test]
+    fn rejects_another_magic() {
+        let mut frame = encode(KEY, 1, &display()).unwrap();
+        frame[0] = b'X';
+        assert_eq!(decode(KEY, &frame), Err(GroupError::Framing));
+    }
+
+    #[test]
+    fn rejects_another_version() {
+        let mut frame = encode(KEY, 1, &display()).unwrap();
+        frame[4] = PROTOCOL_VERSION + 1;
+        assert_eq!(
+            decode(KEY, &frame),
+            Err(GroupError::Version(PROTOCOL_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn rejects_short_and_oversize_frames() {
+        assert_eq!(decode(KEY, &[]), Err(GroupError::Framing));
+        assert_eq!(
+            decode(KEY, &[0; HEADER_SIZE + MAC_SIZE - 1]),
+            Err(GroupError::Framing)
+        );
+
+        let mut frame = encode(KEY, 1, &display()).unwrap();
+        frame.resize(MAX_FRAME_SIZE + 1, b' ');
+        assert_eq!(decode(KEY, &frame), Err(GroupError::Framing));
+    }
+
+    #[test]
+    fn refuses_to_encode_oversize_messages() {
+        let mut settings = Map::new();
+        settings.insert("timezone".to_string(), json!("x".repeat(MAX_FRAME_SIZE)));
+        assert!(matches!(
+            encode(KEY, 1, &Message::Settings { settings }),
+            Err(GroupError::Payload(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_signed_garbage() {
+        let mut frame = b"ICGP\x01\0\0\0\0\0\0\0\x01not json".to_vec();
+        let mac = HMAC::mac(&frame, KEY);
+        frame.extend_from_slice(&mac);
+        assert!(matches!(decode(KEY, &frame), Err(GroupError::Payload(_))));
+    }
+
+    #[test]
+    fn numbers_frames_across_epochs() {
+        let mut sequence = Sequence::new(1);
+        let first = sequence.advance().unwrap();
+        let second = sequence.advance().unwrap();
+        assert_eq!(first, 1 << 32 | 1);
+        assert!(second > first);
+
+        // A restart of the leader, whatever its clock says, keeps the numbers growing
+        let mut restarted = Sequence::new(2);
+        let mut guard = ReplayGuard::default();
+        assert!(guard.accept(second));
+        assert!(guard.accept(restarted.advance().unwrap()));
+
+        let mut exhausted = Sequence {
+            epoch: 1,
+            count: u32::MAX - 1,
+        };
+        assert_eq!(exhausted.advance(), Some(1 << 32 | u64::from(u32::MAX)));
+        assert_eq!(exhausted.advance(), None);
+    }
+
+    #[test]
+    fn rejects_replays() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.accept(0));
+        assert!(!guard.accept(0));
+        assert!(guard.accept(5));
+        assert!(!guard.accept(5));
+        assert!(!guard.accept(3));
+        assert!(guard.accept(6));
+        assert!(guard.accept(u64::MAX));
+        assert!(!guard.accept(u64::MAX));
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    command::Device,
+    device::Clock,
+    display::{self, DisplayMode},
+    error::{self, AppError},
+    group::{self, GroupRole, Message, ReplayGuard, Sequence},
+    health, time,
+};
+use esp_idf_svc::{
+    hal::{
+        delay::FreeRtos,
+        gpio::{IOPin, OutputPin},
+    },
+    nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
+    sys,
+};
+use serde_json::Value;
+use std::{
+    net::{Ipv4Addr, UdpSocket},
+    thread,
+};
+
+/// Stack size of the group thread, the settings are serialized as JSON.
+const GROUP_STACK_SIZE: usize = 8192;
+
+/// Time, in milliseconds, between two checks of the leader for changes to send.
+const LEADER_POLL_MS: u32 = 200;
+
+/// Time, in milliseconds, after which the leader repeats an unchanged message, so
+/// followers joining later catch up.
+const REPEAT_MS: u64 = 10_000;
+
+/// NVS namespace holding the epoch of the leader, apart from the settings.
+const NVS_NAMESPACE: &str = "group";
+
+/// NVS key of the epoch of the leader, see [Sequence].
+const NVS_EPOCH_KEY: &str = "epoch";
+
+/// Maps a socket error to an [AppError].
+fn network_error(e: std::io::Error) -> AppError {
+    AppError::Network(e.to_string())
+}
+
+/// Keeps the epoch of the leader in NVS, so its sequence numbers keep growing across
+/// restarts, even when its time is not set yet.
+struct Epochs {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl Epochs {
+    /// Opens the NVS namespace of the epoch.
+    fn new(nvs: EspDefaultNvsPartition) -> Result<Self, AppError> {
+        let nvs = EspNvs::new(nvs, NVS_NAMESPACE, true)
+            .map_err(|e| AppError::Storage(format!("cannot keep the group epoch: {e}")))?;
+
+        Ok(Epochs { nvs })
+    }
+
+    /// Bumps the stored epoch and returns the [Sequence] numbering the frames of the new one.
+    ///
+    /// The epoch is saved before any frame uses it, so a reset cannot reuse it.
+    fn start(&mut self) -> Result<Sequence, AppError> {
+        let storage =
+            |e: sys::EspError| AppError::Storage(format!("cannot keep the group epoch: {e}"));
+        let epoch = self
+            .nvs
+            .get_u32(NVS_EPOCH_KEY)
+            .map_err(storage)?
+            .unwrap_or(0)
+            .checked_add(1)
+            .ok_or_else(|| AppError::Storage("group epochs exhausted".to_string()))?;
+        self.nvs.set_u32(NVS_EPOCH_KEY, epoch).map_err(storage)?;
+        log::info!("Group leader epoch {epoch}");
+
+        Ok(Sequence::new(epoch))
+    }
+}
+
+/// The last message of a kind sent by the leader.
+#[derive(Default)]
+struct Sent {
+    message: Option<Message>,
+    at_ms: u64,
+}
+
+/// Builds the messages describing the leader: its display and its shared settings.
+fn leader_messages<CLK, DIO>(clock: &Clock<CLK, DIO>) -> Result<[Message; 2], AppError>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    let status = clock.events.status();
+    let display = Message::Display {
+        mode: status.mode.name().to_string(),
+        brightness: status.display.brightness,
+        frame: (status.mode == DisplayMode::Manual).then_some(status.display.frame),
+    };
+
+    let settings = serde_json::to_value(clock.settings.lock().unwrap().get())?;
+    let settings = Message::Settings {
+        settings: group::shared_settings(&settings),
+    };
+
+    Ok([display, settings])
+}
+
+/// Sends every change of the leader to the multicast group, repeating each message
+/// every [REPEAT_MS].
+fn lead<CLK, DIO>(clock: Clock<CLK, DIO>, key: String, mut epochs: Epochs) -> Result<(), AppError>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(network_error)?;
+    socket.set_multicast_ttl_v4(1).map_err(network_error)?;
+
+    let mut sent: [Sent; 2] = Default::default();
+    let mut sequence = epochs.start()?;
+    let _heartbeat = health::watch()
+        .inspect_err(|e| {
+            log::error!("Failed to watch the group task: {:#?}", e);
+            error::record(e);
+        })
+        .ok();
+
+    loop {
+        for (message, sent) in leader_messages(&clock)?.into_iter().zip(&mut sent) {
+            let now_ms = time::uptime_ms();
+            if sent.message.as_ref() == Some(&message) && now_ms - sent.at_ms < REPEAT_MS {
+                continue;
+            }
+
+            let number = match sequence.advance() {
+                Some(number) => number,
+                None => {
+                    sequence = epochs.start()?;
+                    sequence.advance().unwrap_or_default()
+                }
+            };
+            let frame = group::encode(key.as_bytes(), number, &message)
+                .map_err(|e| AppError::Network(e.to_string()))?;
+            if let Err(e) = socket.send_to(&frame, (group::MULTICAST_ADDRESS, group::GROUP_PORT)) {
+                log::warn!("Failed to send group message: {:#?}", e);
+            }
+
+            *sent = Sent {
+                message: Some(message),
+                at_ms: now_ms,
+            };
+        }
+
+        health::beat();
+        FreeRtos::delay_ms(LEADER_POLL_MS);
+    }
+}
+
+/// Applies a message of the leader.
+fn apply<CLK, DIO>(clock: &Clock<CLK, DIO>, message: Message) -> Result<(), AppError>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    match message {
+        Message::Display {
+            mode,
+            brightness,
+            frame,
+        } => {
+            let mode = DisplayMode::from_name(&mode)
+                .ok_or_else(|| AppError::Network(format!("unknown display mode `{mode}`")))?;
+
+            let current = clock.display.lock().unwrap().state();
+            if brightness <= 7 && brightness != current.brightness {
+                clock.set_brightness(brightness)?;
+            }
+            if let Some(frame) = frame.filter(|_| mode == DisplayMode::Manual) {
+                if frame != current.frame {
+                    display::write(&clock.display, frame)?;
+                }
+            }
+            clock.events.update(|status| status.mode = mode);
+        }
+        Message::Settings { settings } => {
+            // Only the shared settings are applied, whatever the leader sent
+            let shared = group::shared_settings(&Value::Object(settings));
+            let mut store = clock.settings.lock().unwrap();
+            let current = group::shared_settings(&serde_json::to_value(store.get())?);
+            if shared != current {
+                store.merge_json(&serde_json::to_vec(&shared)?)?;
+                log::info!("Settings updated by the group leader");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Receives the messages of the leader and applies the authentic and new ones.
+fn follow<CLK, DIO>(clock: Clock<CLK, DIO>, key: String) -> Result<(), AppError>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    let socket =
+        UdpSocket::bind((Ipv4Addr::UNSPECIFIED, group::GROUP_PORT)).map_err(network_error)?;
+    socket
+        .join_multicast_v4(&group::MULTICAST_ADDRESS, &Ipv4Addr::UNSPECIFIED)
+        .map_err(network_error)?;
+
+    let mut guard = ReplayGuard::default();
+    let mut frame = vec![0; group::MAX_FRAME_SIZE];
+    let _heartbeat = health::track();
+
+    loop {
+        let size = match socket.recv(&mut frame) {
+            Ok(size) => size,
+            Err(e) => {
+                log::warn!("Failed to receive group message: {:#?}", e);
+                continue;
+            }
+        };
+        health::beat();
+
+        let message = match group::decode(key.as_bytes(), &frame[..size]) {
+            Ok((sequence, message)) if guard.accept(sequence) => message,
+            Ok(_) => {
+                log::warn!("Replayed group message ignored");
+                continue;
+            }
+            Err(e) => {
+                log::warn!("Group message ignored: {e}");
+                continue;
+            }
+        };
+
+        if let Err(e) = apply(&clock, message) {
+            log::error!("Failed to apply group message: {:#?}", e);
+            error::record(&e);
+        }
+    }
+}
+
+/// Starts the thread keeping a group of clocks in step, according to the
+/// [GroupRole] in the settings.
+///
+/// A leader sends its display mode, brightness and manual digits, and its shared
+/// settings (see [group::SHARED_SETTINGS]) over UDP multicast, signed with the group
+/// key; followers apply them. Nothing is started when the role is [GroupRole::Off].
+///
+/// # Parameters
+/// - `clock`: The [Clock] sending or applying the messages.
+/// - `nvs`: The default NVS partition, keeping the epoch of a leader.
+///
+/// # Returns
+/// `Ok(())` once the thread is running, or an [AppError] if it cannot be spawned.
+///
+/// # Example
+/// ```rust
+/// start_group(clock.clone(), nvs.clone())?;
+/// ```
+pub fn start_group<CLK, DIO>(
+    clock: Clock<CLK, DIO>,
+    nvs: EspDefaultNvsPartition,
+) -> Result<(), AppError>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    let (role, key) = {
+        let settings = clock.settings.lock().unwrap();
+        let settings = settings.get();
+        (settings.group_role, settings.group_key.clone())
+    };
+    if role == GroupRole::Off {
+        return Ok(());
+    }
+    let epochs = match role {
+        GroupRole::Leader => Some(Epochs::new(nvs)?),
+        _ => None,
+    };
+
+    thread::Builder::new()
+        .name("group".to_string())
+        .stack_size(GROUP_STACK_SIZE)
+        .spawn(move || {
+            let result = match epochs {
+                Some(epochs) => lead(clock, key, epochs),
+                None => follow(clock, key),
+            };
+            if let Err(e) = result {
+                log::error!("Group {:?} stopped: {:#?}", role, e);
+                error::record(&e);
+            }
+        })
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    log::info!("Group started as {:?}", role);
+
+    Ok(())
+}
@@ -1,15 +1,158 @@
 use crate::{
-    display::{self, DisplayMessage, Tm1637},
-    error::AppError,
-    time::{self, Sntp},
-    utils, wifi,
+    buzzer::Buzzer,
+    command::{self, Command},
+    countdown::{self, Event},
+    crashlog,
+    device::Clock,
+    display::{self, DisplayMode, Tm1637},
+    error::{self, AppError},
+    events::Events,
+    health,
+    lamp::Lamp,
+    logs,
+    rtttl::{self, Tune},
+    settings,
+    solar::{self, Location},
+    time, utils, wifi,
 };
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
 use esp_idf_svc::{
     hal::gpio::{IOPin, OutputPin},
-    http::server::{EspHttpConnection, Request},
-    sntp::SyncStatus,
-    sys::sntp_restart,
+    http::server::{ws::EspHttpWsConnection, EspHttpConnection, Request},
+    ws::FrameType,
 };
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, thread};
+
+/// Maximum size, in bytes, accepted for a request body.
+const MAX_BODY_LEN: usize = 2048;
+
+/// Maximum size, in bytes, of a frame accepted from a WebSocket client.
+const MAX_WS_FRAME_LEN: usize = 128;
+
+/// Stack size of the thread running a factory reset requested over HTTP.
+const FACTORY_RESET_STACK_SIZE: usize = 4096;
+
+/// Reads the whole body of an HTTP request.
+///
+/// # Returns
+/// The raw body, or an [AppError::BadRequest] if it exceeds [MAX_BODY_LEN].
+fn read_body(request: &mut Request<&mut EspHttpConnection<'_>>) -> Result<Vec<u8>, AppError> {
+    let mut body = Vec::new();
+    let mut buf = [0u8; 256];
+
+    loop {
+        let len = request.read(&mut buf)?;
+        if len == 0 {
+            return Ok(body);
+        }
+        if body.len() + len > MAX_BODY_LEN {
+            return Err(AppError::BadRequest(format!(
+                "body exceeds {MAX_BODY_LEN} bytes"
+            )));
+        }
+        body.extend_from_slice(&buf[..len]);
+    }
+}
+
+/// Responds to the request with `value` serialized as JSON.
+fn write_json(
+    request: Request<&mut EspHttpConnection<'_>>,
+    value: &impl Serialize,
+) -> Result<(), AppError> {
+    let json = serde_json::to_vec(value)?;
+
+    request
+        .into_response(200, None, &[("Content-Type", "application/json")])?
+        .write(&json)?;
+
+    Ok(())
+}
+
+/// Responds to the request with a JSON error message and the given status code.
+fn write_error(
+    request: Request<&mut EspHttpConnection<'_>>,
+    status: u16,
+    error: &AppError,
+) -> Result<(), AppError> {
+    let json = serde_json::json!({ "error": error.to_string() }).to_string();
+
+    request
+        .into_response(status, None, &[("Content-Type", "application/json")])?
+        .write(json.as_bytes())?;
+
+    Ok(())
+}
+
+/// Who may use an endpoint while no API token is configured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Access {
+    /// Anyone on the configured network, but nobody over the open provisioning access
+    /// point, which anyone in range can join.
+    Network,
+    /// Anyone, even over the provisioning access point while no Wi-Fi network is
+    /// configured: the settings, which set up the Wi-Fi and the API token. Once one is,
+    /// the access point is only the fallback of a clock that lost its network, and the
+    /// settings are treated as [Access::Network] so nobody in range can take it over.
+    Provisioning,
+    /// Nobody, the endpoint stays disabled until a token is set.
+    Token,
+}
+
+/// Checks the `Authorization: Bearer <token>` header against the configured API token.
+///
+/// # Parameters
+/// - `request`: The HTTP request to check.
+/// - `settings`: The [settings::Store] holding the API token.
+/// - `access`: Who may use the endpoint while no token is configured.
+///
+/// # Returns
+/// `Ok(())` if the request may proceed, or an [AppError::Unauthorized] otherwise.
+fn authorize(
+    request: &Request<&mut EspHttpConnection<'_>>,
+    settings: &settings::Store,
+    access: Access,
+) -> Result<(), AppError> {
+    let (token, provisioning) = {
+        let settings = settings.lock().unwrap();
+        let config = settings.get();
+        (config.api_token.clone(), wifi::is_provisioning(config))
+    };
+
+    if token.is_empty() {
+        return match access {
+            Access::Provisioning if provisioning => Ok(()),
+            Access::Provisioning | Access::Network if !wifi::is_access_point() => Ok(()),
+            Access::Provisioning | Access::Network => Err(AppError::Unauthorized(
+                "configure an API token in the settings to use this over the access point"
+                    .to_string(),
+            )),
+            Access::Token => Err(AppError::Unauthorized(
+                "configure an API token in the settings first".to_string(),
+            )),
+        };
+    }
+
+    let provided = request
+        .header("Authorization")
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .unwrap_or_default();
+
+    // Compares every byte so the response time does not leak the matching prefix
+    let matches = provided.len() == token.len()
+        && provided
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+
+    if !matches {
+        return Err(AppError::Unauthorized("invalid API token".to_string()));
+    }
+
+    Ok(())
+}
 
 /// Generates the index page response for the HTTP request.
 ///
@@ -30,25 +173,26 @@ pub fn index() -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), App
     }
 }
 
-/// Returns the current status of the system including [wifi::WIFI_SSID], [time::TIMEZONE], and time digits.
+/// Returns the current status of the system including the Wi-Fi SSID, the timezone, and the time.
+///
+/// # Arguments
+///
+/// * `events` - The [Events] hub holding the latest status.
 ///
 /// # Returns
 ///
 /// A closure that handles the HTTP request and returns an HTML response with system status information.
-pub fn get_status() -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+pub fn get_status(
+    events: Events,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
     move |request: Request<&mut EspHttpConnection<'_>>| {
-        let timezone = time::TIMEZONE;
-        let time = time::get_time();
+        let status = events.status();
 
         let status_html = format!(
             "<p><strong>Wi-Fi SSID:</strong> {}</p>
-        <p><strong>Time Zone:</strong> {timezone}</p>
-        <p><strong>Current Time:</strong> {}{}:{}{}</p>",
-            wifi::WIFI_SSID,
-            time[0],
-            time[1],
-            time[2],
-            time[3]
+        <p><strong>Time Zone:</strong> {}</p>
+        <p><strong>Current Time:</strong> {}</p>",
+            status.ssid, status.timezone, status.time
         );
 
         request.into_ok_response()?.write(status_html.as_bytes())?;
@@ -57,29 +201,116 @@ pub fn get_status() -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<()
     }
 }
 
+/// Returns the current [crate::events::Status] as JSON.
+///
+/// Used by the web interface as a polling fallback when the WebSocket stream
+/// served by [status_events] is unavailable.
+///
+/// # Arguments
+///
+/// * `events` - The [Events] hub holding the latest status.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the status as a JSON object.
+pub fn get_status_json(
+    events: Events,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| write_json(request, &events.status())
+}
+
+/// Returns the mirrored [display::DisplayState] as JSON.
+///
+/// The frame holds one byte per digit, with bits 0 to 6 driving segments `a` to `g`
+/// and bit 7 driving the colon.
+///
+/// # Arguments
+///
+/// * `display` - A [Tm1637] display instance.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the display state as a JSON object.
+pub unsafe fn get_display(
+    display: Tm1637<impl OutputPin, impl IOPin>,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| {
+        let state = display.lock().unwrap().state();
+
+        write_json(request, &state)
+    }
+}
+
+/// Streams status changes to a WebSocket client.
+///
+/// New clients receive the current [crate::events::Status] right away and are then
+/// subscribed to the [Events] hub, which pushes every change of time, display state
+/// (frame, brightness, on/off), display mode or sync state as a JSON text frame.
+///
+/// # Arguments
+///
+/// * `events` - The [Events] hub the clients subscribe to.
+///
+/// # Returns
+///
+/// A closure that handles the WebSocket connection events.
+pub fn status_events(events: Events) -> impl Fn(&mut EspHttpWsConnection) -> Result<(), AppError> {
+    move |ws: &mut EspHttpWsConnection| {
+        if ws.is_new() {
+            ws.send(
+                FrameType::Text(false),
+                events.status().to_json()?.as_bytes(),
+            )?;
+            events.subscribe(ws.create_detached_sender()?);
+            log::info!("Status subscriber connected (session {})", ws.session());
+        } else if ws.is_closed() {
+            log::info!("Status subscriber disconnected (session {})", ws.session());
+        } else {
+            // The stream is one-way: incoming frames are consumed and ignored
+            let (_frame_type, len) = ws.recv(&mut [])?;
+            if len > MAX_WS_FRAME_LEN {
+                ws.send(FrameType::Close, &[])?;
+            } else {
+                let mut buf = [0u8; MAX_WS_FRAME_LEN];
+                ws.recv(&mut buf)?;
+            }
+        }
+
+        Ok::<(), AppError>(())
+    }
+}
+
 /// Updates the display digits based on the digits found in the request URL.
 ///
 /// This function retrieves four digits from the request URI, updates the
-/// display accordingly, and responds with a success message.
+/// display accordingly, and responds with a success message. Once an API token is
+/// configured, the request must carry it as `Authorization: Bearer <token>`.
 ///
 /// # Arguments
 ///
 /// * `display` - A [Tm1637] display instance.
+/// * `events` - The [Events] hub notified of the mode change.
+/// * `settings` - The [settings::Store] holding the API token.
 ///
 /// # Returns
 ///
 /// A closure that handles the HTTP request, updates the display, and returns
-/// a success message.
+/// a success message, or a `401` response if the API token is wrong.
 pub unsafe fn set_digits(
     display: Tm1637<impl OutputPin, impl IOPin>,
+    events: Events,
+    settings: settings::Store,
 ) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
     move |request: Request<&mut EspHttpConnection<'_>>| {
+        if let Err(e) = authorize(&request, &settings, Access::Network) {
+            return write_error(request, e.status_code(), &e);
+        }
+
         let digits = utils::find_digits_in_url(request.uri());
 
-        let mut locked_display = display.lock().unwrap();
-        locked_display.clear()?;
-        locked_display.print_hex(0, &digits)?;
+        display::write(&display, display::hex_frame(&digits))?;
 
+        events.update(|status| status.mode = DisplayMode::Manual);
         log::info!("Display digits updated manually");
 
         request
@@ -93,77 +324,682 @@ pub unsafe fn set_digits(
 /// Sets the brightness of the display based on the request URL.
 ///
 /// This function extracts the brightness value from the URL query parameters
-/// and updates the display's brightness accordingly. The brightness value must
-/// be between 1 and 7.
+/// and runs [Command::SetBrightness] accordingly. The brightness value must
+/// be between 1 and 7. Once an API token is configured, the request must carry it as
+/// `Authorization: Bearer <token>`.
 ///
 /// # Arguments
 ///
-/// * `display` - A [Tm1637] display instance.
+/// * `clock` - The [Clock] the command acts on.
 ///
 /// # Returns
 ///
 /// A closure that handles the HTTP request, updates the brightness, and returns
-/// a success message.
+/// a success message, a `400` response if the value is invalid, or a `401` response
+/// if the API token is wrong.
 pub unsafe fn set_brightness(
-    display: Tm1637<impl OutputPin, impl IOPin>,
+    clock: Clock<impl OutputPin, impl IOPin>,
 ) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
     move |request: Request<&mut EspHttpConnection<'_>>| {
+        if let Err(e) = authorize(&request, &clock.settings, Access::Network) {
+            return write_error(request, e.status_code(), &e);
+        }
+
         let url = request.uri();
 
-        if let Some(start) = url.find('?') {
-            let brightness_value = &url[start + 1..];
-            if let Ok(brightness) = brightness_value.parse::<u8>() {
-                if (1..=7).contains(&brightness) {
-                    display.lock().unwrap().set_brightness(brightness)?;
-                    log::info!("Brightness updated to level {brightness}");
-                }
+        let brightness = url
+            .find('?')
+            .and_then(|start| url[start + 1..].parse::<u8>().ok())
+            .filter(|brightness| (1..=7).contains(brightness));
+
+        match brightness {
+            Some(brightness) => {
+                let reply = command::execute(&clock, &Command::SetBrightness(brightness))?;
+                request.into_ok_response()?.write(reply.as_bytes())?;
+            }
+            None => {
+                let error = AppError::BadRequest("brightness must be between 1 and 7".to_string());
+                write_error(request, error.status_code(), &error)?;
             }
         }
 
-        request
-            .into_ok_response()?
-            .write("Brightness Updated!".as_bytes())?;
-
         Ok::<(), AppError>(())
     }
 }
 
 /// Synchronizes the system time using SNTP and updates the display with the sync message.
 ///
-/// This function restarts the SNTP synchronization process, waits for completion,
-/// and updates the display with the current time once synchronization is finished.
+/// This function runs [Command::Sync], which restarts the SNTP synchronization process,
+/// waits for completion, and updates the display with the current time once
+/// synchronization is finished. Once an API token is configured, the request must
+/// carry it as `Authorization: Bearer <token>`.
 ///
 /// # Arguments
 ///
-/// * `display` - A [Tm1637] display instance.
-/// * `sntp` - An instance of [Sntp] used to synchronize the time.
+/// * `clock` - The [Clock] the command acts on.
 ///
 /// # Returns
 ///
 /// A closure that handles the HTTP request, synchronizes the time, updates the display,
-/// and returns a success message.
+/// and returns a success message, a `401` response if the API token is wrong, or a `500`
+/// response if the SNTP server did not answer.
 pub unsafe fn sync_time(
-    display: Tm1637<impl OutputPin, impl IOPin>,
-    sntp: Sntp,
+    clock: Clock<impl OutputPin, impl IOPin>,
 ) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
     move |request: Request<&mut EspHttpConnection<'_>>| {
-        let sync_message = DisplayMessage::Sync.as_bytes();
+        let result = authorize(&request, &clock.settings, Access::Network)
+            .and_then(|()| command::execute(&clock, &Command::Sync));
+
+        match result {
+            Ok(reply) => {
+                request.into_ok_response()?.write(reply.as_bytes())?;
+                Ok(())
+            }
+            Err(e) => write_error(request, e.status_code(), &e),
+        }
+    }
+}
+
+/// Returns the recent errors of the device as JSON, the most recent first, e.g.
+/// `[{"code": "E-12", "message": "SNTP error: ...", "count": 1, "uptime_ms": 31000, "time": "..."}]`.
+///
+/// Up to [error::MAX_RECENT_ERRORS] errors are kept, client errors are not listed.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the errors as a JSON array.
+pub fn get_errors() -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| write_json(request, &error::recent())
+}
+
+/// Returns the crash log as JSON, the most recent boot first, e.g.
+/// `[{"boot": 12, "reason": "panic", "panic": "panicked at src/device.rs:80:5: ...",
+/// "backtrace": {"task": "main", "pc": "0x400d1234", "frames": [...], "corrupted": false}}]`.
+///
+/// Every boot is listed with its reset reason, up to [crashlog::MAX_CRASH_ENTRIES].
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the boots as a JSON array.
+pub fn get_crashes() -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| write_json(request, &crashlog::entries())
+}
+
+/// Returns the health of the clock as JSON, e.g.
+/// `{"uptime_ms": 86400000, "heap": {"free": 112000, "free_min": 98000, "largest_block": 65536},
+/// "tasks": [{"name": "main", "watched": true, "heartbeat_age_ms": 120, "stack_free_min": 3400}]}`.
+///
+/// Watched tasks beat at least every few seconds, the others only when they get work.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the health as a JSON object.
+pub fn get_health() -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| write_json(request, &health::report())
+}
+
+/// Returns the log records kept on the clock as JSON, the oldest first, e.g.
+/// `[{"uptime_ms": 5200, "time": "...", "level": "INFO", "target": "esp_interstellar_clock::wifi", "message": "Wifi Connected!"}]`.
+///
+/// Up to [logs::MAX_LOG_RECORDS] records are kept.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the records as a JSON array.
+pub fn get_logs() -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| write_json(request, &logs::records())
+}
 
-        sntp_restart();
+/// Body of [set_log_level].
+#[derive(Deserialize)]
+struct LogLevel {
+    /// The module, e.g. `esp_interstellar_clock::wifi`, or `*` for all of them.
+    target: String,
+    /// The level, e.g. `debug` or `off`.
+    level: String,
+}
+
+/// Returns the log levels set at runtime as JSON, by target, e.g. `{"*": "INFO"}`.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the levels as a JSON object.
+pub fn get_log_levels() -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| write_json(request, &logs::levels())
+}
 
-        log::info!("Synchronizing with SNTP Server");
+/// Sets the log level of a module from the request body, e.g.
+/// `{"target": "esp_interstellar_clock::wifi", "level": "debug"}`, until the next reboot.
+///
+/// Once an API token is configured, the request must carry it as `Authorization: Bearer <token>`.
+///
+/// # Arguments
+///
+/// * `settings` - The [settings::Store] holding the API token.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the levels as [get_log_levels]
+/// does, a `400` response if the level is unknown, or a `401` response if the API token
+/// is wrong.
+pub fn set_log_level(
+    settings: settings::Store,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |mut request: Request<&mut EspHttpConnection<'_>>| {
+        let result = authorize(&request, &settings, Access::Network)
+            .and_then(|()| read_body(&mut request))
+            .and_then(|body| {
+                let LogLevel { target, level } = serde_json::from_slice(&body)?;
+                let level = LevelFilter::from_str(&level)
+                    .map_err(|_| AppError::BadRequest(format!("unknown log level `{level}`")))?;
+                logs::set_level(&target, level)?;
+                log::info!("Log level of `{target}` set to {level}");
 
-        display::write(&display, sync_message)?;
+                Ok(())
+            });
 
-        while sntp.get_sync_status() != SyncStatus::Completed {}
-        display::update_display_time(&display)?;
+        match result {
+            Ok(()) => write_json(request, &logs::levels()),
+            Err(e) if e.is_client_error() => write_error(request, e.status_code(), &e),
+            Err(e) => Err(e),
+        }
+    }
+}
 
-        log::info!("Time sync completed!");
+/// Body of [set_time].
+#[derive(Deserialize)]
+struct ManualTime {
+    /// The local date and time, in the configured timezone.
+    time: NaiveDateTime,
+}
+
+/// Sets the local date and time by hand from the request body, e.g.
+/// `{"time": "2026-10-18T14:30:00"}`, for clocks without a network.
+///
+/// This function runs [Command::SetTime]; the time stays marked as unsynchronized until
+/// SNTP or GPS takes over. Once an API token is configured, the request must carry it
+/// as `Authorization: Bearer <token>`.
+///
+/// # Arguments
+///
+/// * `clock` - The [Clock] the command acts on.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns a confirmation message, a `400`
+/// response if the time is invalid or kept by SNTP or GPS, or a `401` response if the
+/// API token is wrong.
+pub unsafe fn set_time(
+    clock: Clock<impl OutputPin, impl IOPin>,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |mut request: Request<&mut EspHttpConnection<'_>>| {
+        let result = authorize(&request, &clock.settings, Access::Network)
+            .and_then(|()| read_body(&mut request))
+            .and_then(|body| {
+                let manual: ManualTime = serde_json::from_slice(&body)?;
+                let command = Command::SetTime {
+                    date: Some(manual.time.date()),
+                    time: Some(manual.time.time()),
+                };
+                command::execute(&clock, &command)
+            });
+
+        match result {
+            Ok(reply) => {
+                request.into_ok_response()?.write(reply.as_bytes())?;
+                Ok(())
+            }
+            Err(e) if e.is_client_error() => write_error(request, e.status_code(), &e),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Returns the current [settings::Settings] as JSON.
+///
+/// The Wi-Fi password and the API token are write-only and always returned empty.
+///
+/// # Arguments
+///
+/// * `settings` - The [settings::Store] holding the current settings.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the settings as a JSON object.
+pub fn get_settings(
+    settings: settings::Store,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| {
+        let current = settings.lock().unwrap().get().redacted();
+
+        write_json(request, &current)
+    }
+}
+
+/// Updates the settings from a partial JSON object in the request body.
+///
+/// Only the fields present in the body are changed, e.g. `{"hostname": "bedroom-clock"}`,
+/// down to nested objects: `{"lamp": {"brightness": 80}}` keeps the other lamp options.
+/// The timezone applies right away, network-related options, such as the hostname
+/// or the Wi-Fi credentials, take effect after a restart.
+///
+/// Once an API token is configured, the request must carry it as `Authorization: Bearer <token>`.
+/// Without one, the settings are open over the access point only while no Wi-Fi network is
+/// configured.
+///
+/// # Arguments
+///
+/// * `settings` - The [settings::Store] holding the current settings.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request, persists the new settings and returns
+/// them as a JSON object, a `400` response if they are invalid, or a `401` response
+/// if the API token is wrong.
+pub fn set_settings(
+    settings: settings::Store,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |mut request: Request<&mut EspHttpConnection<'_>>| {
+        let result = authorize(&request, &settings, Access::Provisioning)
+            .and_then(|()| read_body(&mut request))
+            .and_then(|body| settings.lock().unwrap().merge_json(&body));
+
+        match result {
+            Ok(()) => {
+                log::info!("Settings updated");
+                let current = settings.lock().unwrap().get().redacted();
+                write_json(request, &current)
+            }
+            Err(e) if e.is_client_error() => write_error(request, e.status_code(), &e),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Turns off the sunrise showing before an alarm, until the next alarm.
+///
+/// This function runs [Command::CancelSunrise]; the alarm itself still rings. Once an
+/// API token is configured, the request must carry it as `Authorization: Bearer <token>`.
+///
+/// # Arguments
+///
+/// * `clock` - The [Clock] the command acts on.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns whether a sunrise was cancelled,
+/// or a `401` response if the API token is wrong.
+pub unsafe fn cancel_sunrise(
+    clock: Clock<impl OutputPin, impl IOPin>,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| {
+        let result = authorize(&request, &clock.settings, Access::Network)
+            .and_then(|()| command::execute(&clock, &Command::CancelSunrise));
+
+        match result {
+            Ok(reply) => {
+                request.into_ok_response()?.write(reply.as_bytes())?;
+                Ok(())
+            }
+            Err(e) if e.is_client_error() => write_error(request, e.status_code(), &e),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Erases the settings and the Wi-Fi credentials, then reboots into provisioning mode.
+///
+/// The request must carry the configured API token as `Authorization: Bearer <token>`;
+/// the endpoint stays disabled until a token is set. The reset runs [Command::FactoryReset]
+/// on its own thread, so the response is sent before the countdown starts.
+///
+/// # Arguments
+///
+/// * `clock` - The [Clock] the command acts on.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns a `202` response, or a `401`
+/// response if the API token is missing or wrong.
+pub unsafe fn factory_reset<CLK, DIO>(
+    clock: Clock<CLK, DIO>,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    move |request: Request<&mut EspHttpConnection<'_>>| {
+        if let Err(e) = authorize(&request, &clock.settings, Access::Token) {
+            log::warn!("Refused factory reset request: {e}");
+            return write_error(request, e.status_code(), &e);
+        }
+
+        let worker = clock.clone();
+        thread::Builder::new()
+            .name("factory-reset".to_string())
+            .stack_size(FACTORY_RESET_STACK_SIZE)
+            .spawn(move || {
+                if let Err(e) = command::execute(&worker, &Command::FactoryReset) {
+                    log::error!("Failed to factory reset: {:#?}", e);
+                    error::record(&e);
+                }
+            })
+            .map_err(|e| AppError::Thread(e.to_string()))?;
 
         request
-            .into_ok_response()?
-            .write("Time synced successfully!".as_bytes())?;
+            .into_response(202, None, &[("Content-Type", "application/json")])?
+            .write(br#"{"status":"resetting"}"#)?;
 
         Ok::<(), AppError>(())
     }
 }
+
+/// Body of a [test_buzzer] request, every field is optional.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct BuzzerTest {
+    /// A built-in tune, played when no `rtttl` is given.
+    tune: Tune,
+    /// A melody in RTTTL, e.g. `Test:d=4,o=5,b=120:c,e,g`.
+    rtttl: Option<String>,
+    /// The volume, from 0 to 100, instead of the one from the settings.
+    volume: Option<u8>,
+}
+
+/// Plays a melody once on the buzzer, to try the tunes and the volume.
+///
+/// The body is an optional JSON object such as `{"tune": "chime", "volume": 80}`
+/// or `{"rtttl": "Test:d=4,o=5,b=120:c,e,g"}`; an empty body plays the default tune.
+/// Once an API token is configured, the request must carry it as
+/// `Authorization: Bearer <token>`.
+///
+/// # Arguments
+///
+/// * `buzzer` - The [Buzzer] playing the melody.
+/// * `settings` - The [settings::Store] holding the default volume and the API token.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns a `202` response once the melody
+/// is queued, a `400` response if the body or the RTTTL is invalid, or a `401` response
+/// if the API token is wrong.
+pub fn test_buzzer(
+    buzzer: Buzzer,
+    settings: settings::Store,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |mut request: Request<&mut EspHttpConnection<'_>>| {
+        let result = authorize(&request, &settings, Access::Network)
+            .and_then(|()| read_body(&mut request))
+            .and_then(|body| {
+                let test: BuzzerTest = if body.is_empty() {
+                    BuzzerTest::default()
+                } else {
+                    serde_json::from_slice(&body)?
+                };
+                let melody = match &test.rtttl {
+                    Some(text) => rtttl::parse(text)
+                        .map_err(|e| AppError::BadRequest(format!("invalid RTTTL: {e}")))?,
+                    None => test.tune.melody(),
+                };
+                let volume = test
+                    .volume
+                    .unwrap_or_else(|| settings.lock().unwrap().get().buzzer_volume);
+
+                Ok((melody, volume.min(100)))
+            });
+
+        match result {
+            Ok((melody, volume)) => {
+                buzzer.play(melody, volume, false);
+                request
+                    .into_response(202, None, &[("Content-Type", "application/json")])?
+                    .write(br#"{"status":"playing"}"#)?;
+                Ok(())
+            }
+            Err(e) if e.is_client_error() => write_error(request, e.status_code(), &e),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Returns the options of the RGB LED lamp as JSON.
+///
+/// # Arguments
+///
+/// * `settings` - The [settings::Store] holding the lamp options.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the [Lamp] as a JSON object.
+pub fn get_lamp(
+    settings: settings::Store,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| {
+        let lamp = settings.lock().unwrap().get().lamp.clone();
+
+        write_json(request, &lamp)
+    }
+}
+
+/// Changes the RGB LED lamp from a partial JSON object in the request body.
+///
+/// Only the fields present in the body are changed, e.g. `{"on": true, "effect": "candle"}`.
+/// The options are persisted with the settings and shown on the next frame.
+///
+/// Once an API token is configured, the request must carry it as `Authorization: Bearer <token>`.
+///
+/// # Arguments
+///
+/// * `settings` - The [settings::Store] holding the lamp options.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the new [Lamp] as a JSON object,
+/// a `400` response if the options are invalid, or a `401` response if the API token is wrong.
+pub fn set_lamp(
+    settings: settings::Store,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |mut request: Request<&mut EspHttpConnection<'_>>| {
+        let result = authorize(&request, &settings, Access::Network)
+            .and_then(|()| read_body(&mut request))
+            .and_then(|body| {
+                let mut store = settings.lock().unwrap();
+                let lamp: Lamp = settings::patch_json(&store.get().lamp, &body)?;
+                store.update(|s| s.lamp = lamp)
+            });
+
+        match result {
+            Ok(()) => {
+                let lamp = settings.lock().unwrap().get().lamp.clone();
+                log::info!("Lamp updated to {:?}", lamp);
+                write_json(request, &lamp)
+            }
+            Err(e) if e.is_client_error() => write_error(request, e.status_code(), &e),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// An [Event] with the days left until it, as listed by the events API.
+#[derive(Serialize)]
+struct EventStatus<'a> {
+    #[serde(flatten)]
+    event: &'a Event,
+    /// Days from today to the event in the configured timezone, `0` on its day and
+    /// negative once it passed.
+    days: i64,
+}
+
+/// Body of a [delete_event] request.
+#[derive(Deserialize)]
+struct EventName {
+    name: String,
+}
+
+/// Responds with the events of the settings, soonest first, see [EventStatus].
+fn write_events(
+    request: Request<&mut EspHttpConnection<'_>>,
+    settings: &settings::Store,
+) -> Result<(), AppError> {
+    let mut events = settings.lock().unwrap().get().events.clone();
+    events.sort_by_key(|event| event.date);
+
+    let today = time::now().date_naive();
+    let events: Vec<EventStatus> = events
+        .iter()
+        .map(|event| EventStatus {
+            event,
+            days: countdown::days_until(event.date, today),
+        })
+        .collect();
+
+    write_json(request, &events)
+}
+
+/// Returns the events counted down to on the display as JSON.
+///
+/// # Arguments
+///
+/// * `settings` - The [settings::Store] holding the events.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the events, soonest first, with
+/// the days left until each, e.g. `[{"name": "Launch", "date": "2026-05-01", "days": 12}]`.
+pub fn get_events(
+    settings: settings::Store,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| write_events(request, &settings)
+}
+
+/// Adds an event from the JSON object in the request body, or replaces the one with
+/// the same name, e.g. `{"name": "Launch", "date": "2026-05-01"}`.
+///
+/// Once an API token is configured, the request must carry it as `Authorization: Bearer <token>`.
+///
+/// # Arguments
+///
+/// * `settings` - The [settings::Store] holding the events.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the events as [get_events] does,
+/// a `400` response if the event is invalid or too many are set, or a `401` response
+/// if the API token is wrong.
+pub fn set_event(
+    settings: settings::Store,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |mut request: Request<&mut EspHttpConnection<'_>>| {
+        let result = authorize(&request, &settings, Access::Network)
+            .and_then(|()| read_body(&mut request))
+            .and_then(|body| {
+                let event: Event = serde_json::from_slice(&body)?;
+                log::info!("Event `{}` set on {}", event.name, event.date);
+                settings.lock().unwrap().update(|s| {
+                    match s.events.iter_mut().find(|other| other.name == event.name) {
+                        Some(other) => *other = event,
+                        None => s.events.push(event),
+                    }
+                })
+            });
+
+        match result {
+            Ok(()) => write_events(request, &settings),
+            Err(e) if e.is_client_error() => write_error(request, e.status_code(), &e),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Removes the event named in the request body, e.g. `{"name": "Launch"}`.
+///
+/// Once an API token is configured, the request must carry it as `Authorization: Bearer <token>`.
+///
+/// # Arguments
+///
+/// * `settings` - The [settings::Store] holding the events.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the remaining events as
+/// [get_events] does, a `400` response if no event has this name, or a `401` response
+/// if the API token is wrong.
+pub fn delete_event(
+    settings: settings::Store,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |mut request: Request<&mut EspHttpConnection<'_>>| {
+        let result = authorize(&request, &settings, Access::Network)
+            .and_then(|()| read_body(&mut request))
+            .and_then(|body| {
+                let EventName { name } = serde_json::from_slice(&body)?;
+                let mut store = settings.lock().unwrap();
+                if !store.get().events.iter().any(|event| event.name == name) {
+                    return Err(AppError::BadRequest(format!("no event named `{name}`")));
+                }
+                log::info!("Event `{name}` removed");
+                store.update(|s| s.events.retain(|event| event.name != name))
+            });
+
+        match result {
+            Ok(()) => write_events(request, &settings),
+            Err(e) if e.is_client_error() => write_error(request, e.status_code(), &e),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The times of the sun returned by [get_sun], in the configured timezone.
+#[derive(Serialize)]
+struct SunTimes {
+    date: NaiveDate,
+    location: Location,
+    dawn: Option<DateTime<FixedOffset>>,
+    sunrise: Option<DateTime<FixedOffset>>,
+    noon: DateTime<FixedOffset>,
+    sunset: Option<DateTime<FixedOffset>>,
+    dusk: Option<DateTime<FixedOffset>>,
+    /// Whether it is between the civil dusk and dawn.
+    night: bool,
+}
+
+/// Returns the times of the sun today at the location of the settings as JSON.
+///
+/// The times are `null` on days the sun does not cross the horizon, or 6° below it
+/// for the civil twilight, as near the poles.
+///
+/// # Arguments
+///
+/// * `settings` - The [settings::Store] holding the location.
+///
+/// # Returns
+///
+/// A closure that handles the HTTP request and returns the civil dawn, the sunrise, the
+/// solar noon, the sunset and the civil dusk, or a `400` response if no location is set.
+pub fn get_sun(
+    settings: settings::Store,
+) -> impl Fn(Request<&mut EspHttpConnection<'_>>) -> Result<(), AppError> {
+    move |request: Request<&mut EspHttpConnection<'_>>| {
+        let Some(location) = settings.lock().unwrap().get().location else {
+            let e = AppError::BadRequest("set a location in the settings first".to_string());
+            return write_error(request, e.status_code(), &e);
+        };
+
+        let now = time::now();
+        let day = solar::today(&location, &now);
+        let local = |time: DateTime<Utc>| time.with_timezone(&now.timezone()).fixed_offset();
+
+        write_json(
+            request,
+            &SunTimes {
+                date: now.date_naive(),
+                location,
+                dawn: day.dawn().map(local),
+                sunrise: day.sunrise().map(local),
+                noon: local(day.noon),
+                sunset: day.sunset().map(local),
+                dusk: day.dusk().map(local),
+                night: day.is_night(now.to_utc()),
+            },
+        )
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{error::AppError, time};
+use esp_idf_svc::{
+    hal::task,
+    sys::{self, esp},
+};
+use serde::Serialize;
+use std::{marker::PhantomData, ptr, sync::Mutex, thread};
+
+/// A task reporting its heartbeats to the registry.
+struct Task {
+    /// Name of the thread, e.g. `input-poll`.
+    name: String,
+    /// The FreeRTOS handle of the task, kept as an address to share it between threads.
+    handle: usize,
+    /// Whether the task watchdog resets the clock when the task stops beating.
+    watched: bool,
+    /// The [time::uptime_ms] of the last heartbeat.
+    last_beat_ms: u64,
+}
+
+/// The tasks registered by [watch] and [track].
+static TASKS: Mutex<Vec<Task>> = Mutex::new(Vec::new());
+
+/// Keeps the calling task registered, and watched if it came from [watch], until dropped.
+///
+/// A task must drop it before it returns, as its handle is freed with it.
+pub struct Heartbeat {
+    /// The FreeRTOS handle of the task, see [Task].
+    handle: usize,
+    /// Whether the task was subscribed to the task watchdog.
+    watched: bool,
+    /// Keeps it on its task, the watchdog unsubscribes the task dropping it.
+    _task: PhantomData<*const ()>,
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        if self.watched {
+            if let Err(e) = esp!(unsafe { sys::esp_task_wdt_delete(ptr::null_mut()) }) {
+                log::warn!("Failed to stop watching the task: {e}");
+            }
+        }
+        TASKS
+            .lock()
+            .unwrap()
+            .retain(|task| task.handle != self.handle);
+    }
+}
+
+/// The health of a registered task, as listed by the API.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TaskHealth {
+    /// Name of the thread, e.g. `input-poll`.
+    pub name: String,
+    /// Whether the task is subscribed to the task watchdog, the others block waiting for
+    /// work and only beat when they get some.
+    pub watched: bool,
+    /// Milliseconds since the last heartbeat of the task.
+    pub heartbeat_age_ms: u64,
+    /// The smallest amount of stack left free since the task started, in bytes.
+    pub stack_free_min: u32,
+}
+
+/// The state of the heap, in bytes.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Heap {
+    /// Heap currently free.
+    pub free: u32,
+    /// The smallest amount of heap left free since the boot.
+    pub free_min: u32,
+    /// The largest block that can be allocated at once.
+    pub largest_block: usize,
+}
+
+/// The health of the clock, served by `/api/v1/health`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Health {
+    /// Milliseconds since the boot.
+    pub uptime_ms: u64,
+    /// The state of the heap.
+    pub heap: Heap,
+    /// The registered tasks, in the order they started.
+    pub tasks: Vec<TaskHealth>,
+}
+
+/// Returns the handle of the calling task.
+fn current_task() -> usize {
+    task::current().map_or(0, |handle| handle as usize)
+}
+
+/// Adds the calling task to the registry, under the name of its thread.
+fn register(watched: bool) -> Heartbeat {
+    let name = thread::current().name().unwrap_or("unnamed").to_string();
+    let handle = current_task();
+    TASKS.lock().unwrap().push(Task {
+        name,
+        handle,
+        watched,
+        last_beat_ms: time::uptime_ms(),
+    });
+
+    Heartbeat {
+        handle,
+        watched,
+        _task: PhantomData,
+    }
+}
+
+/// Subscribes the calling task to the task watchdog and registers it.
+///
+/// The task must then call [beat] more often than the watchdog timeout
+/// (`CONFIG_ESP_TASK_WDT_TIMEOUT_S`), or the clock resets and the crash log records a
+/// `task_watchdog` reset.
+///
+/// # Returns
+/// The [Heartbeat] keeping the task watched, or an [AppError] if the watchdog refuses it.
+///
+/// # Example
+/// ```rust
+/// let _heartbeat = health::watch()?;
+/// loop {
+///     health::beat();
+///     FreeRtos::delay_ms(FRAME_MS);
+/// }
+/// ```
+pub fn watch() -> Result<Heartbeat, AppError> {
+    esp!(unsafe { sys::esp_task_wdt_add(ptr::null_mut()) })?;
+
+    Ok(register(true))
+}
+
+/// Registers the calling task without the watchdog, for tasks blocking until they get
+/// work; their heartbeat age tells when they last had some.
+pub fn track() -> Heartbeat {
+    register(false)
+}
+
+/// Records a heartbeat of the calling task and feeds the watchdog if it is watched.
+///
+/// Does nothing for a task that is not registered, so long waits shared by several
+/// tasks, such as [time::init_sntp], can call it.
+pub fn beat() {
+    let handle = current_task();
+    let mut tasks = TASKS.lock().unwrap();
+    let Some(task) = tasks.iter_mut().find(|task| task.handle == handle) else {
+        return;
+    };
+
+    task.last_beat_ms = time::uptime_ms();
+    if task.watched {
+        unsafe { sys::esp_task_wdt_reset() };
+    }
+}
+
+/// Returns the health of the heap and of the registered tasks.
+pub fn report() -> Health {
+    let uptime_ms = time::uptime_ms();
+    let tasks = TASKS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|task| TaskHealth {
+            name: task.name.clone(),
+            watched: task.watched,
+            heartbeat_age_ms: uptime_ms.saturating_sub(task.last_beat_ms),
+            // ESP-IDF counts the stack in bytes
+            stack_free_min: unsafe {
+                sys::uxTaskGetStackHighWaterMark(task.handle as sys::TaskHandle_t)
+            },
+        })
+        .collect();
+
+    Health {
+        uptime_ms,
+        heap: Heap {
+            free: unsafe { sys::esp_get_free_heap_size() },
+            free_min: unsafe { sys::esp_get_minimum_free_heap_size() },
+            largest_block: unsafe { sys::heap_caps_get_largest_free_block(sys::MALLOC_CAP_8BIT) },
+        },
+        tasks,
+    }
+}
@@ -0,0 +1,49 @@
+use crate::error::AppError;
+use esp_idf_svc::hal::{
+    delay::TickType,
+    gpio::IOPin,
+    i2c::{I2c, I2cConfig, I2cDriver},
+    peripheral::Peripheral,
+    prelude::*,
+};
+use std::sync::{Arc, Mutex};
+
+/// Clock speed of the I2C bus, every device on it supports standard mode.
+const I2C_BAUDRATE_KHZ: u32 = 100;
+
+/// Time, in milliseconds, a transaction may take before it fails.
+const I2C_TIMEOUT_MS: u64 = 50;
+
+/// Type alias for the shared [I2cDriver].
+/// This is an ´Arc<Mutex<>>´ to ensure thread safety and shared access to the I2C bus.
+pub type I2cBus = Arc<Mutex<I2cDriver<'static>>>;
+
+/// Returns the timeout, in ticks, passed to the [I2cDriver] transactions.
+pub fn timeout() -> u32 {
+    TickType::new_millis(I2C_TIMEOUT_MS).ticks()
+}
+
+/// Creates the I2C bus shared by the optional sensors.
+///
+/// # Parameters
+/// - `i2c`: The I2C peripheral.
+/// - `sda`: The data pin (implements [IOPin]).
+/// - `scl`: The clock pin (implements [IOPin]).
+///
+/// # Returns
+/// The shared [I2cBus], or an [AppError] if the driver cannot be installed.
+///
+/// # Example
+/// ```rust
+/// let bus = get_i2c(peripherals.i2c0, peripherals.pins.gpio21, peripherals.pins.gpio22)?;
+/// ```
+pub fn get_i2c<I: I2c>(
+    i2c: impl Peripheral<P = I> + 'static,
+    sda: impl Peripheral<P = impl IOPin> + 'static,
+    scl: impl Peripheral<P = impl IOPin> + 'static,
+) -> Result<I2cBus, AppError> {
+    let config = I2cConfig::new().baudrate(I2C_BAUDRATE_KHZ.kHz().into());
+    let driver = I2cDriver::new(i2c, sda, scl, &config)?;
+
+    Ok(Arc::new(Mutex::new(driver)))
+}
@@ -0,0 +1,241 @@
+use crate::{
+    command::{self, Command},
+    device::Clock,
+    error::{self, AppError},
+    gesture::{
+        action, change_command, menu_key, Button, Debouncer, GestureRecognizer, InputEvent,
+        Quadrature,
+    },
+    health,
+    menu::{self, Key},
+    time,
+};
+use chrono::Offset;
+use esp_idf_svc::hal::{
+    delay::FreeRtos,
+    gpio::{AnyIOPin, IOPin, Input, OutputPin, PinDriver, Pull},
+};
+use std::{
+    sync::mpsc::{self, Receiver, SyncSender, TrySendError},
+    thread,
+};
+
+/// Interval, in milliseconds, between two samples of the inputs.
+const POLL_INTERVAL_MS: u32 = 10;
+
+/// Number of input events waiting to be handled before new ones are dropped.
+const QUEUE_LEN: usize = 16;
+
+/// Stack size of the thread polling the inputs.
+const POLL_STACK_SIZE: usize = 4096;
+
+/// Stack size of the thread running the actions, some commands format replies.
+const DISPATCH_STACK_SIZE: usize = 6144;
+
+/// The GPIOs wired to the inputs, all active low with the internal pull-ups.
+pub struct InputPins {
+    /// The buttons, wired between the pin and ground.
+    pub buttons: Vec<(Button, AnyIOPin)>,
+    /// The `A` and `B` pins of the rotary encoder, if one is fitted.
+    pub encoder: Option<(AnyIOPin, AnyIOPin)>,
+}
+
+/// A button being polled.
+struct PolledButton {
+    button: Button,
+    pin: PinDriver<'static, AnyIOPin, Input>,
+    debouncer: Debouncer,
+    gestures: GestureRecognizer,
+}
+
+/// Opens a pin as an input with its pull-up enabled.
+fn input_pin(pin: AnyIOPin) -> Result<PinDriver<'static, AnyIOPin, Input>, AppError> {
+    let mut driver = PinDriver::input(pin)?;
+    driver.set_pull(Pull::Up)?;
+
+    Ok(driver)
+}
+
+/// Queues an event, dropping it if the actions fall behind.
+fn send(queue: &SyncSender<InputEvent>, event: InputEvent) {
+    log::debug!("Input event {:?}", event);
+    if let Err(TrySendError::Full(event)) = queue.try_send(event) {
+        log::warn!("Input queue full, dropping {:?}", event);
+    }
+}
+
+/// Collects the current values shown by the settings menu.
+fn menu_values<CLK, DIO>(clock: &Clock<CLK, DIO>) -> menu::Values
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    let settings = clock.settings.lock().unwrap().get().clone();
+    let now = time::now();
+    // A zone off the hour, such as India (UTC+5:30), cannot be offered as a whole hour
+    let offset = now.offset().fix().local_minus_utc();
+    let utc_offset = (offset % 3600 == 0).then_some((offset / 3600) as i8);
+
+    menu::Values {
+        brightness: clock.display.lock().unwrap().state().brightness,
+        twelve_hour: settings.twelve_hour,
+        utc_offset,
+        now: now.naive_local(),
+        alarms: settings.alarms,
+    }
+}
+
+/// Passes a key to the settings menu, opening it first if needed.
+///
+/// # Returns
+/// The [Command] applying the setting confirmed by the key, if any.
+fn navigate<CLK, DIO>(clock: &Clock<CLK, DIO>, key: Key) -> Option<Command>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    let values = menu_values(clock);
+    let now_ms = time::uptime_ms();
+    let mut menu = clock.menu.lock().unwrap();
+
+    if !menu.is_open() {
+        menu.open(values, now_ms);
+        log::info!("Settings menu opened");
+        return None;
+    }
+
+    let change = menu.handle(key, now_ms);
+    if !menu.is_open() {
+        log::info!("Settings menu closed");
+    }
+
+    change.map(change_command)
+}
+
+/// Runs the [Command] mapped to every queued event, routing them to the menu while it is open.
+fn dispatch<CLK, DIO>(events: Receiver<InputEvent>, clock: Clock<CLK, DIO>)
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    let _heartbeat = health::track();
+    for event in events {
+        health::beat();
+        let command = match menu_key(event) {
+            Some(key) if key == Key::Menu || clock.menu.lock().unwrap().is_open() => {
+                navigate(&clock, key)
+            }
+            _ => {
+                let brightness = clock.display.lock().unwrap().state().brightness;
+                action(event, brightness)
+            }
+        };
+        let Some(command) = command else {
+            continue;
+        };
+
+        match command::execute(&clock, &command) {
+            Ok(reply) => log::info!("{reply}"),
+            Err(e) => {
+                log::error!("Failed to run {:?}: {:#?}", command, e);
+                error::record(&e);
+            }
+        }
+    }
+}
+
+/// Starts polling the buttons and the rotary encoder, running the mapped [action]s.
+///
+/// One thread samples the inputs every few milliseconds, debounces them, recognizes
+/// the [crate::gesture::Gesture]s and queues the resulting [InputEvent]s; another one runs the
+/// matching commands, so a slow command never delays the sampling.
+///
+/// # Parameters
+/// - `pins`: The [InputPins] the buttons and the encoder are wired to.
+/// - `clock`: The [Clock] the commands act on.
+///
+/// # Returns
+/// `Ok(())` if the input threads are started, or an [AppError] if it fails.
+///
+/// # Example
+/// ```rust
+/// let pins = InputPins {
+///     buttons: vec![(Button::Boot, peripherals.pins.gpio0.downgrade())],
+///     encoder: None,
+/// };
+/// start_input(pins, clock.clone()).expect("Failed to start the inputs");
+/// ```
+pub fn start_input<CLK, DIO>(pins: InputPins, clock: Clock<CLK, DIO>) -> Result<(), AppError>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    let mut buttons = pins
+        .buttons
+        .into_iter()
+        .map(|(button, pin)| {
+            let pin = input_pin(pin)?;
+            Ok(PolledButton {
+                button,
+                debouncer: Debouncer::new(pin.is_low()),
+                gestures: GestureRecognizer::default(),
+                pin,
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    let mut encoder = match pins.encoder {
+        Some((a, b)) => {
+            let (a, b) = (input_pin(a)?, input_pin(b)?);
+            let decoder = Quadrature::new(a.is_high(), b.is_high());
+            Some((a, b, decoder))
+        }
+        None => None,
+    };
+
+    let (queue, events) = mpsc::sync_channel(QUEUE_LEN);
+
+    thread::Builder::new()
+        .name("input-actions".to_string())
+        .stack_size(DISPATCH_STACK_SIZE)
+        .spawn(move || dispatch(events, clock))
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    thread::Builder::new()
+        .name("input-poll".to_string())
+        .stack_size(POLL_STACK_SIZE)
+        .spawn(move || {
+            let _heartbeat = health::watch()
+                .inspect_err(|e| {
+                    log::error!("Failed to watch the input poll task: {:#?}", e);
+                    error::record(e);
+                })
+                .ok();
+
+            loop {
+                let now_ms = time::uptime_ms();
+
+                for polled in buttons.iter_mut() {
+                    polled.debouncer.update(polled.pin.is_low(), now_ms);
+                    let pressed = polled.debouncer.level();
+                    if let Some(gesture) = polled.gestures.update(pressed, now_ms) {
+                        send(&queue, InputEvent::Button(polled.button, gesture));
+                    }
+                }
+
+                if let Some((a, b, decoder)) = encoder.as_mut() {
+                    if let Some(direction) = decoder.update(a.is_high(), b.is_high()) {
+                        send(&queue, InputEvent::Rotate(direction));
+                    }
+                }
+
+                health::beat();
+                FreeRtos::delay_ms(POLL_INTERVAL_MS);
+            }
+        })
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    log::info!("Input started");
+
+    Ok(())
+}
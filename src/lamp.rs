@@ -0,0 +1,457 @@
+use serde::{Deserialize, Serialize};
+use std::{f32::consts::PI, fmt, str::FromStr};
+
+/// Maximum number of LEDs driven on the strip.
+pub const MAX_LEDS: u16 = 144;
+
+/// Range of the color temperatures, in kelvin, accepted by the `temperature` effect.
+pub const KELVIN_RANGE: std::ops::RangeInclusive<u16> = 1000..=10000;
+
+/// Duration, in milliseconds, of a full breath of the `breathing` effect.
+const BREATHING_PERIOD_MS: u64 = 4000;
+
+/// Level, out of 255, the `breathing` effect fades down to.
+const BREATHING_MIN_LEVEL: f32 = 24.0;
+
+/// Duration, in milliseconds, of a full turn of the `rainbow` effect.
+const RAINBOW_PERIOD_MS: u64 = 10_000;
+
+/// Color temperature, in kelvin, of the `candle` effect.
+const CANDLE_KELVIN: u16 = 1800;
+
+/// Time, in milliseconds, between two flickers of the `candle` effect.
+const CANDLE_STEP_MS: u64 = 120;
+
+/// Level, out of 255, a flickering candle never goes below.
+const CANDLE_MIN_LEVEL: u32 = 140;
+
+/// A color, serialized as `#rrggbb`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::new(0, 0, 0);
+    pub const WHITE: Color = Color::new(255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+
+    /// Scales the color down to `level`, from 0 (off) to 255 (unchanged).
+    pub fn dim(self, level: u8) -> Color {
+        let scale = |c: u8| ((u16::from(c) * u16::from(level) + 127) / 255) as u8;
+
+        Color::new(scale(self.r), scale(self.g), scale(self.b))
+    }
+
+    /// Returns the fully saturated color at `hue` degrees of the color wheel.
+    pub fn from_hue(hue: u16) -> Color {
+        let hue = hue % 360;
+        let rising = ((hue % 60) * 255 / 60) as u8;
+        let falling = 255 - rising;
+
+        match hue / 60 {
+            0 => Color::new(255, rising, 0),
+            1 => Color::new(falling, 255, 0),
+            2 => Color::new(0, 255, rising),
+            3 => Color::new(0, falling, 255),
+            4 => Color::new(rising, 0, 255),
+            _ => Color::new(255, 0, falling),
+        }
+    }
+
+    /// Approximates the color of a black body at `kelvin`, from candlelight to daylight.
+    ///
+    /// Uses Tanner Helland's fit of the blackbody curve, accurate enough for LEDs
+    /// between 1000 K and 40000 K.
+    pub fn from_kelvin(kelvin: u16) -> Color {
+        let t = f32::from(kelvin) / 100.0;
+        let channel = |value: f32| value.clamp(0.0, 255.0).round() as u8;
+
+        let (r, g) = if t <= 66.0 {
+            (255.0, 99.470_8 * t.ln() - 161.119_57)
+        } else {
+            (
+                329.698_73 * (t - 60.0).powf(-0.133_204_76),
+                288.122_16 * (t - 60.0).powf(-0.075_514_85),
+            )
+        };
+        let b = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.517_73 * (t - 10.0).ln() - 305.044_8
+        };
+
+        Color::new(channel(r), channel(g), channel(b))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let invalid = || format!("invalid color `{s}`, expected `#rrggbb`");
+
+        if hex.len() != 6 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+
+        Ok(Color::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+/// The animation shown by the lamp.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    /// The color, steady.
+    #[default]
+    Solid,
+    /// The color, slowly fading in and out.
+    Breathing,
+    /// The color wheel, spread along the strip and turning.
+    Rainbow,
+    /// A warm light flickering like a flame.
+    Candle,
+    /// A white light at the configured color temperature.
+    Temperature,
+}
+
+/// Options of the RGB LED lamp, persisted with the settings.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Lamp {
+    /// Whether the lamp is lit.
+    pub on: bool,
+    pub effect: Effect,
+    /// The color of the `solid` and `breathing` effects.
+    pub color: Color,
+    /// Brightness, from 0 to 100 percent, on a perceptual scale.
+    pub brightness: u8,
+    /// Color temperature, in kelvin, of the `temperature` effect.
+    pub kelvin: u16,
+    /// Number of LEDs on the strip.
+    pub leds: u16,
+    /// Whether the lamp turns on at the civil dusk and off at the dawn, once a location
+    /// is set in the settings.
+    pub dusk_to_dawn: bool,
+}
+
+impl Default for Lamp {
+    fn default() -> Self {
+        Lamp {
+            on: false,
+            effect: Effect::default(),
+            color: Color::WHITE,
+            brightness: 50,
+            kelvin: 2700,
+            leds: 12,
+            dusk_to_dawn: false,
+        }
+    }
+}
+
+impl Lamp {
+    /// Whether the brightness, the color temperature and the number of LEDs are in range.
+    pub fn is_valid(&self) -> bool {
+        self.brightness <= 100 && KELVIN_RANGE.contains(&self.kelvin) && self.leds <= MAX_LEDS
+    }
+}
+
+/// Converts a brightness in percent to an LED level, so equal steps look equally bright.
+///
+/// The eye is far more sensitive to changes in dim light, so the percentage is taken
+/// as the CIE 1931 lightness and turned into the luminance the LEDs must emit. Any
+/// brightness above 0 keeps the LEDs at least faintly lit.
+pub fn perceived_level(percent: u8) -> u8 {
+    let lightness = f32::from(percent.min(100));
+    let luminance = if lightness <= 8.0 {
+        lightness / 903.3
+    } else {
+        ((lightness + 16.0) / 116.0).powi(3)
+    };
+    let level = (luminance * 255.0).round() as u8;
+
+    if percent > 0 {
+        level.max(1)
+    } else {
+        0
+    }
+}
+
+/// Computes the colors of the strip at a point in time.
+///
+/// The frame depends only on the options and `now_ms`, so the effects can be rendered
+/// at any rate and tested without the hardware.
+///
+/// # Parameters
+/// - `lamp`: The [Lamp] options.
+/// - `now_ms`: A monotonic time, in milliseconds, driving the animations.
+///
+/// # Returns
+/// One [Color] per LED, all black while the lamp is off.
+///
+/// # Example
+/// ```rust
+/// let colors = frame(&settings.lamp, time::uptime_ms());
+/// ```
+pub fn frame(lamp: &Lamp, now_ms: u64) -> Vec<Color> {
+    let leds = usize::from(lamp.leds);
+    if !lamp.on {
+        return vec![Color::BLACK; leds];
+    }
+
+    let level = perceived_level(lamp.brightness);
+
+    (0..leds)
+        .map(|index| pixel(lamp, index, leds, now_ms).dim(level))
+        .collect()
+}
+
+/// Returns the color of one LED at full brightness.
+fn pixel(lamp: &Lamp, index: usize, leds: usize, now_ms: u64) -> Color {
+    match lamp.effect {
+        Effect::Solid => lamp.color,
+        Effect::Breathing => {
+            let phase = (now_ms % BREATHING_PERIOD_MS) as f32 / BREATHING_PERIOD_MS as f32;
+            let wave = 0.5 - 0.5 * (2.0 * PI * phase).cos();
+            let level = BREATHING_MIN_LEVEL + (255.0 - BREATHING_MIN_LEVEL) * wave;
+
+            lamp.color.dim(level.round() as u8)
+        }
+        Effect::Rainbow => {
+            let offset = index * 360 / leds;
+            let turn = now_ms % RAINBOW_PERIOD_MS * 360 / RAINBOW_PERIOD_MS;
+
+            Color::from_hue(((offset as u64 + turn) % 360) as u16)
+        }
+        Effect::Candle => {
+            // Fades between random levels so the flame wavers instead of blinking
+            let step = now_ms / CANDLE_STEP_MS;
+            let progress = now_ms % CANDLE_STEP_MS * 255 / CANDLE_STEP_MS;
+            let from = flicker_level(index, step);
+            let to = flicker_level(index, step + 1);
+            let level = from + (to - from) * progress as i32 / 255;
+
+            Color::from_kelvin(CANDLE_KELVIN).dim(level as u8)
+        }
+        Effect::Temperature => Color::from_kelvin(lamp.kelvin),
+    }
+}
+
+/// Returns the pseudo-random level of an LED of the candle at a flicker step.
+fn flicker_level(index: usize, step: u64) -> i32 {
+    let range = 256 - CANDLE_MIN_LEVEL;
+
+    (CANDLE_MIN_LEVEL + u32::from(noise(index as u64, step)) % range) as i32
+}
+
+/// Hashes an LED index and a time step into a byte, the same inputs always giving the same byte.
+///
+/// A splitmix64 finalizer, so the effects need no random number generator.
+fn noise(index: u64, step: u64) -> u8 {
+    let mut x =
+        index.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ step.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x ^= x >> 31;
+    x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 29;
+
+    (x >> 56) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lamp(effect: Effect) -> Lamp {
+        Lamp {
+            on: true,
+            effect,
+            color: Color::new(255, 128, 0),
+            brightness: 100,
+            leds: 6,
+            ..Lamp::default()
+        }
+    }
+
+    #[test]
+    fn parses_and_prints_colors() {
+        assert_eq!("#ff8000".parse(), Ok(Color::new(255, 128, 0)));
+        assert_eq!("00FF7f".parse(), Ok(Color::new(0, 255, 127)));
+        assert_eq!(Color::new(1, 171, 205).to_string(), "#01abcd");
+        assert!("#ff80".parse::<Color>().is_err());
+        assert!("#gg8000".parse::<Color>().is_err());
+        assert!("#ff8000ff".parse::<Color>().is_err());
+        assert!("#+f8000".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn dims_colors() {
+        let color = Color::new(255, 128, 2);
+        assert_eq!(color.dim(255), color);
+        assert_eq!(color.dim(0), Color::BLACK);
+        assert_eq!(color.dim(128), Color::new(128, 64, 1));
+    }
+
+    #[test]
+    fn turns_hues_into_colors() {
+        assert_eq!(Color::from_hue(0), Color::new(255, 0, 0));
+        assert_eq!(Color::from_hue(60), Color::new(255, 255, 0));
+        assert_eq!(Color::from_hue(120), Color::new(0, 255, 0));
+        assert_eq!(Color::from_hue(240), Color::new(0, 0, 255));
+        assert_eq!(Color::from_hue(30), Color::new(255, 127, 0));
+        assert_eq!(Color::from_hue(360), Color::from_hue(0));
+    }
+
+    #[test]
+    fn turns_temperatures_into_colors() {
+        // Candlelight has no blue, daylight is close to white
+        assert_eq!(Color::from_kelvin(1800).b, 0);
+        assert_eq!(Color::from_kelvin(1800).r, 255);
+        let daylight = Color::from_kelvin(6600);
+        assert!(daylight.r >= 250 && daylight.g >= 240 && daylight.b == 255);
+        let cold = Color::from_kelvin(10000);
+        assert!(cold.b == 255 && cold.r < cold.b);
+    }
+
+    #[test]
+    fn maps_brightness_perceptually() {
+        assert_eq!(perceived_level(0), 0);
+        assert_eq!(perceived_level(1), 1);
+        assert_eq!(perceived_level(50), 47);
+        assert_eq!(perceived_level(100), 255);
+        assert_eq!(perceived_level(200), 255);
+
+        let levels: Vec<_> = (0..=100).map(perceived_level).collect();
+        assert!(levels.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn off_lamp_is_black() {
+        let lamp = Lamp {
+            on: false,
+            ..lamp(Effect::Solid)
+        };
+        assert_eq!(frame(&lamp, 1234), vec![Color::BLACK; 6]);
+    }
+
+    #[test]
+    fn solid_applies_color_and_brightness() {
+        assert_eq!(
+            frame(&lamp(Effect::Solid), 0),
+            vec![Color::new(255, 128, 0); 6]
+        );
+
+        let half = Lamp {
+            brightness: 50,
+            ..lamp(Effect::Solid)
+        };
+        assert_eq!(frame(&half, 0), vec![Color::new(255, 128, 0).dim(47); 6]);
+
+        let empty = Lamp {
+            leds: 0,
+            ..lamp(Effect::Solid)
+        };
+        assert!(frame(&empty, 0).is_empty());
+    }
+
+    #[test]
+    fn breathing_fades_in_and_out() {
+        let lamp = lamp(Effect::Breathing);
+        let low = frame(&lamp, 0)[0];
+        let high = frame(&lamp, BREATHING_PERIOD_MS / 2)[0];
+
+        assert_eq!(low, lamp.color.dim(BREATHING_MIN_LEVEL as u8));
+        assert_eq!(high, lamp.color);
+        assert_eq!(frame(&lamp, BREATHING_PERIOD_MS)[0], low);
+    }
+
+    #[test]
+    fn rainbow_spreads_and_turns() {
+        let lamp = lamp(Effect::Rainbow);
+        let colors = frame(&lamp, 0);
+        assert_eq!(colors[0], Color::from_hue(0));
+        assert_eq!(colors[2], Color::from_hue(120));
+        assert_eq!(colors[4], Color::from_hue(240));
+
+        let turned = frame(&lamp, RAINBOW_PERIOD_MS / 4);
+        assert_eq!(turned[0], Color::from_hue(90));
+        assert_eq!(turned[4], Color::from_hue(330));
+        assert_eq!(frame(&lamp, RAINBOW_PERIOD_MS), colors);
+    }
+
+    #[test]
+    fn candle_flickers_in_range() {
+        let lamp = lamp(Effect::Candle);
+        let flame = Color::from_kelvin(CANDLE_KELVIN);
+
+        for now_ms in (0..5_000).step_by(37) {
+            for color in frame(&lamp, now_ms) {
+                assert!(color.r >= flame.dim(CANDLE_MIN_LEVEL as u8).r);
+                assert!(color.r <= flame.r && color.b == 0);
+            }
+        }
+        // The same time always gives the same frame, and the LEDs differ
+        assert_eq!(frame(&lamp, 4321), frame(&lamp, 4321));
+        let colors = frame(&lamp, 0);
+        assert!(colors.iter().any(|color| *color != colors[0]));
+    }
+
+    #[test]
+    fn temperature_uses_the_kelvins() {
+        let lamp = Lamp {
+            kelvin: 4000,
+            ..lamp(Effect::Temperature)
+        };
+        assert_eq!(frame(&lamp, 0), vec![Color::from_kelvin(4000); 6]);
+    }
+
+    #[test]
+    fn validates_the_options() {
+        assert!(Lamp::default().is_valid());
+        assert!(!Lamp {
+            brightness: 101,
+            ..Lamp::default()
+        }
+        .is_valid());
+        assert!(!Lamp {
+            kelvin: 999,
+            ..Lamp::default()
+        }
+        .is_valid());
+        assert!(!Lamp {
+            leds: MAX_LEDS + 1,
+            ..Lamp::default()
+        }
+        .is_valid());
+    }
+}
@@ -0,0 +1,21 @@
+//! The logic of the clock that does not touch the hardware: parsers, protocol codecs and
+//! state machines. It builds for the host too, so its unit tests run with
+//! `cargo test --lib --target <host triple>`; the firmware itself is the binary.
+
+pub mod alarm;
+pub mod climate;
+pub mod command;
+pub mod countdown;
+pub mod dilation;
+pub mod ds3231;
+pub mod gesture;
+pub mod group;
+pub mod lamp;
+pub mod menu;
+pub mod nmea;
+pub mod ntp;
+pub mod rtttl;
+pub mod solar;
+pub mod sunrise;
+pub mod utils;
+pub mod world;
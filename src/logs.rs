@@ -0,0 +1,134 @@
+use crate::{error::AppError, time};
+use chrono::{DateTime, Utc};
+use esp_idf_svc::log::EspLogger;
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+};
+
+/// Number of log records kept by the [RingLogger], the oldest are dropped first.
+pub const MAX_LOG_RECORDS: usize = 100;
+
+/// Longest message kept in a log record, in bytes; the UART still gets all of it.
+const MAX_MESSAGE_LENGTH: usize = 160;
+
+/// A log record kept by the [RingLogger], as listed by the API.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LogRecord {
+    /// The [time::uptime_ms] of the record.
+    pub uptime_ms: u64,
+    /// The system time of the record, only meaningful once the time is set.
+    pub time: DateTime<Utc>,
+    /// The level of the record, e.g. `INFO`.
+    pub level: &'static str,
+    /// The module that logged the record, e.g. `esp_interstellar_clock::wifi`.
+    pub target: String,
+    /// The message, cut to [MAX_MESSAGE_LENGTH] bytes.
+    pub message: String,
+}
+
+/// Writes the log to the UART like [EspLogger], and keeps the last [MAX_LOG_RECORDS]
+/// records for the API.
+pub struct RingLogger {
+    /// Writes the records to the UART and keeps the levels of the targets.
+    uart: EspLogger,
+    /// The records kept, the most recent last.
+    records: Mutex<VecDeque<LogRecord>>,
+    /// The levels set by [set_level], by target.
+    levels: Mutex<BTreeMap<String, LevelFilter>>,
+}
+
+/// The logger installed by [init_logger].
+static LOGGER: RingLogger = RingLogger {
+    uart: EspLogger::new(),
+    records: Mutex::new(VecDeque::new()),
+    levels: Mutex::new(BTreeMap::new()),
+};
+
+impl Log for RingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.uart.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.uart.log(record);
+
+        let mut message = record.args().to_string();
+        if message.len() > MAX_MESSAGE_LENGTH {
+            let mut end = MAX_MESSAGE_LENGTH;
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message.truncate(end);
+        }
+
+        let mut records = self.records.lock().unwrap();
+        if records.len() == MAX_LOG_RECORDS {
+            records.pop_front();
+        }
+        records.push_back(LogRecord {
+            uptime_ms: time::uptime_ms(),
+            time: Utc::now(),
+            level: record.level().as_str(),
+            target: record.target().to_string(),
+            message,
+        });
+    }
+
+    fn flush(&self) {
+        self.uart.flush();
+    }
+}
+
+/// Installs the [RingLogger] in place of [EspLogger::initialize_default].
+///
+/// # Returns
+/// `Ok(())` once installed, or a [SetLoggerError] if a logger was already installed.
+pub fn init_logger() -> Result<(), SetLoggerError> {
+    log::set_logger(&LOGGER)?;
+    LOGGER.uart.initialize();
+
+    Ok(())
+}
+
+/// Returns the log records kept, the oldest first.
+pub fn records() -> Vec<LogRecord> {
+    LOGGER.records.lock().unwrap().iter().cloned().collect()
+}
+
+/// Sets the level of the records logged by a module, or by all of them with `*`.
+///
+/// Levels above `CONFIG_LOG_MAXIMUM_LEVEL` stay filtered out.
+///
+/// # Parameters
+/// - `target`: The module, e.g. `esp_interstellar_clock::wifi`, or `*`.
+/// - `level`: The most verbose level logged.
+///
+/// # Returns
+/// `Ok(())` once the level is set, or an [AppError] if ESP-IDF refuses it.
+pub fn set_level(target: &str, level: LevelFilter) -> Result<(), AppError> {
+    LOGGER.uart.set_target_level(target, level)?;
+    LOGGER
+        .levels
+        .lock()
+        .unwrap()
+        .insert(target.to_string(), level);
+
+    Ok(())
+}
+
+/// Returns the levels set by [set_level], by target, e.g. `{"*": "INFO"}`.
+pub fn levels() -> BTreeMap<String, String> {
+    LOGGER
+        .levels
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(target, level)| (target.clone(), level.to_string()))
+        .collect()
+}
@@ -1,59 +1,517 @@
 use error::AppError;
 use esp_idf_svc::{
-    hal::{delay::FreeRtos, prelude::Peripherals},
-    http::Method,
+    hal::{
+        delay::FreeRtos,
+        gpio::{Gpio4, Gpio5, IOPin, InputPin},
+        prelude::Peripherals,
+    },
+    http::{server::EspHttpServer, Method},
+    nvs::EspDefaultNvsPartition,
 };
+use esp_interstellar_clock::{
+    alarm, climate, command, countdown, dilation, ds3231, gesture, group, lamp, menu, nmea, ntp,
+    rtttl, solar, sunrise, utils, world,
+};
+use events::ServiceState;
+use std::sync::Arc;
 
+mod buzzer;
+mod crashlog;
+mod device;
 mod display;
 mod error;
+mod events;
+mod gps;
+mod group_sync;
 mod handler;
+mod health;
+mod i2c;
+mod input;
+mod logs;
+mod mdns;
+mod mqtt;
+mod rtc;
+mod sensor;
 mod server;
+mod settings;
+mod shell;
+mod sntp_server;
+mod strip;
 mod time;
-mod utils;
 mod wifi;
 
 fn main() -> Result<(), AppError> {
     esp_idf_svc::sys::link_patches();
-    esp_idf_svc::log::EspLogger::initialize_default();
+    logs::init_logger().expect("Failed to install the logger");
+    crashlog::install_panic_hook();
+
+    // Reset the clock if the boot or the main loop hangs, see the crash log
+    let _heartbeat = health::watch()
+        .inspect_err(|e| {
+            log::error!("Failed to watch the main task: {:#?}", e);
+            eprintln!("Failed to watch the main task: {:#?}", e);
+            error::record(e);
+        })
+        .ok();
 
     let peripherals = Peripherals::take().expect("Failed to take peripherals");
+    let nvs = EspDefaultNvsPartition::take().expect("Failed to take NVS partition");
 
-    // Initialize the display
-    let display = display::get_display(peripherals.pins.gpio4, peripherals.pins.gpio5)
-        .inspect_err(|e| {
+    // Record why the clock started, a log that cannot be kept does not stop the boot
+    if let Err(e) = crashlog::record_boot(nvs.clone()) {
+        log::error!("Failed to record the boot: {:#?}", e);
+        eprintln!("Failed to record the boot: {:#?}", e);
+        error::record(&e);
+    }
+
+    // Load the settings, or keep the defaults in memory so the clock still runs
+    let (settings, settings_state) = match settings::load_settings(nvs.clone()) {
+        Ok(settings) => (settings, ServiceState::Running),
+        Err(e) => {
+            log::error!("Failed to load settings: {:#?}", e);
+            eprintln!("Failed to load settings: {:#?}", e);
+            error::record(&e);
+            let reason = format!("defaults kept until the restart: {e}");
+            (settings::memory_settings(), ServiceState::Degraded(reason))
+        }
+    };
+
+    // Start the status events broadcaster, or keep the status for the API alone
+    let events = match events::get_events() {
+        Ok(events) => {
+            events.update(|s| s.services.events = ServiceState::Running);
+            events
+        }
+        Err(e) => {
+            log::error!("Failed to start status events: {:#?}", e);
+            eprintln!("Failed to start status events: {:#?}", e);
+            error::record(&e);
+            let events = events::local_events();
+            let reason = format!("no live updates: {e}");
+            events.update(|s| s.services.events = ServiceState::Degraded(reason));
+            events
+        }
+    };
+    events.update(|s| s.services.settings = settings_state);
+
+    // Initialize the display; a missing or broken display leaves the clock running
+    // headless, see the status API
+    let display = match display::get_display(
+        peripherals.pins.gpio4,
+        peripherals.pins.gpio5,
+        events.clone(),
+    ) {
+        Ok(display) => {
+            match display::init_display(&display) {
+                Ok(()) => events.update(|s| s.services.display = ServiceState::Running),
+                Err(e) => {
+                    log::error!("Failed to configure display: {:#?}", e);
+                    eprintln!("Failed to configure display: {:#?}", e);
+                    error::record(&e);
+                    events.update(|s| s.services.display = ServiceState::Failed(e.to_string()));
+                }
+            }
+            display
+        }
+        Err(e) => {
             log::error!("Failed to initialize display: {:#?}", e);
             eprintln!("Failed to initialize display: {:#?}", e);
-        })?;
-    display::init_display(&display).inspect_err(|e| {
-        log::error!("Failed to configure display: {:#?}", e);
-        eprintln!("Failed to configure display: {:#?}", e);
-    })?;
-
-    // Initialize Wi-Fi
-    let mut wifi = wifi::get_wifi(peripherals.modem).inspect_err(|e| {
-        log::error!("Failed to get Wi-Fi: {:#?}", e);
-        eprintln!("Failed to get Wi-Fi: {:#?}", e);
-    })?;
-    wifi::init_wifi(&mut wifi).inspect_err(|e| {
-        log::error!("Failed to initialize Wi-Fi: {:#?}", e);
-        eprintln!("Failed to initialize Wi-Fi: {:#?}", e);
-    })?;
-
-    // Initialize SNTP
-    let sntp = time::get_sntp().inspect_err(|e| {
-        log::error!("Failed to get SNTP: {:#?}", e);
-        eprintln!("Failed to get SNTP: {:#?}", e);
-    })?;
-    time::init_sntp(&sntp).inspect_err(|e| {
-        log::error!("Failed to initialize SNTP: {:#?}", e);
-        eprintln!("Failed to initialize SNTP: {:#?}", e);
-    })?;
-
-    // Start the HTTP server
-    let mut http_server = server::start_server().inspect_err(|e| {
-        log::error!("Failed to start HTTP server: {:#?}", e);
-        eprintln!("Failed to start HTTP server: {:#?}", e);
-    })?;
+            error::record(&e);
+            events.update(|s| s.services.display = ServiceState::Failed(e.to_string()));
+            display::headless_display(events.clone())
+        }
+    };
+
+    // Set the time from the RTC, if one is fitted, until SNTP syncs
+    let i2c_bus = start_service(
+        &events,
+        "I2C bus",
+        |s| &mut s.i2c,
+        i2c::get_i2c(
+            peripherals.i2c0,
+            peripherals.pins.gpio21,
+            peripherals.pins.gpio22,
+        ),
+    );
+    let rtc = i2c_bus.clone().and_then(rtc::get_rtc);
+    if let Some(rtc) = &rtc {
+        // A failing RTC only delays the time until SNTP, so the boot goes on
+        if let Err(e) = rtc.seed_system_time() {
+            log::error!("Failed to read RTC: {:#?}", e);
+            eprintln!("Failed to read RTC: {:#?}", e);
+        }
+        events.update(|s| {
+            s.rtc = rtc.status().ok();
+            s.time_source = time::time_source();
+        });
+    }
+
+    // Read the time from the optional GPS receiver, trusted over SNTP and the RTC
+    let gps = gps::get_gps_uart(
+        peripherals.uart2,
+        peripherals.pins.gpio17,
+        peripherals.pins.gpio16,
+    )
+    .and_then(|uart| {
+        gps::start_gps(
+            uart,
+            peripherals.pins.gpio34.downgrade_input(),
+            events.clone(),
+        )
+    });
+    start_service(&events, "GPS", |s| &mut s.gps, gps);
+
+    // Create the Wi-Fi and SNTP services, they connect later on
+    let config = settings.lock().unwrap().get().clone();
+    let mut wifi = wifi::get_wifi(peripherals.modem, nvs.clone(), &config)
+        .inspect_err(|e| {
+            log::error!("Failed to get Wi-Fi: {:#?}", e);
+            eprintln!("Failed to get Wi-Fi: {:#?}", e);
+            error::record(e);
+            events.update(|s| s.services.network = ServiceState::Failed(e.to_string()));
+        })
+        .ok();
+    let sntp = time::on_sntp_sync(rtc, events.clone())
+        .and_then(time::get_sntp)
+        .inspect_err(|e| {
+            log::error!("Failed to get SNTP: {:#?}", e);
+            eprintln!("Failed to get SNTP: {:#?}", e);
+            error::record(e);
+            events.update(|s| s.services.time = ServiceState::Failed(e.to_string()));
+        })
+        .ok()
+        .map(Arc::new);
+
+    // Start the buzzer player, the alarms still show on the display without it
+    let buzzer = start_service(
+        &events,
+        "buzzer",
+        |s| &mut s.buzzer,
+        buzzer::get_buzzer(
+            peripherals.ledc.timer0,
+            peripherals.ledc.channel0,
+            peripherals.pins.gpio27,
+        ),
+    )
+    .unwrap_or_else(buzzer::silent_buzzer);
+
+    // Sample the optional climate sensor
+    match i2c_bus {
+        Some(i2c_bus) => {
+            let sensor = sensor::start_sensor(
+                i2c_bus,
+                peripherals.pins.gpio23.downgrade(),
+                settings.clone(),
+                events.clone(),
+            );
+            start_service(&events, "climate sensor", |s| &mut s.sensor, sensor);
+        }
+        None => {
+            events.update(|s| s.services.sensor = ServiceState::Failed("no I2C bus".to_string()))
+        }
+    }
+
+    // Render the lamp effects and the sunrise on the LED strip
+    let sunrise = sunrise::get_sunrise();
+    let strip = strip::start_strip(
+        peripherals.rmt.channel0,
+        peripherals.pins.gpio13,
+        settings.clone(),
+        sunrise.clone(),
+    );
+    start_service(&events, "LED strip", |s| &mut s.strip, strip);
+
+    let clock = device::Clock {
+        display: display.clone(),
+        settings: settings.clone(),
+        events: events.clone(),
+        sntp: sntp.clone(),
+        alarms: alarm::get_alarms(),
+        menu: Default::default(),
+        buzzer: buzzer.clone(),
+        sunrise: sunrise.clone(),
+    };
+
+    // Start the serial shell, available even while the network is down
+    let shell = shell::get_console(
+        peripherals.uart0,
+        peripherals.pins.gpio1,
+        peripherals.pins.gpio3,
+    )
+    .and_then(|console| shell::start_shell(console, clock.clone()));
+    start_service(&events, "shell", |s| &mut s.shell, shell);
+
+    // Poll the buttons and the rotary encoder, holding BOOT erases the clock
+    let input_pins = input::InputPins {
+        buttons: vec![
+            (gesture::Button::Boot, peripherals.pins.gpio0.downgrade()),
+            (gesture::Button::Mode, peripherals.pins.gpio18.downgrade()),
+            (gesture::Button::Action, peripherals.pins.gpio19.downgrade()),
+        ],
+        encoder: Some((
+            peripherals.pins.gpio25.downgrade(),
+            peripherals.pins.gpio26.downgrade(),
+        )),
+    };
+    let input = input::start_input(input_pins, clock.clone());
+    start_service(&events, "input", |s| &mut s.input, input);
+
+    // Connect to Wi-Fi, or start the provisioning access point; without a network the
+    // clock keeps running and its time can be set by hand
+    let mut online = match wifi.as_mut() {
+        Some(wifi) => start_network(wifi, &config, &events),
+        None => false,
+    };
+
+    // Advertise the web interface via mDNS
+    let _mdns = if wifi.is_some() && config.mdns_enabled {
+        mdns::get_mdns()
+            .and_then(|mut mdns| mdns::init_mdns(&mut mdns, &config).map(|()| mdns))
+            .inspect_err(|e| {
+                log::error!("Failed to start mDNS: {:#?}", e);
+                eprintln!("Failed to start mDNS: {:#?}", e);
+                error::record(e);
+                degrade_network(&events, format!("mDNS failed: {e}"));
+            })
+            .ok()
+    } else {
+        None
+    };
+
+    // Synchronize the time with SNTP, unreachable while offline; SNTP keeps polling
+    // in the background after a timeout
+    match &sntp {
+        Some(sntp) if online => match time::init_sntp(sntp) {
+            Ok(()) => events.update(|s| s.services.time = ServiceState::Running),
+            Err(e) => {
+                log::error!("Failed to initialize SNTP: {:#?}", e);
+                eprintln!("Failed to initialize SNTP: {:#?}", e);
+                error::record(&e);
+                events.update(|s| s.services.time = ServiceState::Degraded(e.to_string()));
+            }
+        },
+        Some(_) => events.update(|s| {
+            s.services.time = ServiceState::Degraded("offline, set the time by hand".to_string())
+        }),
+        None => {}
+    }
+
+    // Serve the time to the network, once the Wi-Fi is up
+    if wifi.is_some() && config.sntp_server {
+        if let Err(e) = sntp_server::start_sntp_server() {
+            log::error!("Failed to start SNTP server: {:#?}", e);
+            eprintln!("Failed to start SNTP server: {:#?}", e);
+            error::record(&e);
+            degrade_network(&events, format!("SNTP server failed: {e}"));
+        }
+    }
+
+    // Start the services needing the network, or once the main loop joins it again
+    if online {
+        start_online_services(&clock, &nvs, &config);
+    }
+
+    // Serve the web interface and the API, even without a display or a network
+    let _http_server = start_http_server(&clock)
+        .inspect(|_| events.update(|s| s.services.server = ServiceState::Running))
+        .inspect_err(|e| {
+            log::error!("Failed to start HTTP server: {:#?}", e);
+            eprintln!("Failed to start HTTP server: {:#?}", e);
+            error::record(e);
+            events.update(|s| s.services.server = ServiceState::Failed(e.to_string()));
+        })
+        .ok();
+
+    // Refresh the display and ring the alarms
+    let mut ticker = device::Ticker::default();
+    let mut display_ok = !events.status().services.display.is_impaired();
+    let headless = display.lock().unwrap().is_headless();
+    let provisioning = wifi::is_provisioning(&config);
+    let mut rejoin_ms = time::uptime_ms();
+    loop {
+        // The alarms, the status and the web interface keep running without a display,
+        // and the display comes back on its own once it answers again
+        match clock.tick(&mut ticker) {
+            Ok(()) if !display_ok && !headless => {
+                log::info!("Display recovered");
+                display_ok = true;
+                events.update(|s| s.services.display = ServiceState::Running);
+            }
+            Err(e) if display_ok => {
+                log::error!("Failed to update display: {:#?}", e);
+                eprintln!("Failed to update display: {:#?}", e);
+                error::record(&e);
+                display_ok = false;
+                events.update(|s| s.services.display = ServiceState::Failed(e.to_string()));
+            }
+            _ => {}
+        }
+
+        // A configured clock that started its fallback access point keeps trying its
+        // network, and SNTP takes over once it is joined
+        if let Some(wifi) = wifi.as_mut().filter(|_| !online && !provisioning) {
+            match wifi::rejoin(wifi, &mut rejoin_ms) {
+                Ok(true) => {
+                    online = true;
+                    events.update(|s| {
+                        s.ssid = config.wifi_ssid.clone();
+                        s.services.network = ServiceState::Running;
+                    });
+                    start_online_services(&clock, &nvs, &config);
+                }
+                Ok(false) => {}
+                Err(e) => log::warn!("Failed to check the Wi-Fi: {e}"),
+            }
+        }
+
+        health::beat();
+        FreeRtos::delay_ms(250);
+    }
+}
+
+/// Starts the services that need the configured network: the group, MQTT and the core
+/// dump upload.
+///
+/// Failures are logged and degrade the network service, they never stop the clock.
+fn start_online_services(
+    clock: &device::Clock<Gpio4, Gpio5>,
+    nvs: &EspDefaultNvsPartition,
+    config: &settings::Settings,
+) {
+    let events = &clock.events;
+
+    // Keep the display and the settings in step with the other clocks of the group
+    if let Err(e) = group_sync::start_group(clock.clone(), nvs.clone()) {
+        log::error!("Failed to start group: {:#?}", e);
+        eprintln!("Failed to start group: {:#?}", e);
+        error::record(&e);
+        degrade_network(events, format!("group failed: {e}"));
+    }
+
+    // Publish the climate readings to the configured MQTT broker
+    if !config.mqtt_url.is_empty() {
+        if let Err(e) = mqtt::start_mqtt(
+            config.mqtt_url.clone(),
+            config.hostname.clone(),
+            events.clone(),
+        ) {
+            log::error!("Failed to start MQTT: {:#?}", e);
+            eprintln!("Failed to start MQTT: {:#?}", e);
+            error::record(&e);
+            degrade_network(events, format!("MQTT failed: {e}"));
+        }
+    }
+
+    // Send the core dump of the last crash, if any, to the configured server
+    if !config.coredump_url.is_empty() {
+        if let Err(e) = crashlog::start_upload(config.coredump_url.clone()) {
+            log::error!("Failed to start core dump upload: {:#?}", e);
+            eprintln!("Failed to start core dump upload: {:#?}", e);
+            error::record(&e);
+        }
+    }
+}
+
+/// Connects to the configured Wi-Fi, falling back to the provisioning access point.
+///
+/// A configured network that cannot be joined is tried again by the main loop, next to
+/// the access point, see [wifi::rejoin]. Failures are logged and published as the state
+/// of the network service, they never stop the boot.
+///
+/// # Returns
+/// `true` if the clock joined a network and can reach SNTP and the other clocks.
+fn start_network(
+    wifi: &mut wifi::Wifi,
+    config: &settings::Settings,
+    events: &events::Events,
+) -> bool {
+    let provisioning = wifi::is_provisioning(config);
+    let state = match wifi::init_wifi(wifi) {
+        Ok(()) if provisioning => {
+            ServiceState::Degraded("no Wi-Fi configured, provisioning access point".to_string())
+        }
+        Ok(()) => ServiceState::Running,
+        Err(e) => {
+            log::error!("Failed to initialize Wi-Fi: {:#?}", e);
+            eprintln!("Failed to initialize Wi-Fi: {:#?}", e);
+            error::record(&e);
+            match wifi::start_access_point(wifi) {
+                Ok(()) => ServiceState::Degraded(format!(
+                    "cannot join `{}`, retrying next to the access point: {e}",
+                    config.wifi_ssid
+                )),
+                Err(e) => {
+                    log::error!("Failed to start access point: {:#?}", e);
+                    eprintln!("Failed to start access point: {:#?}", e);
+                    error::record(&e);
+                    events.update(|s| s.services.network = ServiceState::Failed(e.to_string()));
+                    return false;
+                }
+            }
+        }
+    };
+
+    let online = state == ServiceState::Running;
+    events.update(|s| {
+        s.ssid = if online {
+            config.wifi_ssid.clone()
+        } else {
+            wifi::PROVISIONING_SSID.to_string()
+        };
+        s.services.network = state;
+    });
+
+    online
+}
+
+/// Publishes the state of a service started at boot; a failure is logged and recorded,
+/// and the boot goes on without the service.
+///
+/// # Parameters
+/// - `events`: The [events::Events] hub publishing the state.
+/// - `name`: The name of the service in the logs, e.g. `buzzer`.
+/// - `service`: Selects the state of the service in the [events::Services].
+/// - `result`: What starting the service returned.
+///
+/// # Returns
+/// The started service, or `None` if it failed.
+fn start_service<T>(
+    events: &events::Events,
+    name: &str,
+    service: fn(&mut events::Services) -> &mut ServiceState,
+    result: Result<T, AppError>,
+) -> Option<T> {
+    let (state, started) = match result {
+        Ok(started) => (ServiceState::Running, Some(started)),
+        Err(e) => {
+            log::error!("Failed to start {name}: {:#?}", e);
+            eprintln!("Failed to start {name}: {:#?}", e);
+            error::record(&e);
+            (ServiceState::Failed(e.to_string()), None)
+        }
+    };
+    events.update(|s| *service(&mut s.services) = state);
+
+    started
+}
+
+/// Marks the network as degraded after one of its optional services failed.
+fn degrade_network(events: &events::Events, reason: String) {
+    events.update(|s| {
+        if !s.services.network.is_impaired() {
+            s.services.network = ServiceState::Degraded(reason);
+        }
+    });
+}
+
+/// Starts the HTTP server and registers the routes of the web interface and the API.
+///
+/// # Returns
+/// The running server, to be kept alive, or the [AppError] of the first failing step.
+fn start_http_server(
+    clock: &device::Clock<Gpio4, Gpio5>,
+) -> Result<EspHttpServer<'static>, AppError> {
+    let mut http_server = server::start_server()?;
 
     // Define HTTP routes
     http_server
@@ -64,18 +522,196 @@ fn main() -> Result<(), AppError> {
         })?;
 
     http_server
-        .fn_handler("/get_status", Method::Get, handler::get_status())
+        .fn_handler(
+            "/get_status",
+            Method::Get,
+            handler::get_status(clock.events.clone()),
+        )
         .inspect_err(|&e| {
             log::error!("Failed to register get_status handler: {:#?}", e);
             eprintln!("Failed to register get_status handler: {:#?}", e);
         })?;
 
+    http_server
+        .fn_handler(
+            "/api/v1/status",
+            Method::Get,
+            handler::get_status_json(clock.events.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register get_status_json handler: {:#?}", e);
+            eprintln!("Failed to register get_status_json handler: {:#?}", e);
+        })?;
+
+    http_server
+        .ws_handler(
+            "/api/v1/events",
+            handler::status_events(clock.events.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register status_events handler: {:#?}", e);
+            eprintln!("Failed to register status_events handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/settings",
+            Method::Get,
+            handler::get_settings(clock.settings.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register get_settings handler: {:#?}", e);
+            eprintln!("Failed to register get_settings handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/settings",
+            Method::Post,
+            handler::set_settings(clock.settings.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register set_settings handler: {:#?}", e);
+            eprintln!("Failed to register set_settings handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/buzzer/test",
+            Method::Post,
+            handler::test_buzzer(clock.buzzer.clone(), clock.settings.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register test_buzzer handler: {:#?}", e);
+            eprintln!("Failed to register test_buzzer handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/lamp",
+            Method::Get,
+            handler::get_lamp(clock.settings.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register get_lamp handler: {:#?}", e);
+            eprintln!("Failed to register get_lamp handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/lamp",
+            Method::Post,
+            handler::set_lamp(clock.settings.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register set_lamp handler: {:#?}", e);
+            eprintln!("Failed to register set_lamp handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/countdown",
+            Method::Get,
+            handler::get_events(clock.settings.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register get_events handler: {:#?}", e);
+            eprintln!("Failed to register get_events handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/countdown",
+            Method::Post,
+            handler::set_event(clock.settings.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register set_event handler: {:#?}", e);
+            eprintln!("Failed to register set_event handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/countdown/delete",
+            Method::Post,
+            handler::delete_event(clock.settings.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register delete_event handler: {:#?}", e);
+            eprintln!("Failed to register delete_event handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler("/api/v1/errors", Method::Get, handler::get_errors())
+        .inspect_err(|&e| {
+            log::error!("Failed to register get_errors handler: {:#?}", e);
+            eprintln!("Failed to register get_errors handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler("/api/v1/crashes", Method::Get, handler::get_crashes())
+        .inspect_err(|&e| {
+            log::error!("Failed to register get_crashes handler: {:#?}", e);
+            eprintln!("Failed to register get_crashes handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler("/api/v1/health", Method::Get, handler::get_health())
+        .inspect_err(|&e| {
+            log::error!("Failed to register get_health handler: {:#?}", e);
+            eprintln!("Failed to register get_health handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler("/api/v1/logs", Method::Get, handler::get_logs())
+        .inspect_err(|&e| {
+            log::error!("Failed to register get_logs handler: {:#?}", e);
+            eprintln!("Failed to register get_logs handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/logs/levels",
+            Method::Get,
+            handler::get_log_levels(),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register get_log_levels handler: {:#?}", e);
+            eprintln!("Failed to register get_log_levels handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/logs/levels",
+            Method::Post,
+            handler::set_log_level(clock.settings.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register set_log_level handler: {:#?}", e);
+            eprintln!("Failed to register set_log_level handler: {:#?}", e);
+        })?;
+
+    http_server
+        .fn_handler(
+            "/api/v1/sun",
+            Method::Get,
+            handler::get_sun(clock.settings.clone()),
+        )
+        .inspect_err(|&e| {
+            log::error!("Failed to register get_sun handler: {:#?}", e);
+            eprintln!("Failed to register get_sun handler: {:#?}", e);
+        })?;
+
     unsafe {
         http_server
             .fn_handler_nonstatic(
                 "/set_digits",
                 Method::Get,
-                handler::set_digits(display.clone()),
+                handler::set_digits(
+                    clock.display.clone(),
+                    clock.events.clone(),
+                    clock.settings.clone(),
+                ),
             )
             .inspect_err(|&e| {
                 log::error!("Failed to register set_digits handler: {:#?}", e);
@@ -86,7 +722,7 @@ fn main() -> Result<(), AppError> {
             .fn_handler_nonstatic(
                 "/set_brightness",
                 Method::Get,
-                handler::set_brightness(display.clone()),
+                handler::set_brightness(clock.clone()),
             )
             .inspect_err(|&e| {
                 log::error!("Failed to register set_brightness handler: {:#?}", e);
@@ -95,21 +731,55 @@ fn main() -> Result<(), AppError> {
 
         http_server
             .fn_handler_nonstatic(
-                "/sync_time",
+                "/api/v1/display",
                 Method::Get,
-                handler::sync_time(display.clone(), sntp),
+                handler::get_display(clock.display.clone()),
             )
+            .inspect_err(|&e| {
+                log::error!("Failed to register get_display handler: {:#?}", e);
+                eprintln!("Failed to register get_display handler: {:#?}", e);
+            })?;
+
+        http_server
+            .fn_handler_nonstatic("/sync_time", Method::Get, handler::sync_time(clock.clone()))
             .inspect_err(|&e| {
                 log::error!("Failed to register sync_time handler: {:#?}", e);
                 eprintln!("Failed to register sync_time handler: {:#?}", e);
             })?;
-    }
 
-    loop {
-        display::update_display_time(&display).inspect_err(|e| {
-            log::error!("Failed to update display time: {:#?}", e);
-            eprintln!("Failed to update display time: {:#?}", e);
-        })?;
-        FreeRtos::delay_ms(60000);
+        http_server
+            .fn_handler_nonstatic(
+                "/api/v1/time",
+                Method::Post,
+                handler::set_time(clock.clone()),
+            )
+            .inspect_err(|&e| {
+                log::error!("Failed to register set_time handler: {:#?}", e);
+                eprintln!("Failed to register set_time handler: {:#?}", e);
+            })?;
+
+        http_server
+            .fn_handler_nonstatic(
+                "/api/v1/sunrise/cancel",
+                Method::Post,
+                handler::cancel_sunrise(clock.clone()),
+            )
+            .inspect_err(|&e| {
+                log::error!("Failed to register cancel_sunrise handler: {:#?}", e);
+                eprintln!("Failed to register cancel_sunrise handler: {:#?}", e);
+            })?;
+
+        http_server
+            .fn_handler_nonstatic(
+                "/api/v1/factory_reset",
+                Method::Post,
+                handler::factory_reset(clock.clone()),
+            )
+            .inspect_err(|&e| {
+                log::error!("Failed to register factory_reset handler: {:#?}", e);
+                eprintln!("Failed to register factory_reset handler: {:#?}", e);
+            })?;
     }
+
+    Ok(http_server)
 }
@@ -0,0 +1,58 @@
+use crate::{error::AppError, settings::Settings, utils};
+use esp_idf_svc::mdns::EspMdns;
+
+/// Port on which the web interface is served.
+const HTTP_PORT: u16 = 80;
+
+/// Creates the [EspMdns] responder.
+///
+/// # Returns
+/// - `Ok(EspMdns)`: The successfully created mDNS responder.
+/// - `Err(AppError)`: If the responder is already taken or fails to start.
+///
+/// # Example
+/// ```rust
+/// let mdns = get_mdns().expect("Failed to create mDNS responder");
+/// ```
+pub fn get_mdns() -> Result<EspMdns, AppError> {
+    Ok(EspMdns::take()?)
+}
+
+/// Announces the clock as `<hostname>.local` and advertises the web interface.
+///
+/// Registers an `_http._tcp` service record whose TXT records carry the firmware
+/// version and the model, so the clock can be found by service browsers.
+///
+/// # Parameters
+/// - `mdns`: A mutable reference to the [EspMdns] responder.
+/// - `settings`: The [Settings] holding the hostname to announce.
+///
+/// # Returns
+/// `Ok(())` if the announcement is configured, or an [AppError] if it fails.
+///
+/// # Example
+/// ```rust
+/// let mut mdns = get_mdns().expect("Failed to create mDNS responder");
+/// init_mdns(&mut mdns, &settings).expect("Failed to advertise the web interface");
+/// ```
+pub fn init_mdns(mdns: &mut EspMdns, settings: &Settings) -> Result<(), AppError> {
+    mdns.set_hostname(&settings.hostname)?;
+    mdns.set_instance_name(utils::MODEL_NAME)?;
+    mdns.add_service(
+        None,
+        "_http",
+        "_tcp",
+        HTTP_PORT,
+        &[
+            ("version", utils::FIRMWARE_VERSION),
+            ("model", utils::MODEL),
+        ],
+    )?;
+
+    log::info!(
+        "Advertising web interface at http://{}.local",
+        settings.hostname
+    );
+
+    Ok(())
+}
@@ -0,0 +1,679 @@
+use crate::alarm::Alarm;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use chrono_tz::Tz;
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+/// Time, in milliseconds, without input after which the menu closes.
+pub const MENU_TIMEOUT_MS: u64 = 10_000;
+
+/// Range of the UTC offsets, in hours, selectable from the menu.
+const UTC_OFFSETS: std::ops::RangeInclusive<i8> = -12..=14;
+
+/// Range of the years selectable from the menu.
+const YEARS: std::ops::RangeInclusive<i32> = 2000..=2099;
+
+/// Type alias for the shared [Menu].
+/// This is an ´Arc<Mutex<>>´ to ensure thread safety and shared access to the menu state.
+pub type SettingsMenu = Arc<Mutex<Menu>>;
+
+/// A key press driving the menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// Opens the menu, or closes it from anywhere.
+    Menu,
+    /// Edits the shown item, or confirms the edited value.
+    Select,
+    /// Shows the next item, or increases the edited value.
+    Up,
+    /// Shows the previous item, or decreases the edited value.
+    Down,
+}
+
+/// A setting confirmed in the menu, to be applied by the caller.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Brightness(u8),
+    TwelveHour(bool),
+    /// A fixed offset picked in the menu, replacing the configured zone, such as
+    /// `Europe/Berlin`, and its DST changes.
+    Timezone(Tz),
+    /// The local time of day set by hand, at the top of its minute.
+    Time(NaiveTime),
+    /// The local date set by hand.
+    Date(NaiveDate),
+    /// The new time of an alarm, or `None` to disable it.
+    Alarm {
+        index: usize,
+        time: Option<(u8, u8)>,
+    },
+}
+
+/// The values shown when the menu opens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Values {
+    pub brightness: u8,
+    pub twelve_hour: bool,
+    /// The current offset from UTC, in whole hours, or `None` in a zone off the hour, such
+    /// as India (UTC+5:30), which the menu cannot offer.
+    pub utc_offset: Option<i8>,
+    /// The local date and time, edited by the time and date items.
+    pub now: NaiveDateTime,
+    pub alarms: Vec<Alarm>,
+}
+
+/// A page of the menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Item {
+    Brightness,
+    HourFormat,
+    UtcOffset,
+    Time,
+    Date,
+    Alarm(usize),
+}
+
+/// The part of an item being edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Value,
+    AlarmEnabled,
+    AlarmHour,
+    AlarmMinute,
+    Hour,
+    Minute,
+    Year,
+    Month,
+    Day,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Closed,
+    Browsing(usize),
+    Editing(usize, Field),
+}
+
+/// State machine of the settings menu shown on the 4-digit display.
+///
+/// The menu lists the items by their label (`bri`, `12h`, `tz`, `tInE`, `dAtE`, `AL1`...); selecting
+/// one edits its value, and confirming it returns a [Change]. Nothing is applied by
+/// the menu itself, and the time is passed in, so it runs the same on the host.
+#[derive(Debug)]
+pub struct Menu {
+    state: State,
+    values: Values,
+    /// The UTC offset when the menu opened, confirmed again without a change.
+    opened_utc_offset: Option<i8>,
+    last_input_ms: u64,
+}
+
+impl Default for Menu {
+    fn default() -> Self {
+        Menu {
+            state: State::Closed,
+            values: Values {
+                brightness: 1,
+                twelve_hour: false,
+                utc_offset: Some(0),
+                now: NaiveDateTime::default(),
+                alarms: Vec::new(),
+            },
+            opened_utc_offset: Some(0),
+            last_input_ms: 0,
+        }
+    }
+}
+
+impl Menu {
+    /// Whether the menu is shown.
+    pub fn is_open(&self) -> bool {
+        self.state != State::Closed
+    }
+
+    /// Opens the menu on its first item.
+    ///
+    /// # Parameters
+    /// - `values`: The current [Values] of the settings.
+    /// - `now_ms`: The current time, in milliseconds, starting the timeout.
+    pub fn open(&mut self, values: Values, now_ms: u64) {
+        self.opened_utc_offset = values.utc_offset;
+        self.values = values;
+        self.state = State::Browsing(0);
+        self.last_input_ms = now_ms;
+    }
+
+    /// Closes the menu, dropping any unconfirmed edit.
+    pub fn close(&mut self) {
+        self.state = State::Closed;
+    }
+
+    /// Closes the menu if no key was pressed for [MENU_TIMEOUT_MS].
+    ///
+    /// # Returns
+    /// `true` if the menu was closed by this call.
+    pub fn tick(&mut self, now_ms: u64) -> bool {
+        let expired = now_ms.saturating_sub(self.last_input_ms) >= MENU_TIMEOUT_MS;
+        if self.is_open() && expired {
+            self.close();
+            return true;
+        }
+
+        false
+    }
+
+    /// Handles a key press.
+    ///
+    /// [Key::Menu] must be handled by the caller while the menu is closed, since
+    /// opening it needs the current [Values]; here it only closes the menu.
+    ///
+    /// # Returns
+    /// The [Change] confirmed by this key, if any.
+    pub fn handle(&mut self, key: Key, now_ms: u64) -> Option<Change> {
+        self.last_input_ms = now_ms;
+        let items = self.items();
+
+        match (self.state, key) {
+            (State::Closed, _) => None,
+            (_, Key::Menu) => {
+                self.close();
+                None
+            }
+            (State::Browsing(index), Key::Up) => {
+                self.state = State::Browsing((index + 1) % items.len());
+                None
+            }
+            (State::Browsing(index), Key::Down) => {
+                self.state = State::Browsing((index + items.len() - 1) % items.len());
+                None
+            }
+            (State::Browsing(index), Key::Select) => {
+                let field = match items[index] {
+                    Item::Time => Field::Hour,
+                    Item::Date => Field::Year,
+                    Item::Alarm(_) => Field::AlarmEnabled,
+                    _ => Field::Value,
+                };
+                self.state = State::Editing(index, field);
+                None
+            }
+            (State::Editing(index, field), Key::Up) => {
+                self.adjust(items[index], field, 1);
+                None
+            }
+            (State::Editing(index, field), Key::Down) => {
+                self.adjust(items[index], field, -1);
+                None
+            }
+            (State::Editing(index, field), Key::Select) => self.confirm(index, items[index], field),
+        }
+    }
+
+    /// Returns the text to show, or `None` while the menu is closed.
+    ///
+    /// The edited part of a time or a date blinks, using `now_ms` as the phase.
+    pub fn text(&self, now_ms: u64) -> Option<String> {
+        let items = self.items();
+        let blank = (now_ms / 500) % 2 == 1;
+
+        let text = match self.state {
+            State::Closed => return None,
+            State::Browsing(index) => match items[index] {
+                Item::Brightness => "bri".to_string(),
+                Item::HourFormat => "12h".to_string(),
+                Item::UtcOffset => "tz".to_string(),
+                Item::Time => "tInE".to_string(),
+                Item::Date => "dAtE".to_string(),
+                Item::Alarm(alarm) => format!("AL{}", alarm + 1),
+            },
+            State::Editing(index, field) => match (items[index], field) {
+                (Item::Brightness, _) => format!("bri{}", self.values.brightness),
+                (Item::HourFormat, _) if self.values.twelve_hour => "12h".to_string(),
+                (Item::HourFormat, _) => "24h".to_string(),
+                (Item::UtcOffset, _) => match self.values.utc_offset {
+                    Some(offset) => format!("{:>4}", format!("{offset:+}")),
+                    None => "----".to_string(),
+                },
+                (Item::Time, field) => {
+                    let hour = format!("{:02}", self.values.now.hour());
+                    let minute = format!("{:02}", self.values.now.minute());
+                    match (field, blank) {
+                        (Field::Hour, true) => format!("  :{minute}"),
+                        (Field::Minute, true) => format!("{hour}:  "),
+                        _ => format!("{hour}:{minute}"),
+                    }
+                }
+                (Item::Date, Field::Year) if blank => "    ".to_string(),
+                (Item::Date, Field::Year) => format!("{}", self.values.now.year()),
+                (Item::Date, field) => {
+                    // Day then month, as on the date page
+                    let day = format!("{:02}", self.values.now.day());
+                    let month = format!("{:02}", self.values.now.month());
+                    match (field, blank) {
+                        (Field::Day, true) => format!("  {month}"),
+                        (Field::Month, true) => format!("{day}  "),
+                        _ => format!("{day}{month}"),
+                    }
+                }
+                (Item::Alarm(alarm), Field::AlarmEnabled) if self.values.alarms[alarm].enabled => {
+                    " On".to_string()
+                }
+                (Item::Alarm(_), Field::AlarmEnabled) => "OFF".to_string(),
+                (Item::Alarm(alarm), field) => {
+                    let alarm = self.values.alarms[alarm];
+                    let hour = format!("{:02}", alarm.hour);
+                    let minute = format!("{:02}", alarm.minute);
+                    match (field, blank) {
+                        (Field::AlarmHour, true) => format!("  :{minute}"),
+                        (Field::AlarmMinute, true) => format!("{hour}:  "),
+                        _ => format!("{hour}:{minute}"),
+                    }
+                }
+            },
+        };
+
+        Some(text)
+    }
+
+    /// Lists the items, one per alarm after the fixed ones.
+    fn items(&self) -> Vec<Item> {
+        let mut items = vec![
+            Item::Brightness,
+            Item::HourFormat,
+            Item::UtcOffset,
+            Item::Time,
+            Item::Date,
+        ];
+        items.extend((0..self.values.alarms.len()).map(Item::Alarm));
+
+        items
+    }
+
+    /// Changes the edited value by `step`, wrapping or clamping as the value requires.
+    fn adjust(&mut self, item: Item, field: Field, step: i8) {
+        let values = &mut self.values;
+
+        match (item, field) {
+            (Item::Brightness, _) => {
+                values.brightness = values.brightness.saturating_add_signed(step).clamp(1, 7);
+            }
+            (Item::HourFormat, _) => values.twelve_hour = !values.twelve_hour,
+            (Item::UtcOffset, _) => {
+                if let Some(offset) = &mut values.utc_offset {
+                    *offset = (*offset + step).clamp(*UTC_OFFSETS.start(), *UTC_OFFSETS.end());
+                }
+            }
+            (Item::Time, Field::Hour) => {
+                let hour = (values.now.hour() as i8 + step).rem_euclid(24) as u32;
+                values.now = values.now.with_hour(hour).unwrap_or(values.now);
+            }
+            (Item::Time, _) => {
+                let minute = (values.now.minute() as i8 + step).rem_euclid(60) as u32;
+                values.now = values.now.with_minute(minute).unwrap_or(values.now);
+            }
+            (Item::Date, field) => {
+                let date = values.now.date();
+                let (mut year, mut month, mut day) = (date.year(), date.month(), date.day());
+                match field {
+                    Field::Year => {
+                        year = (year + step as i32).clamp(*YEARS.start(), *YEARS.end());
+                    }
+                    Field::Month => {
+                        month = (month as i32 - 1 + step as i32).rem_euclid(12) as u32 + 1
+                    }
+                    _ => {
+                        let days = days_in_month(year, month) as i32;
+                        day = (day as i32 - 1 + step as i32).rem_euclid(days) as u32 + 1;
+                    }
+                }
+                let day = day.min(days_in_month(year, month));
+                if let Some(date) = NaiveDate::from_ymd_opt(year, month, day) {
+                    values.now = date.and_time(values.now.time());
+                }
+            }
+            (Item::Alarm(index), Field::AlarmEnabled) => {
+                values.alarms[index].enabled = !values.alarms[index].enabled;
+            }
+            (Item::Alarm(index), Field::AlarmHour) => {
+                let alarm = &mut values.alarms[index];
+                alarm.hour = (alarm.hour as i8 + step).rem_euclid(24) as u8;
+            }
+            (Item::Alarm(index), _) => {
+                let alarm = &mut values.alarms[index];
+                alarm.minute = (alarm.minute as i8 + step).rem_euclid(60) as u8;
+            }
+        }
+    }
+
+    /// Confirms the edited field, moving to the next one or returning the [Change].
+    fn confirm(&mut self, index: usize, item: Item, field: Field) -> Option<Change> {
+        let values = &self.values;
+
+        let change = match (item, field) {
+            (Item::Brightness, _) => Change::Brightness(values.brightness),
+            (Item::HourFormat, _) => Change::TwelveHour(values.twelve_hour),
+            // An offset replaces the configured zone and its DST, so it is only applied
+            // when changed
+            (Item::UtcOffset, _) => match values.utc_offset {
+                Some(offset) if values.utc_offset != self.opened_utc_offset => {
+                    Change::Timezone(offset_timezone(offset))
+                }
+                _ => {
+                    self.state = State::Browsing(index);
+                    return None;
+                }
+            },
+            (Item::Time, Field::Hour) => {
+                self.state = State::Editing(index, Field::Minute);
+                return None;
+            }
+            (Item::Time, _) => {
+                let time = NaiveTime::from_hms_opt(values.now.hour(), values.now.minute(), 0);
+                Change::Time(time.unwrap_or_default())
+            }
+            (Item::Date, Field::Year) => {
+                self.state = State::Editing(index, Field::Month);
+                return None;
+            }
+            (Item::Date, Field::Month) => {
+                self.state = State::Editing(index, Field::Day);
+                return None;
+            }
+            (Item::Date, _) => Change::Date(values.now.date()),
+            (Item::Alarm(alarm), Field::AlarmEnabled) if values.alarms[alarm].enabled => {
+                self.state = State::Editing(index, Field::AlarmHour);
+                return None;
+            }
+            (Item::Alarm(_), Field::AlarmHour) => {
+                self.state = State::Editing(index, Field::AlarmMinute);
+                return None;
+            }
+            (Item::Alarm(alarm), Field::AlarmEnabled) => Change::Alarm {
+                index: alarm,
+                time: None,
+            },
+            (Item::Alarm(alarm), _) => Change::Alarm {
+                index: alarm,
+                time: Some((values.alarms[alarm].hour, values.alarms[alarm].minute)),
+            },
+        };
+
+        self.state = State::Browsing(index);
+        Some(change)
+    }
+}
+
+/// Returns the fixed-offset timezone `hours` ahead of UTC.
+///
+/// The `Etc/GMT` zones use the POSIX sign convention, so `Etc/GMT-3` is UTC+3.
+pub fn offset_timezone(hours: i8) -> Tz {
+    Tz::from_str(&format!("Etc/GMT{:+}", -hours)).unwrap_or(Tz::UTC)
+}
+
+/// Returns the number of days in a month of the proleptic Gregorian calendar.
+fn days_in_month(year: i32, month: u32) -> u32 {
+    (28..=31)
+        .rev()
+        .find(|&day| NaiveDate::from_ymd_opt(year, month, day).is_some())
+        .unwrap_or(28)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Values {
+        Values {
+            brightness: 4,
+            twelve_hour: false,
+            utc_offset: Some(1),
+            now: NaiveDate::from_ymd_opt(2026, 1, 31)
+                .unwrap()
+                .and_hms_opt(23, 59, 30)
+                .unwrap(),
+            alarms: vec![
+                Alarm::default(),
+                Alarm {
+                    enabled: true,
+                    ..Alarm::default()
+                },
+            ],
+        }
+    }
+
+    fn open_menu() -> Menu {
+        let mut menu = Menu::default();
+        menu.open(values(), 0);
+        menu
+    }
+
+    /// Presses the keys one after the other, returning the last [Change].
+    fn press(menu: &mut Menu, keys: &[Key]) -> Option<Change> {
+        keys.iter().fold(None, |_, &key| menu.handle(key, 0))
+    }
+
+    #[test]
+    fn browses_the_items_and_wraps() {
+        let mut menu = open_menu();
+        assert!(menu.is_open());
+        assert_eq!(menu.text(0).as_deref(), Some("bri"));
+
+        let labels: Vec<_> = (0..7)
+            .map(|_| {
+                menu.handle(Key::Up, 0);
+                menu.text(0).unwrap()
+            })
+            .collect();
+        assert_eq!(labels, ["12h", "tz", "tInE", "dAtE", "AL1", "AL2", "bri"]);
+
+        menu.handle(Key::Down, 0);
+        assert_eq!(menu.text(0).as_deref(), Some("AL2"));
+    }
+
+    #[test]
+    fn closed_menu_ignores_keys() {
+        let mut menu = Menu::default();
+        assert_eq!(menu.handle(Key::Select, 0), None);
+        assert_eq!(menu.handle(Key::Up, 0), None);
+        assert!(!menu.is_open());
+        assert_eq!(menu.text(0), None);
+    }
+
+    #[test]
+    fn menu_key_closes_and_drops_the_edit() {
+        let mut menu = open_menu();
+        assert_eq!(press(&mut menu, &[Key::Select, Key::Up, Key::Menu]), None);
+        assert!(!menu.is_open());
+    }
+
+    #[test]
+    fn brightness_is_clamped() {
+        let mut menu = open_menu();
+        let mut keys = vec![Key::Select];
+        keys.extend([Key::Up; 10]);
+        keys.push(Key::Select);
+        assert_eq!(press(&mut menu, &keys), Some(Change::Brightness(7)));
+
+        let mut keys = vec![Key::Select];
+        keys.extend([Key::Down; 10]);
+        keys.push(Key::Select);
+        assert_eq!(press(&mut menu, &keys), Some(Change::Brightness(1)));
+
+        // Back to browsing the same item after confirming
+        assert_eq!(menu.text(0).as_deref(), Some("bri"));
+    }
+
+    #[test]
+    fn hour_format_toggles() {
+        let mut menu = open_menu();
+        assert_eq!(
+            press(&mut menu, &[Key::Up, Key::Select, Key::Down, Key::Select]),
+            Some(Change::TwelveHour(true))
+        );
+    }
+
+    #[test]
+    fn utc_offset_is_clamped() {
+        let mut menu = open_menu();
+        let mut keys = vec![Key::Up, Key::Up, Key::Select];
+        keys.extend([Key::Up; 20]);
+        assert_eq!(press(&mut menu, &keys), None);
+        assert_eq!(menu.text(0).as_deref(), Some(" +14"));
+        assert_eq!(
+            menu.handle(Key::Select, 0),
+            Some(Change::Timezone(offset_timezone(14)))
+        );
+        assert_eq!(offset_timezone(14), Tz::Etc__GMTMinus14);
+        assert_eq!(offset_timezone(-12), Tz::Etc__GMTPlus12);
+    }
+
+    #[test]
+    fn unchanged_utc_offset_keeps_the_timezone() {
+        let mut menu = open_menu();
+        assert_eq!(press(&mut menu, &[Key::Up, Key::Up, Key::Select]), None);
+        assert_eq!(menu.text(0).as_deref(), Some("  +1"));
+        assert_eq!(menu.handle(Key::Select, 0), None);
+        assert_eq!(menu.text(0).as_deref(), Some("tz"));
+
+        // Back to the opening value after a change is no change either
+        assert_eq!(
+            press(&mut menu, &[Key::Select, Key::Up, Key::Down, Key::Select]),
+            None
+        );
+    }
+
+    #[test]
+    fn utc_offset_off_the_hour_is_not_offered() {
+        let mut menu = Menu::default();
+        menu.open(
+            Values {
+                utc_offset: None,
+                ..values()
+            },
+            0,
+        );
+        let keys = [Key::Up, Key::Up, Key::Select, Key::Up, Key::Up];
+        assert_eq!(press(&mut menu, &keys), None);
+        assert_eq!(menu.text(0).as_deref(), Some("----"));
+        assert_eq!(menu.handle(Key::Select, 0), None);
+        assert_eq!(menu.text(0).as_deref(), Some("tz"));
+    }
+
+    #[test]
+    fn time_wraps_hour_and_minute() {
+        let mut menu = open_menu();
+        // 23:59 becomes 00:00, the seconds are dropped
+        let keys = [
+            Key::Up,
+            Key::Up,
+            Key::Up,
+            Key::Select,
+            Key::Up,
+            Key::Select,
+            Key::Up,
+            Key::Select,
+        ];
+        assert_eq!(
+            press(&mut menu, &keys),
+            Some(Change::Time(NaiveTime::from_hms_opt(0, 0, 0).unwrap()))
+        );
+    }
+
+    #[test]
+    fn date_keeps_the_day_in_the_month() {
+        let mut menu = open_menu();
+        // 2026-01-31, one month up is February, which has 28 days
+        let keys = [
+            Key::Down,
+            Key::Down,
+            Key::Down,
+            Key::Select,
+            Key::Select,
+            Key::Up,
+            Key::Select,
+            Key::Select,
+        ];
+        assert_eq!(
+            press(&mut menu, &keys),
+            Some(Change::Date(NaiveDate::from_ymd_opt(2026, 2, 28).unwrap()))
+        );
+    }
+
+    #[test]
+    fn date_wraps_day_and_clamps_year() {
+        let mut menu = open_menu();
+        let mut keys = vec![Key::Down, Key::Down, Key::Down, Key::Select];
+        keys.extend([Key::Up; 100]);
+        keys.extend([Key::Select, Key::Select, Key::Up, Key::Select]);
+        // The year stops at 2099 and day 31 wraps to 1
+        assert_eq!(
+            press(&mut menu, &keys),
+            Some(Change::Date(NaiveDate::from_ymd_opt(2099, 1, 1).unwrap()))
+        );
+    }
+
+    #[test]
+    fn alarm_is_enabled_and_set_field_by_field() {
+        let mut menu = open_menu();
+        // AL1, disabled at 07:00: enable it, then one hour down and one minute down
+        let keys = [Key::Down, Key::Down, Key::Select, Key::Up];
+        assert_eq!(press(&mut menu, &keys), None);
+        assert_eq!(menu.text(0).as_deref(), Some(" On"));
+
+        let keys = [Key::Select, Key::Down, Key::Select, Key::Down];
+        assert_eq!(press(&mut menu, &keys), None);
+        assert_eq!(menu.text(0).as_deref(), Some("06:59"));
+        assert_eq!(
+            menu.handle(Key::Select, 0),
+            Some(Change::Alarm {
+                index: 0,
+                time: Some((6, 59))
+            })
+        );
+    }
+
+    #[test]
+    fn alarm_is_disabled() {
+        let mut menu = open_menu();
+        // AL2, enabled: switching it off confirms right away
+        let keys = [Key::Down, Key::Select, Key::Up];
+        assert_eq!(press(&mut menu, &keys), None);
+        assert_eq!(menu.text(0).as_deref(), Some("OFF"));
+        assert_eq!(
+            menu.handle(Key::Select, 0),
+            Some(Change::Alarm {
+                index: 1,
+                time: None
+            })
+        );
+    }
+
+    #[test]
+    fn edited_field_blinks() {
+        let mut menu = open_menu();
+        press(&mut menu, &[Key::Up, Key::Up, Key::Up, Key::Select]);
+        assert_eq!(menu.text(0).as_deref(), Some("23:59"));
+        assert_eq!(menu.text(500).as_deref(), Some("  :59"));
+    }
+
+    #[test]
+    fn closes_after_the_timeout() {
+        let mut menu = open_menu();
+        menu.handle(Key::Up, 5_000);
+        assert!(!menu.tick(5_000 + MENU_TIMEOUT_MS - 1));
+        assert!(menu.is_open());
+        assert!(menu.tick(5_000 + MENU_TIMEOUT_MS));
+        assert!(!menu.is_open());
+        assert!(!menu.tick(100_000));
+    }
+
+    #[test]
+    fn counts_days_in_month() {
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert_eq!(days_in_month(2026, 4), 30);
+        assert_eq!(days_in_month(2026, 12), 31);
+    }
+}
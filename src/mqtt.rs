@@ -0,0 +1,77 @@
+use crate::{error::AppError, events::Events, health};
+use esp_idf_svc::{
+    hal::delay::FreeRtos,
+    mqtt::client::{EspMqttClient, EventPayload, MqttClientConfiguration, QoS},
+};
+use std::thread;
+
+/// Time, in milliseconds, between two climate messages, the sampling interval of the sensor.
+const PUBLISH_INTERVAL_MS: u32 = 30_000;
+
+/// Stack size of the thread publishing the readings, they are serialized as JSON.
+const MQTT_STACK_SIZE: usize = 6144;
+
+/// Starts the thread publishing the climate readings to an MQTT broker.
+///
+/// The last [crate::climate::Reading] of the status, as in `/api/v1/status`, is
+/// published every [PUBLISH_INTERVAL_MS] to the retained topic `<hostname>/climate`,
+/// e.g. `{"temperature":23.4,"unit":"celsius","humidity":45.0,"pressure":1013.2}`.
+/// Nothing is sent while no sensor answers. The client reconnects on its own after the
+/// broker or the Wi-Fi drop.
+///
+/// # Parameters
+/// - `url`: The address of the broker, from the `mqtt_url` setting.
+/// - `hostname`: The hostname of the clock, used as client id and topic prefix.
+/// - `events`: The [Events] hub holding the readings.
+///
+/// # Returns
+/// `Ok(())` once the thread is running, or an [AppError] if the client cannot be
+/// created or the thread cannot be spawned.
+///
+/// # Example
+/// ```rust
+/// start_mqtt(config.mqtt_url.clone(), config.hostname.clone(), events.clone())?;
+/// ```
+pub fn start_mqtt(url: String, hostname: String, events: Events) -> Result<(), AppError> {
+    let config = MqttClientConfiguration {
+        client_id: Some(&hostname),
+        ..Default::default()
+    };
+    let mut client = EspMqttClient::new_cb(&url, &config, |event| match event.payload() {
+        EventPayload::Connected(_) => log::info!("MQTT connected"),
+        EventPayload::Disconnected => log::warn!("MQTT disconnected"),
+        EventPayload::Error(e) => log::warn!("MQTT error: {:?}", e),
+        _ => {}
+    })?;
+    let topic = format!("{hostname}/climate");
+
+    thread::Builder::new()
+        .name("mqtt".to_string())
+        .stack_size(MQTT_STACK_SIZE)
+        .spawn(move || {
+            let _heartbeat = health::track();
+            loop {
+                FreeRtos::delay_ms(PUBLISH_INTERVAL_MS);
+                health::beat();
+
+                let Some(reading) = events.status().climate else {
+                    continue;
+                };
+                let payload = match serde_json::to_vec(&reading) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        log::error!("Failed to serialize climate reading: {:#?}", e);
+                        continue;
+                    }
+                };
+                if let Err(e) = client.enqueue(&topic, QoS::AtMostOnce, true, &payload) {
+                    log::warn!("Failed to publish climate reading: {e}");
+                }
+            }
+        })
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    log::info!("Publishing the climate to `{url}`");
+
+    Ok(())
+}
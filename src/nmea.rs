@@ -0,0 +1,456 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use std::{fmt, mem};
+
+/// Longest sentence allowed by NMEA 0183, from the `$` to the line ending.
+pub const MAX_SENTENCE_LENGTH: usize = 82;
+
+/// Errors returned when a line cannot be decoded into a [Fix].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NmeaError {
+    /// The line does not start with `$` or does not end with a `*hh` checksum.
+    Framing,
+    /// The checksum does not match the sentence.
+    Checksum,
+    /// The sentence is valid but not one carrying the time, e.g. `$GPGSV`.
+    Unsupported,
+    /// A field of the sentence is missing or invalid.
+    Field,
+    /// The receiver has no fix yet, so its time cannot be trusted.
+    NoFix,
+}
+
+impl fmt::Display for NmeaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NmeaError::Framing => write!(f, "not an NMEA sentence"),
+            NmeaError::Checksum => write!(f, "checksum mismatch"),
+            NmeaError::Unsupported => write!(f, "unsupported sentence"),
+            NmeaError::Field => write!(f, "invalid field"),
+            NmeaError::NoFix => write!(f, "no fix"),
+        }
+    }
+}
+
+/// The sentences the time is read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sentence {
+    /// Recommended minimum data, e.g. `$GPRMC`, only used once the receiver has a fix.
+    Rmc,
+    /// Time and date, e.g. `$GPZDA`.
+    Zda,
+}
+
+/// A UTC time decoded from a sentence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fix {
+    pub sentence: Sentence,
+    pub utc: NaiveDateTime,
+}
+
+/// Computes the checksum of a sentence: the XOR of every byte between `$` and `*`.
+pub fn checksum(body: &[u8]) -> u8 {
+    body.iter().fold(0, |checksum, byte| checksum ^ byte)
+}
+
+/// Parses a field made only of decimal digits, unlike [str::parse] which accepts a sign.
+fn digits(field: &str) -> Option<u32> {
+    if field.is_empty() || field.len() > 9 || !field.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    field.parse().ok()
+}
+
+/// Parses a `hhmmss` or `hhmmss.sss` time field.
+fn parse_time(field: &str) -> Option<NaiveTime> {
+    let (whole, fraction) = field.split_once('.').unwrap_or((field, ""));
+    if whole.len() != 6 || fraction.len() > 9 {
+        return None;
+    }
+
+    let hour = digits(whole.get(0..2)?)?;
+    let minute = digits(whole.get(2..4)?)?;
+    let second = digits(whole.get(4..6)?)?;
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        digits(fraction)? * 10u32.pow(9 - fraction.len() as u32)
+    };
+
+    NaiveTime::from_hms_nano_opt(hour, minute, second, nanos)
+}
+
+/// Parses the fields of an RMC sentence, after the address.
+fn parse_rmc<'a>(mut fields: impl Iterator<Item = &'a str>) -> Result<NaiveDateTime, NmeaError> {
+    let time = fields.next().ok_or(NmeaError::Field)?;
+    let status = fields.next().ok_or(NmeaError::Field)?;
+    // Latitude, longitude, speed and course are not needed
+    let date = fields.nth(6).ok_or(NmeaError::Field)?;
+    // NMEA 2.3 adds a mode after the magnetic variation, `N` when the data is not valid
+    let mode = fields.nth(2);
+
+    if status != "A" || mode == Some("N") {
+        return Err(NmeaError::NoFix);
+    }
+
+    let time = parse_time(time).ok_or(NmeaError::Field)?;
+    if date.len() != 6 {
+        return Err(NmeaError::Field);
+    }
+    let day = date.get(0..2).and_then(digits).ok_or(NmeaError::Field)?;
+    let month = date.get(2..4).and_then(digits).ok_or(NmeaError::Field)?;
+    let year = date.get(4..6).and_then(digits).ok_or(NmeaError::Field)?;
+    let date = NaiveDate::from_ymd_opt(2000 + year as i32, month, day).ok_or(NmeaError::Field)?;
+
+    Ok(date.and_time(time))
+}
+
+/// Parses the fields of a ZDA sentence, after the address.
+fn parse_zda<'a>(mut fields: impl Iterator<Item = &'a str>) -> Result<NaiveDateTime, NmeaError> {
+    let mut next = || fields.next().ok_or(NmeaError::Field);
+    let time = parse_time(next()?).ok_or(NmeaError::Field)?;
+    let day = digits(next()?).ok_or(NmeaError::Field)?;
+    let month = digits(next()?).ok_or(NmeaError::Field)?;
+    let year = next()?;
+    if year.len() != 4 {
+        return Err(NmeaError::Field);
+    }
+    let year = digits(year).ok_or(NmeaError::Field)?;
+    let date = NaiveDate::from_ymd_opt(year as i32, month, day).ok_or(NmeaError::Field)?;
+
+    Ok(date.and_time(time))
+}
+
+/// Parses a line received from a GPS receiver.
+///
+/// RMC and ZDA sentences from any talker (`GP`, `GN`, `GL`, ...) are decoded, with a
+/// mandatory checksum. The function never panics, whatever the bytes received, so it can
+/// be fed the raw serial line and fuzzed.
+///
+/// # Parameters
+/// - `line`: The sentence, from the `$`, with or without the line ending.
+///
+/// # Returns
+/// The [Fix] carried by the sentence, or the [NmeaError] explaining why there is none.
+///
+/// # Example
+/// ```rust
+/// let fix = parse(b"$GPZDA,201530.00,04,07,2002,00,00*60")?;
+/// ```
+pub fn parse(line: &[u8]) -> Result<Fix, NmeaError> {
+    let mut line = line;
+    while let [rest @ .., b'\r' | b'\n'] = line {
+        line = rest;
+    }
+    if line.len() > MAX_SENTENCE_LENGTH {
+        return Err(NmeaError::Framing);
+    }
+
+    let line = std::str::from_utf8(line).map_err(|_| NmeaError::Framing)?;
+    let sentence = line.strip_prefix('$').ok_or(NmeaError::Framing)?;
+    let (body, expected) = sentence.split_once('*').ok_or(NmeaError::Framing)?;
+    if expected.len() != 2 || !expected.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(NmeaError::Framing);
+    }
+    let expected = u8::from_str_radix(expected, 16).map_err(|_| NmeaError::Framing)?;
+    if checksum(body.as_bytes()) != expected {
+        return Err(NmeaError::Checksum);
+    }
+
+    let mut fields = body.split(',');
+    let address = fields.next().unwrap_or_default();
+    if address.len() != 5 || !address.is_ascii() {
+        return Err(NmeaError::Unsupported);
+    }
+
+    match address.get(2..) {
+        Some("RMC") => Ok(Fix {
+            sentence: Sentence::Rmc,
+            utc: parse_rmc(fields)?,
+        }),
+        Some("ZDA") => Ok(Fix {
+            sentence: Sentence::Zda,
+            utc: parse_zda(fields)?,
+        }),
+        _ => Err(NmeaError::Unsupported),
+    }
+}
+
+/// Splits the bytes received from the receiver into sentences.
+///
+/// A `$` always starts a new sentence, so the buffer resynchronizes after noise or a
+/// lost line ending, and lines longer than [MAX_SENTENCE_LENGTH] are dropped.
+#[derive(Debug, Default)]
+pub struct SentenceBuffer {
+    line: Vec<u8>,
+    overflowed: bool,
+}
+
+impl SentenceBuffer {
+    /// Appends a received byte.
+    ///
+    /// # Returns
+    /// The completed line when `byte` ends it, `None` otherwise.
+    pub fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        match byte {
+            b'$' => {
+                self.line.clear();
+                self.overflowed = false;
+                self.line.push(byte);
+                None
+            }
+            b'\n' => {
+                let line = mem::take(&mut self.line);
+                let overflowed = mem::take(&mut self.overflowed);
+                (!overflowed && !line.is_empty()).then_some(line)
+            }
+            _ if self.line.len() >= MAX_SENTENCE_LENGTH => {
+                self.overflowed = true;
+                None
+            }
+            _ => {
+                self.line.push(byte);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames `body` as a sentence, with its checksum and line ending.
+    fn sentence(body: &str) -> Vec<u8> {
+        format!("${body}*{:02X}\r\n", checksum(body.as_bytes())).into_bytes()
+    }
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, s)
+            .unwrap()
+    }
+
+    /// Feeds bytes to a buffer, returning the completed lines.
+    fn feed(buffer: &mut SentenceBuffer, bytes: &[u8]) -> Vec<Vec<u8>> {
+        bytes.iter().filter_map(|&b| buffer.push(b)).collect()
+    }
+
+    #[test]
+    fn parses_rmc() {
+        let line = sentence("GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230324,003.1,W");
+        let fix = parse(&line).unwrap();
+        assert_eq!(fix.sentence, Sentence::Rmc);
+        assert_eq!(fix.utc, utc(2024, 3, 23, 12, 35, 19));
+    }
+
+    #[test]
+    fn parses_rmc_with_mode_and_fraction() {
+        let line = sentence("GNRMC,083559.25,A,4717.11437,N,00833.91522,E,0.004,77.52,091202,,,A");
+        let fix = parse(&line).unwrap();
+        assert_eq!(
+            fix.utc,
+            utc(2002, 12, 9, 8, 35, 59) + chrono::TimeDelta::milliseconds(250)
+        );
+    }
+
+    #[test]
+    fn parses_zda() {
+        let fix = parse(b"$GPZDA,201530.00,04,07,2002,00,00*60").unwrap();
+        assert_eq!(fix.sentence, Sentence::Zda);
+        assert_eq!(fix.utc, utc(2002, 7, 4, 20, 15, 30));
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        assert_eq!(
+            parse(b"$GPZDA,201530.00,04,07,2002,00,00*61"),
+            Err(NmeaError::Checksum)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_framing() {
+        assert_eq!(parse(b""), Err(NmeaError::Framing));
+        assert_eq!(
+            parse(b"GPZDA,201530.00,04,07,2002,00,00*60"),
+            Err(NmeaError::Framing)
+        );
+        assert_eq!(
+            parse(b"$GPZDA,201530.00,04,07,2002,00,00"),
+            Err(NmeaError::Framing)
+        );
+        assert_eq!(
+            parse(b"$GPZDA,201530.00,04,07,2002,00,00*6G"),
+            Err(NmeaError::Framing)
+        );
+        assert_eq!(
+            parse(b"$GPZDA,201530.00,04,07,2002,00,00*600"),
+            Err(NmeaError::Framing)
+        );
+    }
+
+    #[test]
+    fn rejects_rmc_without_fix() {
+        let void = sentence("GPRMC,123519,V,,,,,,,230394,,");
+        assert_eq!(parse(&void), Err(NmeaError::NoFix));
+
+        let not_valid =
+            sentence("GNRMC,083559.00,A,4717.11437,N,00833.91522,E,0.004,77.52,091202,,,N");
+        assert_eq!(parse(&not_valid), Err(NmeaError::NoFix));
+    }
+
+    #[test]
+    fn rejects_truncated_fields() {
+        assert_eq!(
+            parse(&sentence("GPRMC,123519,A,4807.038,N")),
+            Err(NmeaError::Field)
+        );
+        assert_eq!(
+            parse(&sentence("GPZDA,201530.00,04,07")),
+            Err(NmeaError::Field)
+        );
+        assert_eq!(
+            parse(&sentence("GPZDA,2015,04,07,2002")),
+            Err(NmeaError::Field)
+        );
+        assert_eq!(
+            parse(&sentence("GPZDA,201530,04,07,02")),
+            Err(NmeaError::Field)
+        );
+        assert_eq!(
+            parse(&sentence("GPZDA,201530,+4,07,2002")),
+            Err(NmeaError::Field)
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        assert_eq!(
+            parse(&sentence("GPZDA,246030,04,07,2002")),
+            Err(NmeaError::Field)
+        );
+        assert_eq!(
+            parse(&sentence("GPZDA,201530,31,02,2002")),
+            Err(NmeaError::Field)
+        );
+        assert_eq!(
+            parse(&sentence("GPRMC,123519,A,,,,,,,231394,,")),
+            Err(NmeaError::Field)
+        );
+    }
+
+    #[test]
+    fn rejects_other_sentences() {
+        assert_eq!(
+            parse(&sentence("GPGSV,1,1,00")),
+            Err(NmeaError::Unsupported)
+        );
+        assert_eq!(
+            parse(&sentence("ZDA,201530,04,07,2002")),
+            Err(NmeaError::Unsupported)
+        );
+    }
+
+    #[test]
+    fn rejects_non_utf8() {
+        assert_eq!(parse(b"$GP\xff\xfeA,1*00"), Err(NmeaError::Framing));
+    }
+
+    #[test]
+    fn rejects_too_long_lines() {
+        let body = format!("GPZDA,201530.00,04,07,2002,00,00{}", ",".repeat(60));
+        assert_eq!(parse(&sentence(&body)), Err(NmeaError::Framing));
+    }
+
+    #[test]
+    fn buffer_splits_lines() {
+        let mut buffer = SentenceBuffer::default();
+        let mut bytes = sentence("GPZDA,201530.00,04,07,2002,00,00");
+        bytes.extend(sentence("GPGSV,1,1,00"));
+
+        let lines = feed(&mut buffer, &bytes);
+        assert_eq!(lines.len(), 2);
+        assert!(parse(&lines[0]).is_ok());
+        assert_eq!(parse(&lines[1]), Err(NmeaError::Unsupported));
+    }
+
+    #[test]
+    fn buffer_resyncs_on_dollar() {
+        let mut buffer = SentenceBuffer::default();
+        let mut bytes = b"noise$GPZ".to_vec();
+        bytes.extend(sentence("GPZDA,201530.00,04,07,2002,00,00"));
+
+        let lines = feed(&mut buffer, &bytes);
+        assert_eq!(lines.len(), 1);
+        assert!(parse(&lines[0]).is_ok());
+    }
+
+    #[test]
+    fn buffer_drops_overflowing_lines() {
+        let mut buffer = SentenceBuffer::default();
+        let mut bytes = b"$GP".to_vec();
+        bytes.extend([b'x'; 2 * MAX_SENTENCE_LENGTH]);
+        bytes.push(b'\n');
+        assert!(feed(&mut buffer, &bytes).is_empty());
+
+        // The next sentence is received whole again
+        let lines = feed(&mut buffer, &sentence("GPZDA,201530.00,04,07,2002,00,00"));
+        assert_eq!(lines.len(), 1);
+        assert!(parse(&lines[0]).is_ok());
+    }
+
+    /// A small xorshift generator, so the fuzz test needs no dependency and is repeatable.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    #[test]
+    fn never_panics() {
+        let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+        let valid = [
+            sentence("GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230324,003.1,W"),
+            sentence("GPZDA,201530.00,04,07,2002,00,00"),
+        ];
+        let alphabet = b"$*,.0123456789ABCDEFGNPRZMV\r\n\xff";
+        let mut buffer = SentenceBuffer::default();
+
+        for i in 0..20_000 {
+            let bytes: Vec<u8> = if i % 2 == 1 {
+                // Random bytes drawn mostly from the characters of a sentence
+                let len = (rng.next() % 100) as usize;
+                (0..len)
+                    .map(|_| alphabet[(rng.next() % alphabet.len() as u64) as usize])
+                    .collect()
+            } else {
+                // A valid sentence with a few bytes changed, inserted or removed
+                let mut bytes = valid[(rng.next() % 2) as usize].clone();
+                for _ in 0..=(rng.next() % 3) {
+                    let at = (rng.next() % bytes.len() as u64) as usize;
+                    match rng.next() % 3 {
+                        0 => bytes[at] = rng.next() as u8,
+                        1 => bytes.insert(at, rng.next() as u8),
+                        _ => {
+                            bytes.remove(at);
+                        }
+                    }
+                }
+                bytes
+            };
+
+            let _ = parse(&bytes);
+            for line in feed(&mut buffer, &bytes) {
+                assert!(line.len() <= MAX_SENTENCE_LENGTH);
+                let _ = parse(&line);
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// UDP port of the NTP protocol.
+pub const NTP_PORT: u16 = 123;
+
+/// Size of an NTP packet without extensions nor authentication.
+pub const PACKET_SIZE: usize = 48;
+
+/// Stratum reported while the clock is not synchronized, as defined by NTPv4.
+pub const STRATUM_UNSYNCHRONIZED: u8 = 16;
+
+/// Leap indicator reported while the clock is not synchronized.
+const LEAP_UNSYNCHRONIZED: u8 = 3;
+
+/// Seconds from the NTP epoch, 1900-01-01, to the Unix epoch.
+const UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// Mode of a client request.
+const MODE_CLIENT: u8 = 3;
+
+/// Mode of a server reply.
+const MODE_SERVER: u8 = 4;
+
+/// Precision of the system clock, as a power of two in seconds: about a microsecond.
+const PRECISION: i8 = -20;
+
+/// The state of the local clock announced to the clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerClock {
+    /// Whether the clock follows an upstream source, see [STRATUM_UNSYNCHRONIZED].
+    pub synchronized: bool,
+    /// Stratum of the clock: `1` for a reference clock, one more than the upstream server otherwise.
+    pub stratum: u8,
+    /// Four ASCII characters naming a reference clock, e.g. `GPS`, or the upstream address.
+    pub reference_id: [u8; 4],
+    /// When the clock was last set from its source.
+    pub reference_time: Option<DateTime<Utc>>,
+    /// Maximum error relative to the source, in milliseconds.
+    pub dispersion_ms: u32,
+}
+
+/// Converts a time to an NTP timestamp: seconds since 1900 in the upper 32 bits, the
+/// fraction of a second in the lower 32 bits.
+pub fn to_timestamp(time: DateTime<Utc>) -> u64 {
+    let seconds = (time.timestamp() + UNIX_OFFSET) as u64;
+    let fraction = (u64::from(time.timestamp_subsec_nanos()) << 32) / 1_000_000_000;
+
+    seconds << 32 | fraction
+}
+
+/// Converts an NTP timestamp back to a time, assuming the era from 1900 to 2036.
+pub fn from_timestamp(timestamp: u64) -> DateTime<Utc> {
+    let seconds = (timestamp >> 32) as i64 - UNIX_OFFSET;
+    let nanos = ((timestamp & 0xffff_ffff) * 1_000_000_000) >> 32;
+
+    DateTime::UNIX_EPOCH + TimeDelta::seconds(seconds) + TimeDelta::nanoseconds(nanos as i64)
+}
+
+/// Encodes a duration in milliseconds as an NTP short format: 16.16 bits of seconds.
+fn to_short(ms: u32) -> u32 {
+    ((u64::from(ms) << 16) / 1000).min(u64::from(u32::MAX)) as u32
+}
+
+/// Builds the reply of an SNTPv4 server to a client request.
+///
+/// The version and poll interval of the request are echoed, its transmit timestamp is
+/// returned as the originate timestamp, and the leap indicator is set to `3` with the
+/// stratum [STRATUM_UNSYNCHRONIZED] while the clock is not synchronized, so clients
+/// ignore it. The function never panics, whatever the bytes received.
+///
+/// # Parameters
+/// - `request`: The datagram received from the client.
+/// - `clock`: The state of the [ServerClock].
+/// - `received`: When the request was received.
+/// - `transmit`: When the reply is sent.
+///
+/// # Returns
+/// The reply, or `None` if the request is not an NTP client request.
+///
+/// # Example
+/// ```rust
+/// let received = Utc::now();
+/// if let Some(reply) = reply(&buffer[..size], &clock, received, Utc::now()) {
+///     socket.send_to(&reply, client)?;
+/// }
+/// ```
+pub fn reply(
+    request: &[u8],
+    clock: &ServerClock,
+    received: DateTime<Utc>,
+    transmit: DateTime<Utc>,
+) -> Option<[u8; PACKET_SIZE]> {
+    let request: &[u8; PACKET_SIZE] = request.get(..PACKET_SIZE)?.try_into().ok()?;
+    let version = request[0] >> 3 & 0b111;
+    let mode = request[0] & 0b111;
+    if mode != MODE_CLIENT || !(1..=4).contains(&version) {
+        return None;
+    }
+
+    let (leap, stratum) = if clock.synchronized {
+        (0, clock.stratum)
+    } else {
+        (LEAP_UNSYNCHRONIZED, STRATUM_UNSYNCHRONIZED)
+    };
+
+    let mut reply = [0; PACKET_SIZE];
+    reply[0] = leap << 6 | version << 3 | MODE_SERVER;
+    reply[1] = stratum;
+    reply[2] = request[2];
+    reply[3] = PRECISION as u8;
+    // The root delay stays at 0, the round trip to the source is not known
+    reply[8..12].copy_from_slice(&to_short(clock.dispersion_ms).to_be_bytes());
+    reply[12..16].copy_from_slice(&clock.reference_id);
+    if let Some(reference_time) = clock.reference_time {
+        reply[16..24].copy_from_slice(&to_timestamp(reference_time).to_be_bytes());
+    }
+    reply[24..32].copy_from_slice(&request[40..48]);
+    reply[32..40].copy_from_slice(&to_timestamp(received).to_be_bytes());
+    reply[40..48].copy_from_slice(&to_timestamp(transmit).to_be_bytes());
+
+    Some(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn time(seconds: i64, nanos: u32) -> DateTime<Utc> {
+        Utc.timestamp_opt(seconds, nanos).unwrap()
+    }
+
+    fn synchronized() -> ServerClock {
+        ServerClock {
+            synchronized: true,
+            stratum: 1,
+            reference_id: *b"GPS\0",
+            reference_time: Some(time(1_792_300_000, 0)),
+            dispersion_ms: 500,
+        }
+    }
+
+    /// A client request of the given version, with a poll of 2^6 s and a transmit
+    /// timestamp the reply must echo.
+    fn request(version: u8) -> [u8; PACKET_SIZE] {
+        let mut request = [0; PACKET_SIZE];
+        request[0] = version << 3 | MODE_CLIENT;
+        request[2] = 6;
+        request[40..48].copy_from_slice(&0x1234_5678_9abc_def0u64.to_be_bytes());
+        request
+    }
+
+    fn reply_to(request: &[u8], clock: &ServerClock) -> Option<[u8; PACKET_SIZE]> {
+        reply(
+            request,
+            clock,
+            time(1_792_300_100, 250_000_000),
+            time(1_792_300_100, 500_000_000),
+        )
+    }
+
+    fn timestamp(reply: &[u8; PACKET_SIZE], offset: usize) -> u64 {
+        u64::from_be_bytes(reply[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn converts_timestamps() {
+        assert_eq!(to_timestamp(time(0, 0)), (UNIX_OFFSET as u64) << 32);
+        assert_eq!(
+            to_timestamp(time(0, 500_000_000)) & 0xffff_ffff,
+            0x8000_0000
+        );
+        assert_eq!(from_timestamp(0), time(-UNIX_OFFSET, 0));
+
+        let now = time(1_792_300_100, 250_000_000);
+        assert_eq!(from_timestamp(to_timestamp(now)), now);
+    }
+
+    #[test]
+    fn converts_short_durations() {
+        assert_eq!(to_short(0), 0);
+        assert_eq!(to_short(500), 0x8000);
+        assert_eq!(to_short(1000), 0x1_0000);
+        assert_eq!(to_short(u32::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn ignores_short_datagrams() {
+        let request = request(4);
+        assert_eq!(reply_to(&[], &synchronized()), None);
+        assert_eq!(reply_to(&request[..PACKET_SIZE - 1], &synchronized()), None);
+    }
+
+    #[test]
+    fn ignores_garbage() {
+        // A pseudo-random sequence, from a xorshift generator
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for _ in 0..1000 {
+            let mut datagram = [0; 64];
+            for byte in &mut datagram {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                *byte = state as u8;
+            }
+            let length = (state >> 8) as usize % datagram.len();
+            if let Some(reply) = reply_to(&datagram[..length], &synchronized()) {
+                assert_eq!(datagram[0] & 0b111, MODE_CLIENT);
+                assert_eq!(reply[0] & 0b111, MODE_SERVER);
+            }
+        }
+    }
+
+    #[test]
+    fn ignores_other_modes() {
+        for mode in (0..8).filter(|&mode| mode != MODE_CLIENT) {
+            let mut request = request(4);
+            request[0] = 4 << 3 | mode;
+            assert_eq!(reply_to(&request, &synchronized()), None, "mode {mode}");
+        }
+    }
+
+    #[test]
+    fn ignores_unknown_versions() {
+        assert_eq!(reply_to(&request(0), &synchronized()), None);
+        assert_eq!(reply_to(&request(5), &synchronized()), None);
+        assert_eq!(reply_to(&request(7), &synchronized()), None);
+    }
+
+    #[test]
+    fn echoes_the_version_and_poll() {
+        for version in 1..=4 {
+            let reply = reply_to(&request(version), &synchronized()).unwrap();
+            assert_eq!(reply[0] >> 3 & 0b111, version);
+            assert_eq!(reply[0] & 0b111, MODE_SERVER);
+            assert_eq!(reply[2], 6);
+        }
+    }
+
+    #[test]
+    fn accepts_longer_datagrams() {
+        let mut datagram = request(4).to_vec();
+        datagram.extend_from_slice(&[0xff; 20]);
+        assert!(reply_to(&datagram, &synchronized()).is_some());
+    }
+
+    #[test]
+    fn returns_the_request_transmit_as_originate() {
+        let reply = reply_to(&request(4), &synchronized()).unwrap();
+        assert_eq!(timestamp(&reply, 24), 0x1234_5678_9abc_def0);
+        assert_eq!(
+            timestamp(&reply, 32),
+            to_timestamp(time(1_792_300_100, 250_000_000))
+        );
+        assert_eq!(
+            timestamp(&reply, 40),
+            to_timestamp(time(1_792_300_100, 500_000_000))
+        );
+    }
+
+    #[test]
+    fn announces_a_synchronized_clock() {
+        let reply = reply_to(&request(4), &synchronized()).unwrap();
+        assert_eq!(reply[0] >> 6, 0);
+        assert_eq!(reply[1], 1);
+        assert_eq!(reply[3] as i8, PRECISION);
+        assert_eq!(&reply[4..8], &[0; 4]);
+        assert_eq!(&reply[8..12], &0x8000u32.to_be_bytes());
+        assert_eq!(&reply[12..16], b"GPS\0");
+        assert_eq!(timestamp(&reply, 16), to_timestamp(time(1_792_300_000, 0)));
+    }
+
+    #[test]
+    fn announces_an_unsynchronized_clock() {
+        let clock = ServerClock {
+            synchronized: false,
+            reference_time: None,
+            ..synchronized()
+        };
+        let reply = reply_to(&request(4), &clock).unwrap();
+        assert_eq!(reply[0] >> 6, LEAP_UNSYNCHRONIZED);
+        assert_eq!(reply[1], STRATUM_UNSYNCHRONIZED);
+        assert_eq!(timestamp(&reply, 16), 0);
+    }
+}
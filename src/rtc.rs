@@ -0,0 +1,164 @@
+use crate::{
+    ds3231,
+    error::AppError,
+    i2c::{self, I2cBus},
+    time::{self, TimeSource},
+};
+use chrono::{DateTime, Utc};
+use esp_idf_svc::hal::delay::FreeRtos;
+use serde::Serialize;
+
+/// State of the RTC, as published in the status.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct RtcStatus {
+    /// Temperature of the RTC crystal, in degrees Celsius.
+    pub temperature: f32,
+    /// Seconds the RTC was ahead of SNTP at the last synchronization, behind if negative.
+    pub drift_seconds: Option<i64>,
+    /// Whether the RTC lost its time, e.g. after running out of battery, until it is set again.
+    pub lost_power: bool,
+}
+
+/// A DS3231 real-time clock on the I2C bus, keeping the time while the clock is unplugged.
+#[derive(Clone)]
+pub struct Rtc {
+    bus: I2cBus,
+}
+
+impl Rtc {
+    fn read(&self, register: u8, buf: &mut [u8]) -> Result<(), AppError> {
+        self.bus
+            .lock()
+            .unwrap()
+            .write_read(ds3231::ADDRESS, &[register], buf, i2c::timeout())?;
+
+        Ok(())
+    }
+
+    fn write(&self, register: u8, values: &[u8]) -> Result<(), AppError> {
+        let mut bytes = vec![register];
+        bytes.extend_from_slice(values);
+        self.bus
+            .lock()
+            .unwrap()
+            .write(ds3231::ADDRESS, &bytes, i2c::timeout())?;
+
+        Ok(())
+    }
+
+    /// Whether the oscillator stopped since the time was last set, making it meaningless.
+    fn lost_power(&self) -> Result<bool, AppError> {
+        let mut status = [0];
+        self.read(ds3231::STATUS_REGISTER, &mut status)?;
+
+        Ok(status[0] & ds3231::OSCILLATOR_STOPPED != 0)
+    }
+
+    /// Reads the time held by the RTC, or `None` if it lost it.
+    fn time(&self) -> Result<Option<DateTime<Utc>>, AppError> {
+        if self.lost_power()? {
+            return Ok(None);
+        }
+
+        let mut registers = [0; 7];
+        self.read(ds3231::TIME_REGISTER, &mut registers)?;
+
+        Ok(ds3231::decode_time(&registers).map(|time| time.and_utc()))
+    }
+
+    fn temperature(&self) -> Result<f32, AppError> {
+        let mut registers = [0; 2];
+        self.read(ds3231::TEMPERATURE_REGISTER, &mut registers)?;
+
+        Ok(ds3231::decode_temperature(registers[0], registers[1]))
+    }
+
+    /// Returns the state of the RTC, without a drift until one is measured.
+    pub fn status(&self) -> Result<RtcStatus, AppError> {
+        Ok(RtcStatus {
+            temperature: self.temperature()?,
+            drift_seconds: None,
+            lost_power: self.lost_power()?,
+        })
+    }
+
+    /// Offers the time of the RTC as the system time, so the clock shows the right time
+    /// before SNTP.
+    ///
+    /// # Returns
+    /// `true` if the system time was set, `false` if the RTC lost its time or a more
+    /// trusted source is in use.
+    pub fn seed_system_time(&self) -> Result<bool, AppError> {
+        match self.time()? {
+            Some(time) => {
+                let accepted = time::offer_time(TimeSource::Rtc, time)?;
+                if accepted {
+                    log::info!("System time set from the RTC to {time}");
+                }
+                Ok(accepted)
+            }
+            None => {
+                log::warn!("RTC lost its time, waiting for SNTP");
+                Ok(false)
+            }
+        }
+    }
+
+    /// Writes the system time to the RTC, measuring how far the RTC drifted first.
+    ///
+    /// # Returns
+    /// The [RtcStatus] after the write.
+    pub fn store_system_time(&self) -> Result<RtcStatus, AppError> {
+        // The RTC starts a new second when written, so the write waits for a full second
+        let to_next_second = 1000 - Utc::now().timestamp_subsec_millis().min(999);
+        FreeRtos::delay_ms(to_next_second);
+        let now = Utc::now();
+        let drift_seconds = self
+            .time()?
+            .map(|rtc| (rtc - now).num_milliseconds() as f64 / 1000.0)
+            .map(|drift| drift.round() as i64);
+
+        let registers = ds3231::encode_time(&now.naive_utc())
+            .ok_or_else(|| AppError::Sensor(format!("{now} does not fit the RTC")))?;
+        self.write(ds3231::TIME_REGISTER, &registers)?;
+
+        let mut status = [0];
+        self.read(ds3231::STATUS_REGISTER, &mut status)?;
+        self.write(
+            ds3231::STATUS_REGISTER,
+            &[status[0] & !ds3231::OSCILLATOR_STOPPED],
+        )?;
+
+        Ok(RtcStatus {
+            temperature: self.temperature()?,
+            drift_seconds,
+            lost_power: false,
+        })
+    }
+}
+
+/// Looks for a DS3231 on the I2C bus.
+///
+/// # Parameters
+/// - `bus`: The [I2cBus] the RTC may be wired to.
+///
+/// # Returns
+/// The [Rtc] if one answers, `None` otherwise.
+///
+/// # Example
+/// ```rust
+/// if let Some(rtc) = get_rtc(bus.clone()) {
+///     rtc.seed_system_time()?;
+/// }
+/// ```
+pub fn get_rtc(bus: I2cBus) -> Option<Rtc> {
+    let rtc = Rtc { bus };
+
+    match rtc.lost_power() {
+        Ok(_) => Some(rtc),
+        Err(_) => {
+            log::info!("No RTC found");
+            None
+        }
+    }
+}
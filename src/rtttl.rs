@@ -0,0 +1,316 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Duration, as a fraction of a whole note, used when the header does not set one.
+const DEFAULT_DURATION: u32 = 4;
+
+/// Octave used when the header does not set one.
+const DEFAULT_OCTAVE: u32 = 6;
+
+/// Tempo, in beats per minute, used when the header does not set one.
+const DEFAULT_BPM: u32 = 63;
+
+/// A note of a [Melody].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note {
+    /// Pitch of the tone, in hertz, or `None` for a pause.
+    pub frequency: Option<u32>,
+    /// Length of the note, in milliseconds.
+    pub duration_ms: u32,
+}
+
+/// A sequence of notes parsed from RTTTL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Melody {
+    pub name: String,
+    pub notes: Vec<Note>,
+}
+
+/// Errors returned by [parse] for malformed RTTTL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RtttlError {
+    /// The text does not have the three `name:defaults:notes` sections.
+    MissingSection,
+    InvalidDefault(String),
+    InvalidNote(String),
+}
+
+impl fmt::Display for RtttlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtttlError::MissingSection => write!(f, "expected `name:defaults:notes`"),
+            RtttlError::InvalidDefault(value) => write!(f, "invalid default `{value}`"),
+            RtttlError::InvalidNote(value) => write!(f, "invalid note `{value}`"),
+        }
+    }
+}
+
+/// Parses a melody written in RTTTL, the ringtone format of old mobile phones.
+///
+/// The text has three sections separated by colons: the name, the defaults
+/// (`d` duration, `o` octave and `b` tempo) and the comma-separated notes, each
+/// written `[duration]note[#][.][octave][.]`, with `p` for a pause.
+///
+/// # Returns
+/// The parsed [Melody], or an [RtttlError] pointing at the faulty part.
+///
+/// # Example
+/// ```rust
+/// let melody = parse("Beep:d=8,o=6,b=120:a,p,a")?;
+/// assert_eq!(melody.notes[0].frequency, Some(1760));
+/// ```
+pub fn parse(text: &str) -> Result<Melody, RtttlError> {
+    let mut sections = text.trim().splitn(3, ':');
+    let (Some(name), Some(defaults), Some(notes)) =
+        (sections.next(), sections.next(), sections.next())
+    else {
+        return Err(RtttlError::MissingSection);
+    };
+
+    let mut duration = DEFAULT_DURATION;
+    let mut octave = DEFAULT_OCTAVE;
+    let mut bpm = DEFAULT_BPM;
+
+    for default in defaults.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let invalid = || RtttlError::InvalidDefault(default.to_string());
+        let (key, value) = default.split_once('=').ok_or_else(invalid)?;
+        let value = value.trim().parse::<u32>().map_err(|_| invalid())?;
+
+        match key.trim() {
+            "d" if is_duration(value) => duration = value,
+            "o" if (4..=7).contains(&value) => octave = value,
+            "b" if (1..=900).contains(&value) => bpm = value,
+            _ => return Err(invalid()),
+        }
+    }
+
+    let whole_note_ms = 60_000 * 4 / bpm;
+    let notes = notes
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|note| parse_note(note, duration, octave, whole_note_ms))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Melody {
+        name: name.trim().to_string(),
+        notes,
+    })
+}
+
+/// Whether `value` is one of the note durations RTTTL allows.
+fn is_duration(value: u32) -> bool {
+    matches!(value, 1 | 2 | 4 | 8 | 16 | 32)
+}
+
+/// Parses a single note, falling back to the defaults for the omitted parts.
+fn parse_note(
+    text: &str,
+    default_duration: u32,
+    default_octave: u32,
+    whole_note_ms: u32,
+) -> Result<Note, RtttlError> {
+    let invalid = || RtttlError::InvalidNote(text.to_string());
+    let lower = text.to_ascii_lowercase();
+    let mut rest = lower.as_str();
+
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    let duration = match digits {
+        0 => default_duration,
+        _ => rest[..digits]
+            .parse::<u32>()
+            .ok()
+            .filter(|duration| is_duration(*duration))
+            .ok_or_else(invalid)?,
+    };
+    rest = &rest[digits..];
+
+    let mut chars = rest.chars();
+    let semitone = match chars.next().ok_or_else(invalid)? {
+        'c' => Some(0),
+        'd' => Some(2),
+        'e' => Some(4),
+        'f' => Some(5),
+        'g' => Some(7),
+        'a' => Some(9),
+        'b' | 'h' => Some(11),
+        'p' => None,
+        _ => return Err(invalid()),
+    };
+    rest = chars.as_str();
+
+    let sharp = rest.starts_with('#');
+    if sharp {
+        rest = &rest[1..];
+    }
+
+    let mut dotted = false;
+    if let Some(after_dot) = rest.strip_prefix('.') {
+        dotted = true;
+        rest = after_dot;
+    }
+
+    let octave = match rest.chars().next() {
+        Some(c @ '4'..='7') => {
+            rest = &rest[1..];
+            c as u32 - '0' as u32
+        }
+        _ => default_octave,
+    };
+
+    if let Some(after_dot) = rest.strip_prefix('.') {
+        dotted = true;
+        rest = after_dot;
+    }
+
+    if !rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut duration_ms = whole_note_ms / duration;
+    if dotted {
+        duration_ms += duration_ms / 2;
+    }
+
+    Ok(Note {
+        frequency: semitone.map(|semitone| frequency(semitone + u32::from(sharp), octave)),
+        duration_ms,
+    })
+}
+
+/// Returns the frequency, in hertz, of a semitone (0 for C) in an octave, tuned to A4 = 440 Hz.
+fn frequency(semitone: u32, octave: u32) -> u32 {
+    let from_a4 = semitone as f32 - 9.0 + (octave as f32 - 4.0) * 12.0;
+
+    (440.0 * 2f32.powf(from_a4 / 12.0)).round() as u32
+}
+
+/// The melodies built into the firmware, selectable for each alarm.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Tune {
+    #[default]
+    Beep,
+    Chime,
+    GranVals,
+    FurElise,
+}
+
+impl Tune {
+    /// Returns the RTTTL source of the tune.
+    pub fn rtttl(self) -> &'static str {
+        match self {
+            Tune::Beep => "Beep:d=8,o=6,b=120:a,p,a,p,a,p,a,2p",
+            Tune::Chime => "Chime:d=4,o=5,b=100:e6,c6,d6,2g,p,g,d6,e6,2c6,2p",
+            Tune::GranVals => "GranVals:d=4,o=5,b=180:8e6,8d6,f#,g#,8c#6,8b,d,e,8b,8a,c#,e,2a,2p",
+            Tune::FurElise => {
+                "FurElise:d=8,o=5,b=125:e6,d#6,e6,d#6,e6,b,d6,c6,4a.,c,e,a,4b.,e,g#,b,4c6.,\
+                 e,e6,d#6,e6,d#6,e6,b,d6,c6,4a.,2p"
+            }
+        }
+    }
+
+    /// Returns the parsed [Melody] of the tune.
+    pub fn melody(self) -> Melody {
+        parse(self.rtttl()).expect("Built-in tunes are valid RTTTL")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(frequency: Option<u32>, duration_ms: u32) -> Note {
+        Note {
+            frequency,
+            duration_ms,
+        }
+    }
+
+    #[test]
+    fn uses_the_defaults() {
+        let melody = parse("Plain::a").unwrap();
+        assert_eq!(melody.name, "Plain");
+        // A quarter note at 63 bpm, A6
+        assert_eq!(melody.notes, [note(Some(1760), 240_000 / 63 / 4)]);
+    }
+
+    #[test]
+    fn applies_the_header() {
+        let melody = parse(" Beep : d=8, o=4, b=120 : a, p, 2c ").unwrap();
+        assert_eq!(melody.name, "Beep");
+        assert_eq!(
+            melody.notes,
+            [note(Some(440), 250), note(None, 250), note(Some(262), 1000)]
+        );
+    }
+
+    #[test]
+    fn parses_sharps_and_octaves() {
+        let melody = parse("Scale:d=4,o=5,b=120:c#,a4,a7,h,B#").unwrap();
+        let frequencies: Vec<_> = melody.notes.iter().map(|n| n.frequency).collect();
+        assert_eq!(
+            frequencies,
+            [Some(554), Some(440), Some(3520), Some(988), Some(1047)]
+        );
+    }
+
+    #[test]
+    fn lengthens_dotted_notes() {
+        // The dot may come before or after the octave
+        let melody = parse("Dots:d=4,o=5,b=120:a.,a6.,8p.").unwrap();
+        assert_eq!(
+            melody.notes,
+            [note(Some(880), 750), note(Some(1760), 750), note(None, 375)]
+        );
+    }
+
+    #[test]
+    fn accepts_empty_notes_and_defaults() {
+        let melody = parse("Empty:,:").unwrap();
+        assert_eq!(melody.name, "Empty");
+        assert!(melody.notes.is_empty());
+    }
+
+    #[test]
+    fn rejects_missing_sections() {
+        assert_eq!(parse(""), Err(RtttlError::MissingSection));
+        assert_eq!(parse("Name:d=4"), Err(RtttlError::MissingSection));
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let invalid = |value: &str| Err(RtttlError::InvalidDefault(value.to_string()));
+        assert_eq!(parse("X:d4:a"), invalid("d4"));
+        assert_eq!(parse("X:d=x:a"), invalid("d=x"));
+        assert_eq!(parse("X:q=4:a"), invalid("q=4"));
+        assert_eq!(parse("X:d=3:a"), invalid("d=3"));
+        assert_eq!(parse("X:o=3:a"), invalid("o=3"));
+        assert_eq!(parse("X:o=8:a"), invalid("o=8"));
+        assert_eq!(parse("X:b=0:a"), invalid("b=0"));
+        assert_eq!(parse("X:b=901:a"), invalid("b=901"));
+        assert_eq!(parse("X:b=-1:a"), invalid("b=-1"));
+    }
+
+    #[test]
+    fn rejects_bad_notes() {
+        let invalid = |value: &str| Err(RtttlError::InvalidNote(value.to_string()));
+        assert_eq!(parse("X::3a"), invalid("3a"));
+        assert_eq!(parse("X::64a"), invalid("64a"));
+        assert_eq!(parse("X::99999999999a"), invalid("99999999999a"));
+        assert_eq!(parse("X::x"), invalid("x"));
+        assert_eq!(parse("X::8"), invalid("8"));
+        assert_eq!(parse("X::a8"), invalid("a8"));
+        assert_eq!(parse("X::a##"), invalid("a##"));
+        assert_eq!(parse("X::a5.."), invalid("a5.."));
+        assert_eq!(parse("X::a,é"), invalid("é"));
+    }
+
+    #[test]
+    fn builtin_tunes_parse() {
+        for tune in [Tune::Beep, Tune::Chime, Tune::GranVals, Tune::FurElise] {
+            assert!(parse(tune.rtttl()).is_ok(), "{tune:?}");
+            assert!(!tune.melody().notes.is_empty());
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    climate::{self, Bme280Calibration, Reading},
+    error::{self, AppError},
+    events::Events,
+    health,
+    i2c::{self, I2cBus},
+    settings,
+};
+use esp_idf_svc::{
+    hal::{
+        delay::{Ets, FreeRtos},
+        gpio::{AnyIOPin, InputOutput, PinDriver, Pull},
+        interrupt,
+    },
+    sys::esp_timer_get_time,
+};
+use std::thread;
+
+/// Time, in milliseconds, between two samples of the sensor.
+const SAMPLE_INTERVAL_MS: u32 = 30_000;
+
+/// Failed samples in a row after which the reading is dropped from the status.
+const MAX_FAILURES: u32 = 3;
+
+/// Stack size of the thread sampling the sensor.
+const SENSOR_STACK_SIZE: usize = 4096;
+
+/// I2C addresses a BME280 answers on, depending on its `SDO` pin.
+const BME280_ADDRESSES: [u8; 2] = [0x76, 0x77];
+
+/// Value of the BME280 `id` register.
+const BME280_CHIP_ID: u8 = 0x60;
+
+/// I2C addresses an SHT3x answers on, depending on its `ADDR` pin.
+const SHT3X_ADDRESSES: [u8; 2] = [0x44, 0x45];
+
+/// Time, in milliseconds, an SHT3x takes for a high-repeatability measurement.
+const SHT3X_MEASURE_MS: u32 = 20;
+
+/// Time, in microseconds, a DHT22 waits for the next level before the read is abandoned.
+const DHT22_TIMEOUT_US: i64 = 150;
+
+/// Length, in microseconds, above which a high pulse of the DHT22 encodes a `1`.
+const DHT22_ONE_US: i64 = 45;
+
+/// The climate sensor found on the board.
+enum Sensor {
+    Bme280 {
+        address: u8,
+        calibration: Bme280Calibration,
+    },
+    Sht3x {
+        address: u8,
+    },
+    Dht22(PinDriver<'static, AnyIOPin, InputOutput>),
+}
+
+impl Sensor {
+    fn name(&self) -> &'static str {
+        match self {
+            Sensor::Bme280 { .. } => "BME280",
+            Sensor::Sht3x { .. } => "SHT3x",
+            Sensor::Dht22(_) => "DHT22",
+        }
+    }
+
+    /// Takes a sample, in degrees Celsius.
+    fn read(&mut self, bus: &I2cBus) -> Result<Reading, AppError> {
+        let decoded = match self {
+            Sensor::Bme280 {
+                address,
+                calibration,
+            } => {
+                let mut raw = [0; 8];
+                bus.lock()
+                    .unwrap()
+                    .write_read(*address, &[0xf7], &mut raw, i2c::timeout())?;
+                calibration.compensate(&raw)
+            }
+            Sensor::Sht3x { address } => {
+                bus.lock()
+                    .unwrap()
+                    .write(*address, &[0x24, 0x00], i2c::timeout())?;
+                FreeRtos::delay_ms(SHT3X_MEASURE_MS);
+                let mut frame = [0; 6];
+                bus.lock()
+                    .unwrap()
+                    .read(*address, &mut frame, i2c::timeout())?;
+                climate::sht3x_decode(&frame)
+            }
+            Sensor::Dht22(pin) => climate::dht22_decode(&read_dht22(pin)?),
+        };
+
+        decoded.map_err(|e| AppError::Sensor(format!("{}: {e}", self.name())))
+    }
+}
+
+/// Returns the time since boot, in microseconds.
+fn now_us() -> i64 {
+    unsafe { esp_timer_get_time() }
+}
+
+/// Waits for the DHT22 line to reach `level`.
+///
+/// # Returns
+/// The time waited, in microseconds, or `None` after [DHT22_TIMEOUT_US].
+fn wait_level(pin: &PinDriver<'static, AnyIOPin, InputOutput>, level: bool) -> Option<i64> {
+    let start = now_us();
+
+    while pin.is_high() != level {
+        if now_us() - start > DHT22_TIMEOUT_US {
+            return None;
+        }
+    }
+
+    Some(now_us() - start)
+}
+
+/// Reads the 5 bytes of a DHT22 over its single-wire protocol.
+///
+/// The host pulls the line low for over 1 ms, then the sensor answers with 40 bits,
+/// each a 50 µs low pulse followed by a high pulse of 26 µs for a `0` or 70 µs for a `1`.
+/// Interrupts are disabled while the bits are timed.
+fn read_dht22(pin: &mut PinDriver<'static, AnyIOPin, InputOutput>) -> Result<[u8; 5], AppError> {
+    pin.set_low()?;
+    Ets::delay_us(1200);
+    pin.set_high()?;
+
+    let frame = interrupt::free(|| {
+        // The sensor acknowledges with 80 µs low, then 80 µs high
+        wait_level(pin, false)?;
+        wait_level(pin, true)?;
+        wait_level(pin, false)?;
+
+        let mut frame = [0u8; 5];
+        for bit in 0..40 {
+            wait_level(pin, true)?;
+            if wait_level(pin, false)? > DHT22_ONE_US {
+                frame[bit / 8] |= 0x80 >> (bit % 8);
+            }
+        }
+
+        Some(frame)
+    });
+
+    frame.ok_or_else(|| AppError::Sensor("DHT22 did not answer".to_string()))
+}
+
+/// Looks for a BME280, then an SHT3x on the I2C bus, and falls back to a DHT22 on `dht_pin`.
+fn probe(bus: &I2cBus, dht_pin: AnyIOPin) -> Result<Option<Sensor>, AppError> {
+    for address in BME280_ADDRESSES {
+        let mut id = [0];
+        let mut bus = bus.lock().unwrap();
+        if bus
+            .write_read(address, &[0xd0], &mut id, i2c::timeout())
+            .is_err()
+            || id[0] != BME280_CHIP_ID
+        {
+            continue;
+        }
+
+        let (mut low, mut high) = ([0; 26], [0; 7]);
+        bus.write_read(address, &[0x88], &mut low, i2c::timeout())?;
+        bus.write_read(address, &[0xe1], &mut high, i2c::timeout())?;
+
+        // Humidity, temperature and pressure oversampled once, normal mode, 1 s standby
+        bus.write(address, &[0xf2, 0x01], i2c::timeout())?;
+        bus.write(address, &[0xf5, 0xa0], i2c::timeout())?;
+        bus.write(address, &[0xf4, 0x27], i2c::timeout())?;
+
+        return Ok(Some(Sensor::Bme280 {
+            address,
+            calibration: Bme280Calibration::parse(&low, &high),
+        }));
+    }
+
+    for address in SHT3X_ADDRESSES {
+        let mut sensor = Sensor::Sht3x { address };
+        if sensor.read(bus).is_ok() {
+            return Ok(Some(sensor));
+        }
+    }
+
+    let mut pin = PinDriver::input_output_od(dht_pin)?;
+    pin.set_pull(Pull::Up)?;
+    pin.set_high()?;
+    // The DHT22 needs a second after power-up before it answers
+    FreeRtos::delay_ms(1000);
+    let mut sensor = Sensor::Dht22(pin);
+
+    Ok(sensor.read(bus).is_ok().then_some(sensor))
+}
+
+/// Starts the thread sampling the optional climate sensor.
+///
+/// The sensor is probed once on the thread: a BME280 or an SHT3x on the I2C bus, else a
+/// DHT22 on `dht_pin`. Each sample is published to the [Events] status in the unit from
+/// the settings; the thread stops if no sensor answers.
+///
+/// # Parameters
+/// - `bus`: The [I2cBus] the I2C sensors are wired to.
+/// - `dht_pin`: The pin a DHT22 may be wired to, with a pull-up.
+/// - `settings`: The [settings::Store] holding the temperature unit.
+/// - `events`: The [Events] hub publishing the readings.
+///
+/// # Returns
+/// `Ok(())` once the thread is running, or an [AppError] if it cannot be spawned.
+///
+/// # Example
+/// ```rust
+/// start_sensor(bus.clone(), peripherals.pins.gpio23.downgrade(), settings.clone(), events.clone())?;
+/// ```
+pub fn start_sensor(
+    bus: I2cBus,
+    dht_pin: AnyIOPin,
+    settings: settings::Store,
+    events: Events,
+) -> Result<(), AppError> {
+    thread::Builder::new()
+        .name("sensor".to_string())
+        .stack_size(SENSOR_STACK_SIZE)
+        .spawn(move || {
+            let mut sensor = match probe(&bus, dht_pin) {
+                Ok(Some(sensor)) => sensor,
+                Ok(None) => {
+                    log::info!("No climate sensor found");
+                    return;
+                }
+                Err(e) => {
+                    log::error!("Failed to probe climate sensor: {:#?}", e);
+                    error::record(&e);
+                    return;
+                }
+            };
+            log::info!("Climate sensor {} found", sensor.name());
+            let _heartbeat = health::track();
+
+            let mut failures = 0;
+            loop {
+                match sensor.read(&bus) {
+                    Ok(reading) => {
+                        failures = 0;
+                        let unit = settings.lock().unwrap().get().temperature_unit;
+                        events.update(|status| status.climate = Some(reading.in_unit(unit)));
+                    }
+                    Err(e) => {
+                        failures += 1;
+                        log::warn!("Failed to read climate sensor: {e}");
+                        if failures == MAX_FAILURES {
+                            events.update(|status| status.climate = None);
+                        }
+                    }
+                }
+
+                health::beat();
+                FreeRtos::delay_ms(SAMPLE_INTERVAL_MS);
+            }
+        })
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    Ok(())
+}
@@ -1,11 +1,14 @@
 use crate::error::AppError;
 use esp_idf_svc::http::server::{Configuration as ServerConfiguration, EspHttpServer};
 
+/// Maximum number of simultaneous connections, the most lwIP allows for the HTTP server.
+const MAX_OPEN_SOCKETS: usize = 7;
+
 /// Initializes and starts an HTTP server.
 ///
 /// This function creates a new instance of the [EspHttpServer] using the default configuration
-/// provided by [ServerConfiguration::default]. It is used to set up a basic HTTP server that can
-/// handle incoming requests.
+/// provided by [ServerConfiguration::default], with room for [MAX_OPEN_SOCKETS] connections
+/// since every browser keeps a WebSocket open for the live status.
 ///
 /// # Returns
 /// - `Ok(EspHttpServer)`: The successfully created HTTP server instance.
@@ -16,7 +19,10 @@ use esp_idf_svc::http::server::{Configuration as ServerConfiguration, EspHttpSer
 /// let server = start_server().expect("Failed to start HTTP server");
 /// ```
 pub fn start_server() -> Result<EspHttpServer<'static>, AppError> {
-    let http_server = EspHttpServer::new(&ServerConfiguration::default())?;
+    let http_server = EspHttpServer::new(&ServerConfiguration {
+        max_open_sockets: MAX_OPEN_SOCKETS,
+        ..Default::default()
+    })?;
 
     Ok(http_server)
 }
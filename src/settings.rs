@@ -0,0 +1,447 @@
+use crate::{
+    alarm::{Alarm, MAX_ALARMS},
+    climate::TemperatureUnit,
+    countdown::{Event, MAX_EVENTS, MAX_NAME_LENGTH},
+    dilation::{Dilation, MAX_FACTOR},
+    error::AppError,
+    group::GroupRole,
+    lamp::{Lamp, KELVIN_RANGE, MAX_LEDS},
+    solar::Location,
+    sunrise::MAX_SUNRISE_MINUTES,
+    time, utils,
+    world::{Zone, MAX_ZONES},
+};
+use chrono_tz::Tz;
+use esp_idf_svc::{
+    nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
+    sys::EspError,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+/// NVS namespace reserved for the clock settings.
+pub const NVS_NAMESPACE: &str = "settings";
+
+/// NVS key under which the serialized [Settings] are stored.
+const NVS_KEY: &str = "settings";
+
+/// Type alias for the [SettingsStore].
+/// This is an ´Arc<Mutex<>>´ to ensure thread safety and shared access to the settings.
+pub type Store = Arc<Mutex<SettingsStore>>;
+
+/// User-configurable options of the clock, persisted as JSON in NVS.
+///
+/// Missing fields fall back to their [Default] value, so settings saved by older
+/// firmware versions keep loading after new options are added.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    /// Hostname of the clock, announced via mDNS as `<hostname>.local`.
+    pub hostname: String,
+    /// Whether the web interface is advertised via mDNS.
+    pub mdns_enabled: bool,
+    /// Whether the clock serves its time to the network over SNTP, on UDP port 123.
+    pub sntp_server: bool,
+    /// IANA name of the timezone the time is displayed in, e.g. `Europe/Berlin`.
+    pub timezone: String,
+    /// Where the clock stands, for the times of the sun; `None` until set.
+    pub location: Option<Location>,
+    /// Display brightness (0 to 7) applied from the civil dusk to the dawn, once a
+    /// location is set; `None` leaves the brightness alone.
+    pub night_brightness: Option<u8>,
+    /// Secondary timezones shown in turns by the world clock display mode.
+    pub world_clock: Vec<Zone>,
+    /// SSID of the Wi-Fi network to connect to.
+    pub wifi_ssid: String,
+    /// Password of the Wi-Fi network, never returned by the settings API.
+    pub wifi_password: String,
+    /// Token required as `Authorization: Bearer <token>` by the protected endpoints,
+    /// never returned by the settings API.
+    pub api_token: String,
+    /// Whether the time is shown in 12-hour format instead of 24-hour.
+    pub twelve_hour: bool,
+    /// Daily alarms, in local time.
+    pub alarms: Vec<Alarm>,
+    /// Volume of the buzzer, from 0 to 100 percent.
+    pub buzzer_volume: u8,
+    /// Options of the RGB LED lamp.
+    pub lamp: Lamp,
+    /// Minutes over which the lamp simulates a sunrise before an alarm, `0` to disable it.
+    pub sunrise_minutes: u16,
+    /// Unit the temperature of the climate sensor is shown and published in.
+    pub temperature_unit: TemperatureUnit,
+    /// Options of the time-dilation display mode.
+    pub dilation: Dilation,
+    /// Named days counted down to on the display, e.g. a launch or a holiday.
+    pub events: Vec<Event>,
+    /// The part the clock plays in a group of clocks showing the same thing.
+    pub group_role: GroupRole,
+    /// Key shared by the clocks of the group to sign their messages, never returned by
+    /// the settings API.
+    pub group_key: String,
+    /// Address a core dump is sent to with a `POST` after a crash, empty to keep it on
+    /// the clock.
+    pub coredump_url: String,
+    /// Address of the MQTT broker the climate readings are published to, e.g.
+    /// `mqtt://broker.local:1883`, empty to keep them on the clock.
+    pub mqtt_url: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            hostname: "interstellar-clock".to_string(),
+            mdns_enabled: true,
+            sntp_server: false,
+            timezone: time::DEFAULT_TIMEZONE.name().to_string(),
+            location: None,
+            night_brightness: None,
+            world_clock: Vec::new(),
+            wifi_ssid: String::new(),
+            wifi_password: String::new(),
+            api_token: String::new(),
+            twelve_hour: false,
+            alarms: vec![Alarm::default(); 2],
+            buzzer_volume: 50,
+            lamp: Lamp::default(),
+            sunrise_minutes: 30,
+            temperature_unit: TemperatureUnit::default(),
+            dilation: Dilation::default(),
+            events: Vec::new(),
+            group_role: GroupRole::default(),
+            group_key: String::new(),
+            coredump_url: String::new(),
+            mqtt_url: String::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Returns the configured timezone, or [time::DEFAULT_TIMEZONE] if it is unknown.
+    pub fn timezone(&self) -> Tz {
+        Tz::from_str(&self.timezone).unwrap_or(time::DEFAULT_TIMEZONE)
+    }
+
+    /// Returns a copy of the settings with the secrets blanked out, safe to send to clients.
+    pub fn redacted(&self) -> Settings {
+        Settings {
+            wifi_password: String::new(),
+            api_token: String::new(),
+            group_key: String::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Checks that every option holds an acceptable value.
+    ///
+    /// # Returns
+    /// `Ok(())` if the settings are valid, or an [AppError::Settings] describing the first invalid option.
+    pub fn validate(&self) -> Result<(), AppError> {
+        let hostname = self.hostname.as_bytes();
+        let valid_hostname = (1..=63).contains(&hostname.len())
+            && hostname
+                .iter()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || *c == b'-')
+            && hostname.first() != Some(&b'-')
+            && hostname.last() != Some(&b'-');
+
+        if !valid_hostname {
+            return Err(AppError::Settings(format!(
+                "invalid hostname `{}`: use 1-63 lowercase letters, digits or hyphens",
+                self.hostname
+            )));
+        }
+
+        if Tz::from_str(&self.timezone).is_err() {
+            return Err(AppError::Settings(format!(
+                "unknown timezone `{}`",
+                self.timezone
+            )));
+        }
+
+        if self.location.is_some_and(|location| !location.is_valid()) {
+            return Err(AppError::Settings(
+                "latitude must be between -90 and 90, longitude between -180 and 180".to_string(),
+            ));
+        }
+
+        if self
+            .night_brightness
+            .is_some_and(|brightness| brightness > 7)
+        {
+            return Err(AppError::Settings(
+                "night brightness must be between 0 and 7".to_string(),
+            ));
+        }
+
+        if self.world_clock.len() > MAX_ZONES || !self.world_clock.iter().all(Zone::is_valid) {
+            return Err(AppError::Settings(format!(
+                "at most {MAX_ZONES} world clock zones with a known timezone and a label of 2-4 letters or digits"
+            )));
+        }
+
+        if self.wifi_ssid.len() > 32 || self.wifi_password.len() > 64 {
+            return Err(AppError::Settings(
+                "Wi-Fi SSID or password too long".to_string(),
+            ));
+        }
+
+        if self.api_token.len() > 64 || !self.api_token.bytes().all(|c| c.is_ascii_graphic()) {
+            return Err(AppError::Settings(
+                "API token must be at most 64 printable characters without spaces".to_string(),
+            ));
+        }
+
+        if self.alarms.len() > MAX_ALARMS || !self.alarms.iter().all(Alarm::is_valid) {
+            return Err(AppError::Settings(format!(
+                "at most {MAX_ALARMS} alarms with an hour of 0-23 and a minute of 0-59"
+            )));
+        }
+
+        if self.buzzer_volume > 100 {
+            return Err(AppError::Settings(
+                "buzzer volume must be between 0 and 100".to_string(),
+            ));
+        }
+
+        if !self.lamp.is_valid() {
+            return Err(AppError::Settings(format!(
+                "lamp needs a brightness of 0-100, a temperature of {}-{} K and at most {MAX_LEDS} LEDs",
+                KELVIN_RANGE.start(),
+                KELVIN_RANGE.end()
+            )));
+        }
+
+        if self.sunrise_minutes > MAX_SUNRISE_MINUTES {
+            return Err(AppError::Settings(format!(
+                "sunrise must last at most {MAX_SUNRISE_MINUTES} minutes"
+            )));
+        }
+
+        if !self.dilation.is_valid() {
+            return Err(AppError::Settings(format!(
+                "dilation factor must be between 1 and {MAX_FACTOR}"
+            )));
+        }
+
+        if self.events.len() > MAX_EVENTS || !self.events.iter().all(Event::is_valid) {
+            return Err(AppError::Settings(format!(
+                "at most {MAX_EVENTS} events named with 1-{MAX_NAME_LENGTH} characters"
+            )));
+        }
+
+        if self.events.iter().enumerate().any(|(i, event)| {
+            self.events[..i]
+                .iter()
+                .any(|other| other.name == event.name)
+        }) {
+            return Err(AppError::Settings("event names must be unique".to_string()));
+        }
+
+        if self.group_role != GroupRole::Off && !(8..=64).contains(&self.group_key.len()) {
+            return Err(AppError::Settings(
+                "a group needs a shared key of 8 to 64 characters".to_string(),
+            ));
+        }
+
+        let url = &self.coredump_url;
+        if !url.is_empty()
+            && (url.len() > 256 || !(url.starts_with("http://") || url.starts_with("https://")))
+        {
+            return Err(AppError::Settings(format!(
+                "invalid coredump_url `{url}`: use an http:// or https:// address of at most 256 characters"
+            )));
+        }
+
+        let url = &self.mqtt_url;
+        if !url.is_empty()
+            && (url.len() > 256 || !(url.starts_with("mqtt://") || url.starts_with("mqtts://")))
+        {
+            return Err(AppError::Settings(format!(
+                "invalid mqtt_url `{url}`: use an mqtt:// or mqtts:// address of at most 256 characters"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Holds the current [Settings] together with the NVS handle used to persist them.
+pub struct SettingsStore {
+    /// `None` when NVS cannot be opened, the settings then only last until the restart.
+    nvs: Option<EspNvs<NvsDefault>>,
+    settings: Settings,
+}
+
+impl SettingsStore {
+    /// Returns the current settings.
+    pub fn get(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Applies a partial JSON document on top of the current settings and persists the result.
+    ///
+    /// Only the fields present in `json` are changed, e.g. `{"hostname": "clock"}`.
+    ///
+    /// # Parameters
+    /// - `json`: The raw JSON object received from the client.
+    ///
+    /// # Returns
+    /// `Ok(())` if the merged settings are valid and were saved, or an [AppError] otherwise.
+    pub fn merge_json(&mut self, json: &[u8]) -> Result<(), AppError> {
+        let settings = patch_json(&self.settings, json)?;
+
+        self.replace(settings)
+    }
+
+    /// Changes some options and persists the result.
+    ///
+    /// # Parameters
+    /// - `update`: A closure applying the changes to a copy of the current [Settings].
+    ///
+    /// # Returns
+    /// `Ok(())` if the updated settings are valid and were saved, or an [AppError] otherwise.
+    ///
+    /// # Example
+    /// ```rust
+    /// settings.lock().unwrap().update(|s| s.mdns_enabled = false)?;
+    /// ```
+    pub fn update(&mut self, update: impl FnOnce(&mut Settings)) -> Result<(), AppError> {
+        let mut settings = self.settings.clone();
+        update(&mut settings);
+
+        self.replace(settings)
+    }
+
+    /// Validates and persists a new set of settings.
+    ///
+    /// The timezone is applied right away, other options are read by the
+    /// services when they start.
+    ///
+    /// # Parameters
+    /// - `settings`: The settings replacing the current ones.
+    ///
+    /// # Returns
+    /// `Ok(())` if the settings are valid and were saved, or an [AppError] otherwise.
+    pub fn replace(&mut self, settings: Settings) -> Result<(), AppError> {
+        settings.validate()?;
+        if let Some(nvs) = &mut self.nvs {
+            nvs.set_blob(NVS_KEY, &serde_json::to_vec(&settings)?)
+                .map_err(|e| AppError::Storage(format!("cannot save the settings: {e}")))?;
+        }
+        time::set_timezone(settings.timezone());
+        self.settings = settings;
+
+        Ok(())
+    }
+
+    /// Erases the stored settings, going back to the [Default] ones.
+    pub fn erase(&mut self) -> Result<(), AppError> {
+        if let Some(nvs) = &mut self.nvs {
+            nvs.remove(NVS_KEY)
+                .map_err(|e| AppError::Storage(format!("cannot erase the settings: {e}")))?;
+        }
+        self.settings = Settings::default();
+        time::set_timezone(self.settings.timezone());
+
+        log::warn!("Settings erased");
+
+        Ok(())
+    }
+}
+
+/// Applies a partial JSON object on top of a copy of `current`.
+///
+/// Only the fields present in `json` are replaced, the others keep their value; nested
+/// objects are merged the same way, see [utils::merge_json].
+///
+/// # Parameters
+/// - `current`: The value to start from.
+/// - `json`: The raw JSON object received from the client, e.g. `{"on": true}`.
+///
+/// # Returns
+/// The patched value, or an [AppError] if `json` is not an object or does not fit the type.
+///
+/// # Example
+/// ```rust
+/// let lamp = patch_json(&settings.lamp, br#"{"brightness": 80}"#)?;
+/// ```
+pub fn patch_json<T: Serialize + DeserializeOwned>(
+    current: &T,
+    json: &[u8],
+) -> Result<T, AppError> {
+    let patch: serde_json::Value = serde_json::from_slice(json)?;
+    let mut value = serde_json::to_value(current)?;
+
+    if !value.is_object() || !patch.is_object() {
+        return Err(AppError::BadRequest("expected a JSON object".to_string()));
+    }
+    utils::merge_json(&mut value, patch);
+
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Opens the settings namespace in NVS and loads the stored [Settings].
+///
+/// If nothing was stored yet, or the stored data can no longer be parsed,
+/// the [Default] settings are used.
+///
+/// # Parameters
+/// - `nvs`: The default NVS partition.
+///
+/// # Returns
+/// The [Store] holding the loaded settings, or an [AppError] if NVS cannot be opened.
+///
+/// # Example
+/// ```rust
+/// let settings = load_settings(nvs.clone()).expect("Failed to load settings");
+/// ```
+pub fn load_settings(nvs: EspDefaultNvsPartition) -> Result<Store, AppError> {
+    let storage = |e: EspError| AppError::Storage(format!("cannot read the settings: {e}"));
+    let nvs = EspNvs::new(nvs, NVS_NAMESPACE, true).map_err(storage)?;
+
+    let settings = match nvs.blob_len(NVS_KEY).map_err(storage)? {
+        Some(len) => {
+            let mut buf = vec![0; len];
+            let stored = nvs
+                .get_blob(NVS_KEY, &mut buf)
+                .map_err(storage)?
+                .unwrap_or_default();
+
+            serde_json::from_slice(stored).unwrap_or_else(|e| {
+                log::warn!("Stored settings are invalid, using defaults: {e}");
+                Settings::default()
+            })
+        }
+        None => Settings::default(),
+    };
+
+    log::info!("Settings loaded for `{}`", settings.hostname);
+    time::set_timezone(settings.timezone());
+
+    Ok(Arc::new(Mutex::new(SettingsStore {
+        nvs: Some(nvs),
+        settings,
+    })))
+}
+
+/// Keeps the [Default] settings in memory only, for a clock whose NVS cannot be read.
+///
+/// Changes apply as usual but are lost at the next restart.
+///
+/// # Example
+/// ```rust
+/// let settings = load_settings(nvs.clone()).unwrap_or_else(|_| memory_settings());
+/// ```
+pub fn memory_settings() -> Store {
+    let settings = Settings::default();
+    time::set_timezone(settings.timezone());
+
+    Arc::new(Mutex::new(SettingsStore {
+        nvs: None,
+        settings,
+    }))
+}
@@ -0,0 +1,117 @@
+use crate::{command, device::Clock, error::AppError, health};
+use esp_idf_svc::{
+    hal::{
+        gpio::{AnyIOPin, IOPin, InputPin, OutputPin},
+        peripheral::Peripheral,
+        uart::{self, Uart, UartDriver},
+    },
+    io::vfs::BlockingStdIo,
+};
+use std::{
+    io::{self, BufRead, Write},
+    thread,
+};
+
+/// Stack size of the shell thread, commands may format long replies.
+const SHELL_STACK_SIZE: usize = 8192;
+
+/// Creates the [UartDriver] of the console, using the default 115200 baud 8N1 configuration.
+///
+/// # Parameters
+/// - `uart`: The UART peripheral of the console (UART0 by default).
+/// - `tx`: The TX pin of the console (implements [OutputPin]).
+/// - `rx`: The RX pin of the console (implements [InputPin]).
+///
+/// # Returns
+/// The [UartDriver] ready to be passed to [start_shell], or an [AppError] if it fails.
+///
+/// # Example
+/// ```rust
+/// let uart = get_console(peripherals.uart0, pins.gpio1, pins.gpio3).expect("Failed to get console");
+/// ```
+pub fn get_console<UART: Uart>(
+    uart: impl Peripheral<P = UART> + 'static,
+    tx: impl Peripheral<P = impl OutputPin> + 'static,
+    rx: impl Peripheral<P = impl InputPin> + 'static,
+) -> Result<UartDriver<'static>, AppError> {
+    Ok(UartDriver::new(
+        uart,
+        tx,
+        rx,
+        Option::<AnyIOPin>::None,
+        Option::<AnyIOPin>::None,
+        &uart::config::Config::default(),
+    )?)
+}
+
+/// Starts a line-based command shell on the UART console.
+///
+/// Each line is parsed with [command::parse] and run with [command::execute], the same
+/// command layer used by the HTTP handlers. Type `help` for the list of commands.
+///
+/// # Parameters
+/// - `uart`: The [UartDriver] of the console, made blocking for the standard input.
+/// - `clock`: The [Clock] the commands act on.
+///
+/// # Returns
+/// `Ok(())` if the shell thread is started, or an [AppError] if it fails.
+///
+/// # Example
+/// ```rust
+/// start_shell(uart, clock.clone()).expect("Failed to start the shell");
+/// ```
+pub fn start_shell<CLK, DIO>(
+    uart: UartDriver<'static>,
+    clock: Clock<CLK, DIO>,
+) -> Result<(), AppError>
+where
+    CLK: OutputPin,
+    DIO: IOPin,
+{
+    let stdio = BlockingStdIo::uart(uart)?;
+
+    thread::Builder::new()
+        .name("shell".to_string())
+        .stack_size(SHELL_STACK_SIZE)
+        .spawn(move || {
+            // Keeps the console in blocking mode for as long as the shell runs
+            let _stdio = stdio;
+            let mut lines = io::stdin().lock().lines();
+            let _heartbeat = health::track();
+
+            loop {
+                print!("> ");
+                let _ = io::stdout().flush();
+
+                let line = match lines.next() {
+                    Some(Ok(line)) => line,
+                    Some(Err(e)) => {
+                        log::error!("Failed to read from the console: {:#?}", e);
+                        continue;
+                    }
+                    None => break,
+                };
+
+                health::beat();
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let reply = command::parse(&line)
+                    .map_err(|e| e.to_string())
+                    .and_then(|command| {
+                        command::execute(&clock, &command).map_err(|e| e.to_string())
+                    });
+
+                match reply {
+                    Ok(reply) => println!("{reply}"),
+                    Err(e) => println!("Error: {e}"),
+                }
+            }
+        })
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    log::info!("Serial shell started, type `help` for a list of commands");
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use crate::{
+    error::AppError,
+    health,
+    ntp::{self, ServerClock},
+    time::{self, TimeSource},
+};
+use chrono::Utc;
+use std::{
+    net::{Ipv4Addr, UdpSocket},
+    thread,
+};
+
+/// Stack size of the thread answering the SNTP requests.
+const SNTP_SERVER_STACK_SIZE: usize = 4096;
+
+/// Time, in milliseconds, the last update keeps the clock synchronized: twice the hour
+/// between two SNTP synchronizations.
+const SYNC_VALIDITY_MS: u64 = 2 * 3_600_000;
+
+/// Stratum assumed for the upstream SNTP server, as the pool servers usually are.
+const UPSTREAM_STRATUM: u8 = 2;
+
+/// Maximum error, in milliseconds, announced relative to the source.
+const DISPERSION_MS: u32 = 100;
+
+/// Returns the state of the local clock, from the source that last set it.
+///
+/// The esp-idf `SyncStatus` is reset once read, so the last time accepted by
+/// [time::offer_time] is used instead: the clock is synchronized while GPS or SNTP set
+/// it within the last [SYNC_VALIDITY_MS], and never from the RTC or a time set by hand.
+fn server_clock() -> ServerClock {
+    let reference = time::reference();
+    let synchronized = reference.is_some_and(|reference| {
+        reference.source.is_synchronized()
+            && time::uptime_ms() - reference.uptime_ms < SYNC_VALIDITY_MS
+    });
+    let (stratum, reference_id) = match reference.map(|reference| reference.source) {
+        Some(TimeSource::Gps) => (1, *b"GPS\0"),
+        Some(TimeSource::Sntp) => (UPSTREAM_STRATUM + 1, [0; 4]),
+        Some(TimeSource::Rtc | TimeSource::Manual) | None => {
+            (ntp::STRATUM_UNSYNCHRONIZED, *b"LOCL")
+        }
+    };
+
+    ServerClock {
+        synchronized,
+        stratum,
+        reference_id,
+        reference_time: reference.map(|reference| reference.time),
+        dispersion_ms: DISPERSION_MS,
+    }
+}
+
+/// Starts an SNTPv4 server answering on UDP port 123, so other devices on the network
+/// can take their time from the clock.
+///
+/// # Returns
+/// `Ok(())` once the thread is running, or an [AppError] if the port cannot be bound
+/// or the thread cannot be spawned.
+///
+/// # Example
+/// ```rust
+/// if config.sntp_server {
+///     start_sntp_server()?;
+/// }
+/// ```
+pub fn start_sntp_server() -> Result<(), AppError> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, ntp::NTP_PORT))
+        .map_err(|e| AppError::Network(e.to_string()))?;
+
+    thread::Builder::new()
+        .name("sntp-server".to_string())
+        .stack_size(SNTP_SERVER_STACK_SIZE)
+        .spawn(move || {
+            let mut request = [0; ntp::PACKET_SIZE];
+            let _heartbeat = health::track();
+
+            loop {
+                let (size, client) = match socket.recv_from(&mut request) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        log::error!("Failed to receive SNTP request: {:#?}", e);
+                        continue;
+                    }
+                };
+                health::beat();
+                let received = Utc::now();
+
+                let Some(reply) =
+                    ntp::reply(&request[..size], &server_clock(), received, Utc::now())
+                else {
+                    continue;
+                };
+                if let Err(e) = socket.send_to(&reply, client) {
+                    log::error!("Failed to answer SNTP request: {:#?}", e);
+                }
+            }
+        })
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    log::info!("SNTP server listening on port {}", ntp::NTP_PORT);
+
+    Ok(())
+}
@@ -0,0 +1,368 @@
+use chrono::{DateTime, NaiveDate, TimeDelta, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Julian day of 2000-01-01 at 12:00 UTC, the J2000.0 epoch.
+const J2000: f64 = 2_451_545.0;
+
+/// Julian day of the Unix epoch.
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2_440_587.5;
+
+/// Obliquity of the ecliptic, in degrees.
+const OBLIQUITY: f64 = 23.4397;
+
+/// Altitude, in degrees, of the center of the sun at sunrise and sunset: the radius of
+/// the sun and the refraction near the horizon lift it by 0.833°.
+const SUNRISE_ALTITUDE: f64 = -0.833;
+
+/// Altitude, in degrees, of the sun at the start and the end of the civil twilight.
+const CIVIL_ALTITUDE: f64 = -6.0;
+
+/// Seconds the sun page shows each of its labels and times, in turns.
+pub const PAGE_SECONDS: u32 = 3;
+
+/// Where the clock stands on Earth, for the times of the sun.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Location {
+    /// Latitude, in degrees, positive to the north.
+    pub latitude: f64,
+    /// Longitude, in degrees, positive to the east.
+    pub longitude: f64,
+}
+
+impl Location {
+    /// Whether the latitude is within ±90° and the longitude within ±180°.
+    pub fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.latitude) && (-180.0..=180.0).contains(&self.longitude)
+    }
+}
+
+/// How the sun crosses an altitude during a day.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Crossing {
+    /// The sun rises above the altitude, then sets below it.
+    Times {
+        rise: DateTime<Utc>,
+        set: DateTime<Utc>,
+    },
+    /// The sun stays above the altitude the whole day, as in the polar summer.
+    AlwaysAbove,
+    /// The sun stays below the altitude the whole day, as in the polar winter.
+    AlwaysBelow,
+}
+
+impl Crossing {
+    /// Whether the sun is above the altitude at `now`, on the day of the crossing.
+    fn is_above(&self, now: DateTime<Utc>) -> bool {
+        match *self {
+            Crossing::Times { rise, set } => rise <= now && now < set,
+            Crossing::AlwaysAbove => true,
+            Crossing::AlwaysBelow => false,
+        }
+    }
+}
+
+/// The times of the sun on a local date.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct SolarDay {
+    /// When the sun is the highest.
+    pub noon: DateTime<Utc>,
+    /// Sunrise and sunset.
+    pub daylight: Crossing,
+    /// Civil dawn and dusk, when the sun is 6° below the horizon.
+    pub twilight: Crossing,
+}
+
+/// Converts a Julian day to a time.
+fn from_julian_day(day: f64) -> DateTime<Utc> {
+    let ms = ((day - UNIX_EPOCH_JULIAN_DAY) * 86_400_000.0).round() as i64;
+
+    DateTime::from_timestamp_millis(ms).unwrap_or_default()
+}
+
+impl SolarDay {
+    /// Computes the times of the sun with the sunrise equation, accurate to about a
+    /// minute away from the polar circles.
+    ///
+    /// # Parameters
+    /// - `date`: The local date, in the timezone of `location`.
+    /// - `location`: Where the clock stands.
+    ///
+    /// # Example
+    /// ```rust
+    /// let day = SolarDay::compute(now.date_naive(), &location);
+    /// log::info!("Sunrise at {:?}", day.sunrise());
+    /// ```
+    pub fn compute(date: NaiveDate, location: &Location) -> SolarDay {
+        let epoch = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap_or_default();
+        let days = (date - epoch).num_days() as f64;
+
+        // Mean solar noon, then the position of the sun on the ecliptic at that time
+        let mean_noon = days - location.longitude / 360.0;
+        let anomaly = (357.5291 + 0.985_600_28 * mean_noon)
+            .rem_euclid(360.0)
+            .to_radians();
+        let center = 1.9148 * anomaly.sin()
+            + 0.0200 * (2.0 * anomaly).sin()
+            + 0.0003 * (3.0 * anomaly).sin();
+        let ecliptic_longitude = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+            .rem_euclid(360.0)
+            .to_radians();
+        let transit =
+            J2000 + mean_noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+        let declination = (ecliptic_longitude.sin() * OBLIQUITY.to_radians().sin()).asin();
+
+        let latitude = location.latitude.to_radians();
+        let crossing = |altitude: f64| {
+            let cos_hour_angle = (altitude.to_radians().sin() - latitude.sin() * declination.sin())
+                / (latitude.cos() * declination.cos());
+            if cos_hour_angle < -1.0 {
+                return Crossing::AlwaysAbove;
+            }
+            if cos_hour_angle > 1.0 || cos_hour_angle.is_nan() {
+                return Crossing::AlwaysBelow;
+            }
+
+            let half_day = cos_hour_angle.acos().to_degrees() / 360.0;
+            Crossing::Times {
+                rise: from_julian_day(transit - half_day),
+                set: from_julian_day(transit + half_day),
+            }
+        };
+
+        SolarDay {
+            noon: from_julian_day(transit),
+            daylight: crossing(SUNRISE_ALTITUDE),
+            twilight: crossing(CIVIL_ALTITUDE),
+        }
+    }
+
+    /// Returns the sunrise, if the sun rises and sets this day.
+    pub fn sunrise(&self) -> Option<DateTime<Utc>> {
+        match self.daylight {
+            Crossing::Times { rise, .. } => Some(rise),
+            _ => None,
+        }
+    }
+
+    /// Returns the sunset, if the sun rises and sets this day.
+    pub fn sunset(&self) -> Option<DateTime<Utc>> {
+        match self.daylight {
+            Crossing::Times { set, .. } => Some(set),
+            _ => None,
+        }
+    }
+
+    /// Returns the civil dawn, if there is one this day.
+    pub fn dawn(&self) -> Option<DateTime<Utc>> {
+        match self.twilight {
+            Crossing::Times { rise, .. } => Some(rise),
+            _ => None,
+        }
+    }
+
+    /// Returns the civil dusk, if there is one this day.
+    pub fn dusk(&self) -> Option<DateTime<Utc>> {
+        match self.twilight {
+            Crossing::Times { set, .. } => Some(set),
+            _ => None,
+        }
+    }
+
+    /// Whether it is night at `now`: from the civil dusk to the civil dawn, when
+    /// lights are needed outdoors.
+    pub fn is_night(&self, now: DateTime<Utc>) -> bool {
+        !self.twilight.is_above(now)
+    }
+}
+
+/// Returns the times of the sun on the local date of `now`.
+///
+/// # Parameters
+/// - `location`: Where the clock stands.
+/// - `now`: The current time, in the configured timezone.
+pub fn today<Tz: TimeZone>(location: &Location, now: &DateTime<Tz>) -> SolarDay {
+    SolarDay::compute(now.date_naive(), location)
+}
+
+/// Returns the text of the sun page shown at `now`.
+///
+/// The sunrise and the sunset take turns, each showing its label (`rISE`, `SEt`) for
+/// [PAGE_SECONDS] then its local time as `HH:MM`, to the nearest minute. Days without sunrise show
+/// `dAY` or `nIGH` instead.
+pub fn page_text<Tz: TimeZone>(day: &SolarDay, now: &DateTime<Tz>) -> String {
+    let (rise, set) = match day.daylight {
+        Crossing::Times { rise, set } => (rise, set),
+        Crossing::AlwaysAbove => return "dAY".to_string(),
+        Crossing::AlwaysBelow => return "nIGH".to_string(),
+    };
+
+    let page = (now.second() / PAGE_SECONDS) % 4;
+    let (label, time) = if page < 2 {
+        ("rISE", rise)
+    } else {
+        ("SEt", set)
+    };
+    if page % 2 == 1 {
+        // Rounded to the nearest minute, as in the almanacs
+        let local = (time + TimeDelta::seconds(30)).with_timezone(&now.timezone());
+        return format!("{:02}:{:02}", local.hour(), local.minute());
+    }
+
+    label.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Europe::Paris;
+
+    const GREENWICH: Location = Location {
+        latitude: 51.4769,
+        longitude: -0.0005,
+    };
+    const NEW_YORK: Location = Location {
+        latitude: 40.7128,
+        longitude: -74.0060,
+    };
+    const SYDNEY: Location = Location {
+        latitude: -33.8688,
+        longitude: 151.2093,
+    };
+    const TROMSO: Location = Location {
+        latitude: 69.6492,
+        longitude: 18.9553,
+    };
+    const NORTH_POLE: Location = Location {
+        latitude: 90.0,
+        longitude: 0.0,
+    };
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    /// Checks a time against an almanac, which rounds to the minute.
+    fn assert_near(actual: Option<DateTime<Utc>>, expected: &str) {
+        let expected = expected.parse::<DateTime<Utc>>().unwrap();
+        let actual = actual.expect("no crossing");
+        let error = (actual - expected).num_seconds().abs();
+        assert!(error <= 120, "{actual} is {error} s away from {expected}");
+    }
+
+    #[test]
+    fn computes_greenwich_at_the_summer_solstice() {
+        // 04:43 and 21:21 BST
+        let day = SolarDay::compute(date(2026, 6, 21), &GREENWICH);
+        assert_near(day.sunrise(), "2026-06-21T03:43:00Z");
+        assert_near(day.sunset(), "2026-06-21T20:21:00Z");
+        assert_near(Some(day.noon), "2026-06-21T12:02:00Z");
+    }
+
+    #[test]
+    fn computes_new_york_on_new_years_day() {
+        // 07:20, 11:59 and 16:39 EST
+        let day = SolarDay::compute(date(2026, 1, 1), &NEW_YORK);
+        assert_near(day.sunrise(), "2026-01-01T12:20:00Z");
+        assert_near(Some(day.noon), "2026-01-01T16:59:00Z");
+        assert_near(day.sunset(), "2026-01-01T21:39:00Z");
+        assert_near(day.dawn(), "2026-01-01T11:50:00Z");
+        assert_near(day.dusk(), "2026-01-01T22:09:00Z");
+    }
+
+    #[test]
+    fn computes_sydney_in_the_southern_summer() {
+        // 05:41 and 20:05 AEDT, the sunrise falls on the previous UTC date
+        let day = SolarDay::compute(date(2026, 12, 21), &SYDNEY);
+        assert_near(day.sunrise(), "2026-12-20T18:41:00Z");
+        assert_near(day.sunset(), "2026-12-21T09:05:00Z");
+    }
+
+    #[test]
+    fn finds_the_midnight_sun() {
+        let day = SolarDay::compute(date(2026, 6, 21), &TROMSO);
+        assert_eq!(day.daylight, Crossing::AlwaysAbove);
+        assert_eq!(day.twilight, Crossing::AlwaysAbove);
+        assert_eq!(day.sunrise(), None);
+        assert_eq!(day.sunset(), None);
+        assert!(!day.is_night(day.noon + TimeDelta::hours(12)));
+    }
+
+    #[test]
+    fn finds_the_polar_night() {
+        // The sun stays below the horizon but the civil twilight still lights the noon
+        let day = SolarDay::compute(date(2026, 12, 21), &TROMSO);
+        assert_eq!(day.daylight, Crossing::AlwaysBelow);
+        assert!(matches!(day.twilight, Crossing::Times { .. }));
+        assert!(!day.is_night(day.noon));
+        assert!(day.is_night(day.noon - TimeDelta::hours(6)));
+
+        let day = SolarDay::compute(date(2026, 12, 21), &NORTH_POLE);
+        assert_eq!(day.daylight, Crossing::AlwaysBelow);
+        assert_eq!(day.twilight, Crossing::AlwaysBelow);
+        assert!(day.is_night(day.noon));
+    }
+
+    #[test]
+    fn tells_night_from_day() {
+        let day = SolarDay::compute(date(2026, 1, 1), &NEW_YORK);
+        let (dawn, dusk) = (day.dawn().unwrap(), day.dusk().unwrap());
+        assert!(day.is_night(dawn - TimeDelta::seconds(1)));
+        assert!(!day.is_night(dawn));
+        assert!(!day.is_night(day.noon));
+        assert!(day.is_night(dusk));
+    }
+
+    #[test]
+    fn validates_locations() {
+        assert!(GREENWICH.is_valid());
+        assert!(NORTH_POLE.is_valid());
+        assert!(Location {
+            latitude: -90.0,
+            longitude: 180.0
+        }
+        .is_valid());
+        assert!(!Location {
+            latitude: 90.5,
+            longitude: 0.0
+        }
+        .is_valid());
+        assert!(!Location {
+            latitude: 0.0,
+            longitude: -181.0
+        }
+        .is_valid());
+        assert!(!Location {
+            latitude: f64::NAN,
+            longitude: 0.0
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn pages_the_sunrise_and_sunset() {
+        let day = SolarDay {
+            noon: "2026-10-18T11:45:00Z".parse().unwrap(),
+            daylight: Crossing::Times {
+                rise: "2026-10-18T06:29:29Z".parse().unwrap(),
+                set: "2026-10-18T17:00:31Z".parse().unwrap(),
+            },
+            twilight: Crossing::AlwaysAbove,
+        };
+        let at = |second: u32| Paris.with_ymd_and_hms(2026, 10, 18, 20, 0, second).unwrap();
+
+        assert_eq!(page_text(&day, &at(0)), "rISE");
+        assert_eq!(page_text(&day, &at(3)), "08:29");
+        assert_eq!(page_text(&day, &at(6)), "SEt");
+        assert_eq!(page_text(&day, &at(9)), "19:01");
+        assert_eq!(page_text(&day, &at(12)), "rISE");
+    }
+
+    #[test]
+    fn pages_polar_days() {
+        let day = SolarDay::compute(date(2026, 6, 21), &TROMSO);
+        assert_eq!(page_text(&day, &Utc::now()), "dAY");
+        let day = SolarDay::compute(date(2026, 12, 21), &TROMSO);
+        assert_eq!(page_text(&day, &Utc::now()), "nIGH");
+    }
+}
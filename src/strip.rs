@@ -0,0 +1,140 @@
+use crate::{
+    error::{self, AppError},
+    health,
+    lamp::{self, Color},
+    settings,
+    sunrise::{self, Sunrise},
+    time,
+};
+use esp_idf_svc::hal::{
+    delay::FreeRtos,
+    gpio::OutputPin,
+    peripheral::Peripheral,
+    rmt::{config::TransmitConfig, PinState, Pulse, RmtChannel, TxRmtDriver, VariableLengthSignal},
+};
+use std::{thread, time::Duration};
+
+/// Time, in milliseconds, between two frames of the lamp effects.
+const FRAME_MS: u32 = 33;
+
+/// Stack size of the thread rendering the lamp.
+const STRIP_STACK_SIZE: usize = 4096;
+
+/// Driver of a WS2812 or SK6812 LED strip, clocking the colors out with the RMT peripheral.
+struct Ws2812 {
+    tx: TxRmtDriver<'static>,
+    /// The high and low pulses encoding a `0` bit.
+    zero: [Pulse; 2],
+    /// The high and low pulses encoding a `1` bit.
+    one: [Pulse; 2],
+}
+
+impl Ws2812 {
+    fn new<C: RmtChannel>(
+        channel: impl Peripheral<P = C> + 'static,
+        pin: impl Peripheral<P = impl OutputPin> + 'static,
+    ) -> Result<Self, AppError> {
+        let tx = TxRmtDriver::new(channel, pin, &TransmitConfig::new().clock_divider(1))?;
+        let ticks_hz = tx.counter_clock()?;
+        let pulse =
+            |state, ns| Pulse::new_with_duration(ticks_hz, state, &Duration::from_nanos(ns));
+
+        Ok(Ws2812 {
+            zero: [pulse(PinState::High, 350)?, pulse(PinState::Low, 900)?],
+            one: [pulse(PinState::High, 700)?, pulse(PinState::Low, 600)?],
+            tx,
+        })
+    }
+
+    /// Sends the colors to the strip, the first one going to the LED nearest the pin.
+    fn write(&mut self, colors: &[Color]) -> Result<(), AppError> {
+        let mut signal = VariableLengthSignal::with_capacity(colors.len() * 24 * 2);
+
+        for color in colors {
+            // The LEDs expect green, red, then blue, most significant bit first
+            let bits = u32::from(color.g) << 16 | u32::from(color.r) << 8 | u32::from(color.b);
+            for bit in (0..24).rev() {
+                let pulses = if bits >> bit & 1 == 1 {
+                    &self.one
+                } else {
+                    &self.zero
+                };
+                signal.push(pulses)?;
+            }
+        }
+
+        self.tx.start_blocking(&signal)?;
+
+        Ok(())
+    }
+}
+
+/// Starts the thread rendering the lamp effects on the LED strip.
+///
+/// The lamp options are read from the settings on every frame, so changes made
+/// through the API show up right away, and a running sunrise takes over the lamp.
+/// A frame is only sent when it differs from the previous one, which leaves the
+/// RMT idle for the steady effects.
+///
+/// # Parameters
+/// - `channel`: The RMT channel driving the strip.
+/// - `pin`: The pin the data line of the strip is wired to (implements [OutputPin]).
+/// - `settings`: The [settings::Store] holding the lamp options.
+/// - `sunrise`: The [Sunrise] rising before the alarms.
+///
+/// # Returns
+/// `Ok(())` once the thread is running, or an [AppError] if the RMT setup or the thread fails.
+///
+/// # Example
+/// ```rust
+/// start_strip(peripherals.rmt.channel0, peripherals.pins.gpio13, settings.clone(), sunrise.clone())?;
+/// ```
+pub fn start_strip<C: RmtChannel>(
+    channel: impl Peripheral<P = C> + 'static,
+    pin: impl Peripheral<P = impl OutputPin> + 'static,
+    settings: settings::Store,
+    sunrise: Sunrise,
+) -> Result<(), AppError> {
+    let mut strip = Ws2812::new(channel, pin)?;
+
+    thread::Builder::new()
+        .name("strip".to_string())
+        .stack_size(STRIP_STACK_SIZE)
+        .spawn(move || {
+            let _heartbeat = health::watch()
+                .inspect_err(|e| {
+                    log::error!("Failed to watch the strip task: {:#?}", e);
+                    error::record(e);
+                })
+                .ok();
+            let mut last: Vec<Color> = Vec::new();
+
+            loop {
+                let options = settings.lock().unwrap().get().lamp.clone();
+                let dawn = sunrise.lock().unwrap().progress();
+                let mut colors = match dawn {
+                    Some(progress) => sunrise::frame(progress, options.leds),
+                    None => lamp::frame(&options, time::uptime_ms()),
+                };
+
+                // Blanks the LEDs left over when the strip is made shorter
+                if colors.len() < last.len() {
+                    colors.resize(last.len(), Color::BLACK);
+                }
+
+                if colors != last {
+                    if let Err(e) = strip.write(&colors) {
+                        log::error!("Failed to write LED strip: {:#?}", e);
+                        error::record(&e);
+                    }
+                    last = colors;
+                }
+
+                health::beat();
+                FreeRtos::delay_ms(FRAME_MS);
+            }
+        })
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    Ok(())
+}
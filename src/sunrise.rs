@@ -0,0 +1,134 @@
+use crate::{
+    alarm::Alarm,
+    lamp::{self, Color},
+};
+use chrono::{DateTime, Duration, TimeZone};
+use std::sync::{Arc, Mutex};
+
+/// Longest sunrise, in minutes, that can be configured before an alarm.
+pub const MAX_SUNRISE_MINUTES: u16 = 120;
+
+/// Color temperature, in kelvin, of the first light: a deep red glow.
+const START_KELVIN: f32 = 1000.0;
+
+/// Color temperature, in kelvin, reached at the alarm time: a bright warm white.
+const END_KELVIN: f32 = 4000.0;
+
+/// Type alias for the shared [Dawn].
+/// This is an ´Arc<Mutex<>>´ to ensure thread safety and shared access to the wake-up light state.
+pub type Sunrise = Arc<Mutex<Dawn>>;
+
+/// Decides when the lamp simulates a sunrise before an alarm.
+///
+/// Like the [crate::alarm::Scheduler], it holds no hardware: the main loop feeds it
+/// the current time with [Dawn::tick] and the LED strip renders [Dawn::progress].
+#[derive(Debug, Default)]
+pub struct Dawn {
+    progress: Option<f32>,
+    cancelled: bool,
+}
+
+impl Dawn {
+    /// Returns how far the sunrise is, from 0 (first light) to 1 (full light), or `None`
+    /// if no sunrise is showing.
+    pub fn progress(&self) -> Option<f32> {
+        self.progress.filter(|_| !self.cancelled)
+    }
+
+    /// Turns off the running sunrise until the next alarm.
+    ///
+    /// # Returns
+    /// `true` if a sunrise was showing.
+    pub fn cancel(&mut self) -> bool {
+        let showing = self.progress().is_some();
+        self.cancelled = self.progress.is_some();
+
+        showing
+    }
+
+    /// Advances the sunrise to `now`.
+    ///
+    /// The light rises over the `minutes` before the next enabled alarm, then stays
+    /// at full light while the alarm rings.
+    ///
+    /// # Parameters
+    /// - `now`: The current time, in the timezone the alarms are set in.
+    /// - `alarms`: The alarms from the settings.
+    /// - `minutes`: The length of the sunrise, `0` disables it.
+    /// - `ringing`: Whether an alarm is ringing.
+    ///
+    /// # Returns
+    /// `true` if the sunrise started during this tick.
+    pub fn tick<Tz: TimeZone>(
+        &mut self,
+        now: &DateTime<Tz>,
+        alarms: &[Alarm],
+        minutes: u16,
+        ringing: bool,
+    ) -> bool {
+        let window = Duration::minutes(minutes.into());
+
+        let progress = if minutes == 0 {
+            None
+        } else if ringing {
+            Some(1.0)
+        } else {
+            until_next_alarm(now, alarms)
+                .filter(|left| *left <= window)
+                .map(|left| 1.0 - left.num_seconds() as f32 / window.num_seconds() as f32)
+        };
+
+        // A cancelled sunrise stays off until it is over
+        if progress.is_none() {
+            self.cancelled = false;
+        }
+
+        let started = self.progress.is_none() && progress.is_some();
+        self.progress = progress;
+
+        started && !self.cancelled
+    }
+}
+
+/// Returns the time left until the next enabled alarm rings, within a day.
+fn until_next_alarm<Tz: TimeZone>(now: &DateTime<Tz>, alarms: &[Alarm]) -> Option<Duration> {
+    let today = now.date_naive();
+    let days = [Some(today), today.succ_opt()];
+
+    alarms
+        .iter()
+        .filter(|alarm| alarm.enabled)
+        .flat_map(|alarm| {
+            days.iter().flatten().filter_map(|day| {
+                let local = day.and_hms_opt(alarm.hour.into(), alarm.minute.into(), 0)?;
+                now.timezone().from_local_datetime(&local).earliest()
+            })
+        })
+        .map(|at| at.to_utc() - now.to_utc())
+        .filter(|left| *left > Duration::zero())
+        .min()
+}
+
+/// Computes the colors of the strip for a sunrise.
+///
+/// The light grows on the perceptual brightness curve, so it brightens evenly to the
+/// eye, and shifts from red through orange to warm white, like the sky at dawn.
+///
+/// # Parameters
+/// - `progress`: How far the sunrise is, from 0 to 1, as returned by [Dawn::progress].
+/// - `leds`: The number of LEDs on the strip.
+///
+/// # Returns
+/// One [Color] per LED.
+pub fn frame(progress: f32, leds: u16) -> Vec<Color> {
+    let progress = progress.clamp(0.0, 1.0);
+    let kelvin = START_KELVIN + (END_KELVIN - START_KELVIN) * progress;
+    let level = lamp::perceived_level((progress * 100.0).round() as u8);
+
+    vec![Color::from_kelvin(kelvin as u16).dim(level); usize::from(leds)]
+}
+
+/// Creates the shared [Sunrise], with no sunrise showing.
+pub fn get_sunrise() -> Sunrise {
+    Arc::new(Mutex::new(Dawn::default()))
+}
@@ -1,41 +1,131 @@
-use crate::error::AppError;
-use chrono::{DateTime, Timelike, Utc};
+use crate::{
+    error::{self, AppError},
+    events::{Events, ServiceState},
+    health,
+    rtc::Rtc,
+};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, TimeZone, Timelike, Utc};
 use chrono_tz::{America, Tz};
-use esp_idf_svc::sntp::{EspSntp, SyncStatus};
-use std::time::SystemTime;
+use esp_idf_svc::{
+    hal::delay::FreeRtos,
+    sntp::{EspSntp, SntpConf, SyncStatus},
+    sys::{self, esp, esp_timer_get_time},
+};
+use serde::Serialize;
+use std::{
+    ptr,
+    sync::{
+        mpsc::{self, SyncSender, TrySendError},
+        Mutex, RwLock,
+    },
+    thread,
+    time::Duration,
+    time::SystemTime,
+};
 
 /// Type alias for the SNTP client using [EspSntp].
 pub type Sntp = EspSntp<'static>;
 
-/// Static constant representing the timezone.
-pub static TIMEZONE: Tz = America::Sao_Paulo;
+/// Timezone used until one is configured in the settings.
+pub const DEFAULT_TIMEZONE: Tz = America::Sao_Paulo;
+
+/// The timezone the time is displayed in, applied from the settings.
+static TIMEZONE: RwLock<Tz> = RwLock::new(DEFAULT_TIMEZONE);
+
+/// Time, in milliseconds, a source keeps its priority after its last update.
+const SOURCE_TIMEOUT_MS: u64 = 60_000;
+
+/// Time, in milliseconds, [init_sntp] waits for a synchronization before giving up.
+const SNTP_TIMEOUT_MS: u64 = 30_000;
+
+/// Interval, in milliseconds, at which [init_sntp] checks the synchronization.
+const SNTP_POLL_MS: u32 = 100;
+
+/// Stack size of the thread writing the synchronized time to the RTC.
+const RTC_STACK_SIZE: usize = 4096;
+
+/// Offset, in milliseconds, under which an offered time does not step the clock.
+const STEP_THRESHOLD_MS: i64 = 20;
+
+/// The sources the system time is set from, from the least to the most trusted.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeSource {
+    Rtc,
+    /// Set by hand from the shell, the buttons or the web interface.
+    Manual,
+    Sntp,
+    Gps,
+}
+
+impl TimeSource {
+    /// Whether the source follows an external reference, SNTP or GPS, rather than a
+    /// time kept or typed locally.
+    pub fn is_synchronized(self) -> bool {
+        matches!(self, TimeSource::Sntp | TimeSource::Gps)
+    }
+}
+
+/// The last time accepted from a [TimeSource].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reference {
+    pub source: TimeSource,
+    pub time: DateTime<Utc>,
+    /// The [uptime_ms] when the time was accepted.
+    pub uptime_ms: u64,
+}
+
+/// The source currently setting the system time, see [offer_time].
+static REFERENCE: Mutex<Option<Reference>> = Mutex::new(None);
+
+/// Returns the timezone the time is displayed in.
+pub fn timezone() -> Tz {
+    *TIMEZONE.read().unwrap()
+}
+
+/// Changes the timezone the time is displayed in.
+///
+/// # Parameters
+/// - `timezone`: The new [Tz], e.g. parsed from the settings.
+pub fn set_timezone(timezone: Tz) {
+    *TIMEZONE.write().unwrap() = timezone;
+}
 
 /// Initializes and returns an SNTP client with the default configuration.
 ///
 /// This function creates and returns an instance of the [EspSntp] client, which is used
 /// to synchronize the device's time with a network time server.
 ///
+/// # Parameters
+/// - `on_sync`: Called from the SNTP task with the time since the epoch after each
+///   successful synchronization.
+///
 /// # Returns
 /// - `Ok(EspSntp)`: The successfully created SNTP client instance.
 /// - `Err(AppError)`: If there is an error during the SNTP client creation.
 ///
 /// # Example
 /// ```rust
-/// let sntp = get_sntp().expect("Failed to initialize SNTP client");
+/// let sntp = get_sntp(|_| log::info!("Synced")).expect("Failed to initialize SNTP client");
 /// ```
-pub fn get_sntp() -> Result<EspSntp<'static>, AppError> {
-    Ok(EspSntp::new_default()?)
+pub fn get_sntp(
+    on_sync: impl FnMut(Duration) + Send + 'static,
+) -> Result<EspSntp<'static>, AppError> {
+    Ok(EspSntp::new_with_callback(&SntpConf::default(), on_sync)?)
 }
 
 /// Synchronizes the device's time with an SNTP server.
 ///
-/// This function blocks the execution until the time synchronization is completed.
+/// This function blocks the execution until the time synchronization is completed, for at
+/// most [SNTP_TIMEOUT_MS]. After a timeout the client keeps polling the server in the
+/// background, and the time is set whenever it answers.
 ///
 /// # Parameters
 /// - `sntp`: A reference to the [Sntp] client that manages the synchronization process.
 ///
 /// # Returns
-/// `Ok(())` if the synchronization is successful, or an [AppError] if an error occurs.
+/// `Ok(())` if the synchronization is successful, or an [AppError::Sntp] if the
+/// server did not answer in time.
 ///
 /// # Example
 /// ```rust
@@ -44,15 +134,228 @@ pub fn get_sntp() -> Result<EspSntp<'static>, AppError> {
 /// ```
 pub fn init_sntp(sntp: &Sntp) -> Result<(), AppError> {
     log::info!("Synchronizing with SNTP Server");
-    while sntp.get_sync_status() != SyncStatus::Completed {}
+    let start = uptime_ms();
+    while sntp.get_sync_status() != SyncStatus::Completed {
+        if uptime_ms() - start >= SNTP_TIMEOUT_MS {
+            return Err(AppError::Sntp(format!(
+                "no answer from the SNTP server within {} s",
+                SNTP_TIMEOUT_MS / 1000
+            )));
+        }
+        health::beat();
+        FreeRtos::delay_ms(SNTP_POLL_MS);
+    }
     log::info!("Time Sync Completed");
 
     Ok(())
 }
 
+/// Returns the current time in the configured [timezone].
+pub fn now() -> DateTime<Tz> {
+    let now_utc: DateTime<Utc> = SystemTime::now().into();
+
+    now_utc.with_timezone(&timezone())
+}
+
+/// Sets the system time, e.g. from an external time source.
+///
+/// # Parameters
+/// - `time`: The current time, in UTC.
+///
+/// # Returns
+/// `Ok(())` if the time was set, or an [AppError] if the system refused it.
+pub fn set_system_time(time: DateTime<Utc>) -> Result<(), AppError> {
+    let timeval = sys::timeval {
+        tv_sec: time.timestamp() as _,
+        tv_usec: time.timestamp_subsec_micros() as _,
+    };
+
+    esp!(unsafe { sys::settimeofday(&timeval, ptr::null()) })?;
+
+    Ok(())
+}
+
+/// Offers a time from a source, setting the system time unless a more trusted source
+/// updated it within the last [SOURCE_TIMEOUT_MS].
+///
+/// The priority is GPS, then SNTP, then a time set by hand, then the RTC. SNTP steps the clock itself before
+/// offering its time, so a rejected offer restores the time of the current source.
+///
+/// # Parameters
+/// - `source`: The [TimeSource] the time comes from.
+/// - `time`: The current time according to the source.
+///
+/// # Returns
+/// `true` if the time was accepted, `false` if a more trusted source is in use, or an
+/// [AppError] if the system time cannot be set.
+///
+/// # Example
+/// ```rust
+/// if offer_time(TimeSource::Gps, fix)? {
+///     log::info!("Time set from GPS");
+/// }
+/// ```
+pub fn offer_time(source: TimeSource, time: DateTime<Utc>) -> Result<bool, AppError> {
+    let mut reference = REFERENCE.lock().unwrap();
+    let uptime_ms = uptime_ms();
+
+    if let Some(current) = reference.as_ref() {
+        let elapsed_ms = uptime_ms - current.uptime_ms;
+        if current.source > source && elapsed_ms < SOURCE_TIMEOUT_MS {
+            set_system_time(current.time + TimeDelta::milliseconds(elapsed_ms as i64))?;
+            return Ok(false);
+        }
+    }
+
+    let offset = time - Utc::now();
+    if offset.num_milliseconds().abs() >= STEP_THRESHOLD_MS {
+        set_system_time(time)?;
+    }
+    *reference = Some(Reference {
+        source,
+        time,
+        uptime_ms,
+    });
+
+    Ok(true)
+}
+
+/// Returns the last time accepted by [offer_time], if any.
+pub fn reference() -> Option<Reference> {
+    *REFERENCE.lock().unwrap()
+}
+
+/// Returns the source that last set the system time, if any.
+pub fn time_source() -> Option<TimeSource> {
+    reference().map(|reference| reference.source)
+}
+
+/// Whether the system time was last set by SNTP or GPS, see [TimeSource::is_synchronized].
+pub fn is_synchronized() -> bool {
+    time_source().is_some_and(TimeSource::is_synchronized)
+}
+
+/// Sets the system time by hand, from a local date and time in the configured [timezone].
+///
+/// The time stays unsynchronized until SNTP or GPS takes over, see [offer_time].
+///
+/// # Parameters
+/// - `local`: The date and time typed by the user.
+///
+/// # Returns
+/// The time set, or an [AppError::BadRequest] if it does not exist in the timezone
+/// (skipped by a DST change) or SNTP or GPS set the time within the last minute.
+///
+/// # Example
+/// ```rust
+/// set_manual_time(NaiveDate::from_ymd_opt(2026, 10, 18)?.and_hms_opt(14, 30, 0)?)?;
+/// ```
+pub fn set_manual_time(local: NaiveDateTime) -> Result<DateTime<Tz>, AppError> {
+    let time = timezone()
+        .from_local_datetime(&local)
+        .earliest()
+        .ok_or_else(|| AppError::BadRequest(format!("{local} does not exist in {}", timezone())))?;
+
+    if !offer_time(TimeSource::Manual, time.to_utc())? {
+        return Err(AppError::BadRequest(
+            "the time is kept by SNTP or GPS, it cannot be set by hand".to_string(),
+        ));
+    }
+    log::info!("Time set by hand to {time}");
+
+    Ok(time)
+}
+
+/// Returns the callback passed to [get_sntp], offering each synchronized time and
+/// asking the RTC writer to store it once accepted.
+///
+/// The callback runs in the lwIP task, so it never waits: the RTC write, which waits
+/// for the next second and blocks on the shared I2C bus, runs on its own `rtc` thread.
+///
+/// # Parameters
+/// - `rtc`: The [Rtc], if one is fitted.
+/// - `events`: The [Events] hub publishing the time source and the RTC state.
+///
+/// # Returns
+/// The callback, or an [AppError] if the RTC writer thread cannot be spawned.
+pub fn on_sntp_sync(
+    rtc: Option<Rtc>,
+    events: Events,
+) -> Result<impl FnMut(Duration) + Send + 'static, AppError> {
+    let writer = rtc
+        .map(|rtc| start_rtc_writer(rtc, events.clone()))
+        .transpose()?;
+
+    Ok(move |since_epoch| {
+        let time = DateTime::<Utc>::from(SystemTime::UNIX_EPOCH + since_epoch);
+        match offer_time(TimeSource::Sntp, time) {
+            Ok(true) => events.update(|s| {
+                s.time_source = Some(TimeSource::Sntp);
+                s.services.time = ServiceState::Running;
+            }),
+            Ok(false) => {
+                log::info!("SNTP time ignored, a more trusted source is in use");
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to apply SNTP time: {:#?}", e);
+                error::record(&e);
+                return;
+            }
+        }
+
+        // A write already pending stores the same time, so a full queue is fine
+        if let Some(Err(TrySendError::Disconnected(()))) =
+            writer.as_ref().map(|writer| writer.try_send(()))
+        {
+            log::warn!("RTC writer stopped, the RTC is not updated");
+        }
+    })
+}
+
+/// Spawns the thread writing the system time to the RTC each time it is asked to.
+///
+/// # Parameters
+/// - `rtc`: The [Rtc] to write.
+/// - `events`: The [Events] hub publishing the RTC state.
+///
+/// # Returns
+/// The sender asking for a write, or an [AppError] if the thread cannot be spawned.
+fn start_rtc_writer(rtc: Rtc, events: Events) -> Result<SyncSender<()>, AppError> {
+    let (writes, receiver) = mpsc::sync_channel::<()>(1);
+
+    thread::Builder::new()
+        .name("rtc".to_string())
+        .stack_size(RTC_STACK_SIZE)
+        .spawn(move || {
+            let _heartbeat = health::track();
+            while receiver.recv().is_ok() {
+                health::beat();
+                match rtc.store_system_time() {
+                    Ok(status) => {
+                        log::info!("RTC updated, it drifted {:?} s", status.drift_seconds);
+                        events.update(|s| s.rtc = Some(status));
+                    }
+                    Err(e) => {
+                        log::error!("Failed to update the RTC: {:#?}", e);
+                        error::record(&e);
+                    }
+                }
+            }
+        })
+        .map_err(|e| AppError::Thread(e.to_string()))?;
+
+    Ok(writes)
+}
+
+/// Returns the time elapsed since boot, in milliseconds, unaffected by clock changes.
+pub fn uptime_ms() -> u64 {
+    (unsafe { esp_timer_get_time() } / 1000) as u64
+}
+
 /// Retrieves the current time formatted as a vector of digits representing the hour and minute.
 ///
-/// This function converts the current UTC time to the [TIMEZONE],
+/// This function converts the current UTC time to the configured [timezone],
 /// and then extracts the hour and minute components as a vector of 4 digits.
 ///
 /// # Returns
@@ -63,8 +366,7 @@ pub fn init_sntp(sntp: &Sntp) -> Result<(), AppError> {
 /// let time = get_time();
 /// ```
 pub fn get_time() -> Vec<u8> {
-    let now_utc: DateTime<Utc> = SystemTime::now().into();
-    let now = now_utc.with_timezone(&TIMEZONE);
+    let now = now();
     let hour = now.hour();
     let minute = now.minute();
 
@@ -1,5 +1,16 @@
-/// A lookup table for displaying digits (0-9) on a 7-segment display.
-pub const DISPLAY_DIGIT: [u8; 10] = [
+use serde_json::Value;
+
+/// Version of the running firmware, taken from the crate version.
+pub const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Short identifier of the hardware model.
+pub const MODEL: &str = "esp32-tm1637";
+
+/// Human-readable name of the device.
+pub const MODEL_NAME: &str = "ESP Interstellar Clock";
+
+/// A lookup table for displaying hexadecimal digits (0-9, A-F) on a 7-segment display.
+pub const DISPLAY_DIGIT: [u8; 16] = [
     0b00111111, // 0
     0b00000110, // 1
     0b01011011, // 2
@@ -10,8 +21,84 @@ pub const DISPLAY_DIGIT: [u8; 10] = [
     0b00000111, // 7
     0b01111111, // 8
     0b01101111, // 9
+    0b01110111, // A
+    0b01111100, // b
+    0b00111001, // C
+    0b01011110, // d
+    0b01111001, // E
+    0b01110001, // F
 ];
 
+/// Returns the 7-segment pattern of a character, or a blank digit if it cannot be shown.
+///
+/// Digits and most letters are supported, letters keep their case where both forms
+/// are readable (`b`/`B` both give `b`, but `c` and `C` differ).
+pub fn char_segments(c: char) -> u8 {
+    match c {
+        '0'..='9' => DISPLAY_DIGIT[c as usize - '0' as usize],
+        'A' | 'a' => 0b01110111,
+        'B' | 'b' => 0b01111100,
+        'C' => 0b00111001,
+        'c' => 0b01011000,
+        'D' | 'd' => 0b01011110,
+        'E' | 'e' => 0b01111001,
+        'F' | 'f' => 0b01110001,
+        'G' => 0b00111101,
+        'g' => 0b01101111,
+        'H' => 0b01110110,
+        'h' => 0b01110100,
+        'I' | 'l' => 0b00110000,
+        'i' => 0b00000100,
+        'J' | 'j' => 0b00011110,
+        'L' => 0b00111000,
+        'N' | 'n' => 0b01010100,
+        'O' => 0b00111111,
+        'o' => 0b01011100,
+        'P' | 'p' => 0b01110011,
+        'Q' | 'q' => 0b01100111,
+        'R' | 'r' => 0b01010000,
+        'S' | 's' => 0b01101101,
+        'T' | 't' => 0b01111000,
+        'U' => 0b00111110,
+        'u' => 0b00011100,
+        'Y' | 'y' => 0b01101110,
+        'Z' | 'z' => 0b01011011,
+        '-' => 0b01000000,
+        '_' => 0b00001000,
+        '°' => 0b01100011,
+        _ => 0,
+    }
+}
+
+/// Converts up to 4 characters of text into a frame, see [char_segments].
+///
+/// A `:` lights the colon after the second digit instead of taking a position,
+/// characters past the fourth digit are dropped.
+///
+/// # Example
+/// ```rust
+/// assert_eq!(text_frame("AL1"), [0b01110111, 0b00111000, 0b00000110, 0]);
+/// assert_eq!(text_frame("07:30")[1], DISPLAY_DIGIT[7] | 0b10000000);
+/// ```
+pub fn text_frame(text: &str) -> [u8; 4] {
+    let mut frame = [0; 4];
+    let mut position = 0;
+
+    for c in text.chars() {
+        if c == ':' {
+            frame[1] |= 0b10000000;
+            continue;
+        }
+        if position == frame.len() {
+            break;
+        }
+        frame[position] |= char_segments(c);
+        position += 1;
+    }
+
+    frame
+}
+
 /// Extracts the digits from the URL query string after the "?" symbol.
 ///
 /// This function finds the query string in the URL, extracts the part after the "?" symbol,
@@ -43,3 +130,72 @@ pub fn find_digits_in_url(url: &str) -> Vec<u8> {
     // If `?`` is not found, return an empty vector
     Vec::new()
 }
+
+/// Merges a partial JSON `patch` into `target`, as done for `PATCH` requests.
+///
+/// Objects are merged field by field, recursively, so `{"lamp": {"brightness": 80}}`
+/// keeps the other fields of `lamp`; any other value, arrays included, replaces the
+/// one in `target`.
+pub fn merge_json(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    Some(field) => merge_json(field, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lamp::Lamp;
+    use serde_json::json;
+
+    #[test]
+    fn merge_keeps_the_nested_siblings() {
+        let mut value = json!({
+            "hostname": "clock",
+            "lamp": serde_json::to_value(Lamp::default()).unwrap(),
+            "alarms": [{"hour": 7}, {"hour": 8}],
+        });
+        merge_json(
+            &mut value,
+            json!({"lamp": {"brightness": 80}, "alarms": [{"hour": 9}]}),
+        );
+
+        let lamp: Lamp = serde_json::from_value(value["lamp"].clone()).unwrap();
+        assert_eq!(
+            lamp,
+            Lamp {
+                brightness: 80,
+                ..Lamp::default()
+            }
+        );
+        assert_eq!(value["hostname"], "clock");
+        // Arrays are replaced as a whole
+        assert_eq!(value["alarms"], json!([{"hour": 9}]));
+    }
+
+    #[test]
+    fn merge_replaces_other_values() {
+        let mut value = json!({"location": null, "a": {"b": {"c": 1, "d": 2}}});
+        merge_json(
+            &mut value,
+            json!({"location": {"latitude": 51.5}, "a": {"b": {"c": 3}}, "e": true}),
+        );
+        assert_eq!(
+            value,
+            json!({"location": {"latitude": 51.5}, "a": {"b": {"c": 3, "d": 2}}, "e": true})
+        );
+
+        merge_json(&mut value, json!({"a": null}));
+        assert_eq!(value["a"], Value::Null);
+    }
+}
@@ -1,32 +1,67 @@
-use crate::error::AppError;
+use crate::{error::AppError, health, settings::Settings, time};
 use esp_idf_svc::{
     eventloop::EspSystemEventLoop,
-    hal::modem::Modem,
+    hal::{delay::FreeRtos, modem::Modem},
     nvs::EspDefaultNvsPartition,
+    sys::{esp, esp_wifi_restore, esp_wifi_set_mode, wifi_mode_t_WIFI_MODE_STA},
     wifi::{
-        AuthMethod, BlockingWifi, ClientConfiguration, Configuration as WifiConfiguration, EspWifi,
+        AccessPointConfiguration, AuthMethod, BlockingWifi, ClientConfiguration,
+        Configuration as WifiConfiguration, EspWifi,
     },
 };
-use std::str::FromStr;
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 /// Type alias for a [BlockingWifi] instance.
-type Wifi = BlockingWifi<EspWifi<'static>>;
+pub type Wifi = BlockingWifi<EspWifi<'static>>;
+
+/// SSID of the open access point started while no network is configured or reachable.
+pub const PROVISIONING_SSID: &str = "Interstellar-Clock-Setup";
+
+/// Interval, in milliseconds, between checks of the connection once the IP is up.
+const CONNECT_POLL_MS: u32 = 100;
+
+/// Time, in milliseconds, allowed to the connection to settle once the IP is up.
+const CONNECT_TIMEOUT_MS: u64 = 10_000;
 
-/// The SSID of the Wi-Fi network to connect to.
-pub const WIFI_SSID: &str = "";
+/// Interval, in milliseconds, between two attempts to join the configured network while
+/// the fallback access point is up, see [rejoin].
+const REJOIN_INTERVAL_MS: u64 = 30_000;
 
-/// The password of the WiFi network.
-const WIFI_PASSWORD: &str = "";
+/// Whether the provisioning access point is up, see [is_access_point].
+static ACCESS_POINT: AtomicBool = AtomicBool::new(false);
+
+/// Whether the clock serves the open access point, to be provisioned or while the
+/// configured network cannot be joined, see [start_access_point].
+///
+/// Anyone in range can join the access point, so the API refuses most requests over
+/// it until an API token is configured.
+pub fn is_access_point() -> bool {
+    ACCESS_POINT.load(Ordering::Relaxed)
+}
+
+/// Whether the clock has no network configured and must start in provisioning mode.
+///
+/// In provisioning mode the clock starts an open access point named [PROVISIONING_SSID],
+/// serving the web interface at `http://192.168.71.1` so the Wi-Fi credentials can be entered.
+pub fn is_provisioning(settings: &Settings) -> bool {
+    settings.wifi_ssid.is_empty()
+}
 
 /// Creates a [BlockingWifi] instance for connecting to a Wi-Fi network.
 ///
 /// This function creates and prepares the Wi-Fi instance.
 /// The created instance can be used later for initializing and
-/// connecting to a Wi-Fi network.
+/// connecting to a Wi-Fi network. When no SSID is configured, it is
+/// set up as the provisioning access point instead, see [is_provisioning].
 ///
 /// # Arguments
 ///
 /// * `modem` - The modem instance used for networking.
+/// * `nvs` - The default NVS partition, used by the driver to store its calibration data.
+/// * `settings` - The [Settings] holding the SSID and password of the network.
 ///
 /// # Returns
 ///
@@ -35,19 +70,28 @@ const WIFI_PASSWORD: &str = "";
 ///
 /// # Example
 /// ```rust
-/// get_wifi(modem).expect("Error creating wifi");
+/// get_wifi(modem, nvs.clone(), &settings).expect("Error creating wifi");
 /// ```
-pub fn get_wifi(modem: Modem) -> Result<Wifi, AppError> {
+pub fn get_wifi(
+    modem: Modem,
+    nvs: EspDefaultNvsPartition,
+    settings: &Settings,
+) -> Result<Wifi, AppError> {
     let sysloop = EspSystemEventLoop::take()?;
-    let nvs = EspDefaultNvsPartition::take()?;
 
     let mut wifi = BlockingWifi::wrap(EspWifi::new(modem, sysloop.clone(), Some(nvs))?, sysloop)?;
 
+    if is_provisioning(settings) {
+        wifi.set_configuration(&WifiConfiguration::AccessPoint(access_point_configuration()))?;
+
+        return Ok(wifi);
+    }
+
     wifi.set_configuration(&WifiConfiguration::Client(ClientConfiguration {
-        ssid: heapless::String::from_str(WIFI_SSID).unwrap(),
+        ssid: heapless::String::from_str(&settings.wifi_ssid).unwrap(),
         bssid: None,
         auth_method: AuthMethod::None,
-        password: heapless::String::from_str(WIFI_PASSWORD).unwrap(),
+        password: heapless::String::from_str(&settings.wifi_password).unwrap(),
         channel: None,
         ..Default::default()
     }))?;
@@ -55,12 +99,21 @@ pub fn get_wifi(modem: Modem) -> Result<Wifi, AppError> {
     Ok(wifi)
 }
 
+/// Returns the configuration of the open provisioning access point.
+fn access_point_configuration() -> AccessPointConfiguration {
+    AccessPointConfiguration {
+        ssid: heapless::String::from_str(PROVISIONING_SSID).unwrap(),
+        auth_method: AuthMethod::None,
+        ..Default::default()
+    }
+}
+
 /// Initializes and connects the Wi-Fi to the specified network.
 ///
 /// This function starts the Wi-Fi, attempts to establish a connection to the
 /// network using the provided configuration, and waits for the network interface
 /// to be up. It checks the connection status repeatedly until the connection is
-/// successfully established.
+/// successfully established. In provisioning mode, it only starts the access point.
 ///
 /// # Arguments
 ///
@@ -73,19 +126,153 @@ pub fn get_wifi(modem: Modem) -> Result<Wifi, AppError> {
 ///
 /// # Example
 /// ```rust
-/// let mut wifi = get_wifi(modem).expect("Error creating wifi");
+/// let mut wifi = get_wifi(modem, nvs.clone(), &settings).expect("Error creating wifi");
 /// init_wifi(&mut wifi).expect("Error initializing wifi");
 /// ```
 pub fn init_wifi(wifi: &mut Wifi) -> Result<(), AppError> {
+    // Each blocking call of the driver can take up to 15 s, so the watchdog of the main
+    // task, which boots the network, is fed between them
+    health::beat();
     wifi.start()?;
-    wifi.connect()?;
-    wifi.wait_netif_up()?;
+    health::beat();
+
+    if let WifiConfiguration::AccessPoint(config) = wifi.get_configuration()? {
+        ACCESS_POINT.store(true, Ordering::Relaxed);
+        wifi.wait_netif_up()?;
+        health::beat();
+        log::warn!(
+            "No Wi-Fi configured, provisioning access point `{}` started",
+            config.ssid
+        );
 
+        return Ok(());
+    }
+
+    wifi.connect()
+        .map_err(|e| AppError::Wifi(format!("cannot join the network: {e}")))?;
+    health::beat();
+    wifi.wait_netif_up()
+        .map_err(|e| AppError::Wifi(format!("no IP address from the network: {e}")))?;
+    health::beat();
+
+    let start = time::uptime_ms();
     while !wifi.is_connected()? {
-        let config = wifi.get_configuration()?;
-        log::info!("Waiting for connection {:?}", config);
+        if time::uptime_ms() - start >= CONNECT_TIMEOUT_MS {
+            return Err(AppError::Wifi(format!(
+                "not connected within {} s",
+                CONNECT_TIMEOUT_MS / 1000
+            )));
+        }
+        log::info!("Waiting for connection");
+        health::beat();
+        FreeRtos::delay_ms(CONNECT_POLL_MS);
     }
     log::info!("Wifi Connected!");
 
     Ok(())
 }
+
+/// Starts the provisioning access point after the configured network could not be joined.
+///
+/// The web interface stays reachable at `http://192.168.71.1`, to set the time by hand.
+/// The station keeps the configured network next to the access point, and [rejoin]
+/// tries it again until it answers.
+///
+/// # Arguments
+///
+/// * `wifi` - A mutable reference to the [Wifi] instance that failed to connect.
+///
+/// # Returns
+///
+/// * `Ok(())` if the access point is up.
+/// * `Err(AppError)` if the driver fails to start it.
+pub fn start_access_point(wifi: &mut Wifi) -> Result<(), AppError> {
+    if let Err(e) = wifi.stop() {
+        log::warn!("Failed to stop Wi-Fi: {:?}", e);
+    }
+
+    health::beat();
+    let WifiConfiguration::Client(client) = wifi.get_configuration()? else {
+        return Err(AppError::Wifi(
+            "no network configured to join again".to_string(),
+        ));
+    };
+    ACCESS_POINT.store(true, Ordering::Relaxed);
+    wifi.set_configuration(&WifiConfiguration::Mixed(
+        client,
+        access_point_configuration(),
+    ))?;
+    wifi.start()
+        .map_err(|e| AppError::Wifi(format!("cannot start the access point: {e}")))?;
+    health::beat();
+
+    // The station is down, so only the interface of the access point is waited for
+    let start = time::uptime_ms();
+    while !wifi.wifi().ap_netif().is_up()? {
+        if time::uptime_ms() - start >= CONNECT_TIMEOUT_MS {
+            return Err(AppError::Wifi(format!(
+                "access point not up within {} s",
+                CONNECT_TIMEOUT_MS / 1000
+            )));
+        }
+        health::beat();
+        FreeRtos::delay_ms(CONNECT_POLL_MS);
+    }
+    log::warn!("Wi-Fi unreachable, access point `{PROVISIONING_SSID}` started");
+
+    Ok(())
+}
+
+/// Joins the configured network again while the fallback access point is up.
+///
+/// Called from the main loop, it never blocks: every [REJOIN_INTERVAL_MS] it asks the
+/// driver to connect, and once the station has an IP address the access point is
+/// stopped.
+///
+/// # Arguments
+///
+/// * `wifi` - A mutable reference to the [Wifi] instance started by [start_access_point].
+/// * `last_attempt_ms` - The [time::uptime_ms] of the last attempt, updated on each one.
+///
+/// # Returns
+///
+/// * `Ok(true)` once the network is joined and the access point stopped.
+/// * `Ok(false)` while the network is not joined, or without a fallback access point.
+/// * `Err(AppError)` if the driver fails to report or change its state.
+pub fn rejoin(wifi: &mut Wifi, last_attempt_ms: &mut u64) -> Result<bool, AppError> {
+    if !is_access_point() {
+        return Ok(false);
+    }
+
+    if wifi.is_connected()? && wifi.wifi().sta_netif().is_up()? {
+        esp!(unsafe { esp_wifi_set_mode(wifi_mode_t_WIFI_MODE_STA) })?;
+        ACCESS_POINT.store(false, Ordering::Relaxed);
+        log::info!("Configured network joined, access point `{PROVISIONING_SSID}` stopped");
+
+        return Ok(true);
+    }
+
+    let now = time::uptime_ms();
+    if now - *last_attempt_ms >= REJOIN_INTERVAL_MS {
+        *last_attempt_ms = now;
+        log::info!("Trying to join the configured network again");
+        if let Err(e) = wifi.wifi_mut().connect() {
+            log::warn!("Failed to join the configured network: {e}");
+        }
+    }
+
+    Ok(false)
+}
+
+/// Erases the Wi-Fi configuration kept by the driver in its own NVS namespace.
+///
+/// # Returns
+///
+/// * `Ok(())` if the configuration is restored to the driver defaults.
+/// * `Err(AppError)` if the driver fails to erase it.
+pub fn erase_credentials() -> Result<(), AppError> {
+    esp!(unsafe { esp_wifi_restore() })?;
+    log::warn!("Wi-Fi credentials erased");
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Offset, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Maximum number of secondary zones kept in the settings.
+pub const MAX_ZONES: usize = 6;
+
+/// Seconds each zone stays on the display, its label first.
+pub const ZONE_SECONDS: i64 = 6;
+
+/// Seconds the label of a zone is shown before its time.
+pub const LABEL_SECONDS: i64 = 2;
+
+/// A secondary timezone shown by [crate::display::DisplayMode::World].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Zone {
+    /// Short name shown on the display before the time, e.g. `NYC`.
+    pub label: String,
+    /// IANA name of the timezone, e.g. `America/New_York`.
+    pub timezone: String,
+}
+
+impl Zone {
+    /// Returns the timezone, or `None` if its name is unknown.
+    pub fn tz(&self) -> Option<Tz> {
+        Tz::from_str(&self.timezone).ok()
+    }
+
+    /// Whether the label holds 2 to 4 ASCII letters or digits and the timezone is known.
+    ///
+    /// Letters the 7-segment digits cannot draw, such as `M` or `W`, are shown blank.
+    pub fn is_valid(&self) -> bool {
+        (2..=4).contains(&self.label.len())
+            && self.label.bytes().all(|c| c.is_ascii_alphanumeric())
+            && self.tz().is_some()
+    }
+}
+
+/// The current time in a [Zone], as listed in the status.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ZoneTime {
+    pub label: String,
+    pub timezone: String,
+    /// The local time, as `HH:MM`.
+    pub time: String,
+    /// The current offset from UTC, as `+05:30`, following the DST rules of the zone.
+    pub offset: String,
+}
+
+/// Formats an offset from UTC in seconds as `+HH:MM`.
+pub fn format_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.unsigned_abs() / 60;
+
+    format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Returns the current time in each of the zones, skipping the unknown ones.
+///
+/// # Parameters
+/// - `zones`: The secondary zones from the settings.
+/// - `now`: The current time.
+pub fn zone_times(zones: &[Zone], now: DateTime<Utc>) -> Vec<ZoneTime> {
+    zones
+        .iter()
+        .filter_map(|zone| {
+            let local = now.with_timezone(&zone.tz()?);
+            Some(ZoneTime {
+                label: zone.label.clone(),
+                timezone: zone.timezone.clone(),
+                time: local.format("%H:%M").to_string(),
+                offset: format_offset(local.offset().fix().local_minus_utc()),
+            })
+        })
+        .collect()
+}
+
+/// Returns the text of the world clock page at `now`.
+///
+/// The zones take turns every [ZONE_SECONDS], each showing its label for
+/// [LABEL_SECONDS] then its time as `HH:MM`, or ` H:MM` in 12-hour format.
+///
+/// # Parameters
+/// - `zones`: The secondary zones from the settings.
+/// - `now`: The current time.
+/// - `twelve_hour`: Whether the time is shown in 12-hour format.
+///
+/// # Returns
+/// The text to show, or `None` if there is no known zone.
+///
+/// # Example
+/// ```rust
+/// let frame = world::page_text(&settings.world_clock, now.to_utc(), false)
+///     .map(|text| utils::text_frame(&text));
+/// ```
+pub fn page_text(zones: &[Zone], now: DateTime<Utc>, twelve_hour: bool) -> Option<String> {
+    let zones: Vec<(&Zone, Tz)> = zones
+        .iter()
+        .filter_map(|zone| Some((zone, zone.tz()?)))
+        .collect();
+    if zones.is_empty() {
+        return None;
+    }
+
+    let seconds = now.timestamp();
+    let turn = seconds.div_euclid(ZONE_SECONDS) as usize % zones.len();
+    let (zone, tz) = zones[turn];
+    if seconds.rem_euclid(ZONE_SECONDS) < LABEL_SECONDS {
+        return Some(zone.label.clone());
+    }
+
+    let local = now.with_timezone(&tz);
+    Some(if twelve_hour {
+        format!("{:>2}:{:02}", local.hour12().1, local.minute())
+    } else {
+        format!("{:02}:{:02}", local.hour(), local.minute())
+    })
+}